@@ -0,0 +1,5 @@
+// Las migraciones se incluyen en el binario con `sqlx::migrate!`, así que se debe recompilar
+// cuando se agregue o cambie alguna.
+fn main() {
+    println!("cargo:rerun-if-changed=migrations");
+}
@@ -0,0 +1,19 @@
+-- Add up migration script here
+CREATE TABLE app_scopes (
+    id INTEGER PRIMARY KEY NOT NULL,
+    app_uuid TEXT NOT NULL,
+    scope TEXT CHECK(scope IN ("members:read", "members:write", "projects:read", "projects:write", "apps:admin")) NOT NULL,
+    CONSTRAINT fk_app_uuid FOREIGN KEY(app_uuid) REFERENCES apps(uuid) ON DELETE CASCADE,
+    UNIQUE(app_uuid, scope)
+);
+
+-- Las Apps que ya existían mantienen el acceso total que tenían antes de los permisos.
+INSERT INTO app_scopes(app_uuid, scope)
+SELECT apps.uuid, scopes.scope FROM apps
+CROSS JOIN (
+    SELECT "members:read" AS scope
+    UNION SELECT "members:write"
+    UNION SELECT "projects:read"
+    UNION SELECT "projects:write"
+    UNION SELECT "apps:admin"
+) AS scopes;
@@ -0,0 +1,3 @@
+-- Add down migration script here
+ALTER TABLE apps DROP COLUMN previous_api_token_expires_at;
+ALTER TABLE apps DROP COLUMN previous_api_token;
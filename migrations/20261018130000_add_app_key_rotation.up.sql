@@ -0,0 +1,3 @@
+-- Add up migration script here
+ALTER TABLE apps ADD COLUMN previous_api_token TEXT DEFAULT NULL;
+ALTER TABLE apps ADD COLUMN previous_api_token_expires_at TIMESTAMP DEFAULT NULL;
@@ -0,0 +1,4 @@
+-- Add down migration script here
+ALTER TABLE apps DROP COLUMN last_used_ip;
+ALTER TABLE apps DROP COLUMN last_used_at;
+ALTER TABLE apps DROP COLUMN expires_at;
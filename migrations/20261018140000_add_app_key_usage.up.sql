@@ -0,0 +1,4 @@
+-- Add up migration script here
+ALTER TABLE apps ADD COLUMN expires_at TIMESTAMP DEFAULT NULL;
+ALTER TABLE apps ADD COLUMN last_used_at TIMESTAMP DEFAULT NULL;
+ALTER TABLE apps ADD COLUMN last_used_ip TEXT DEFAULT NULL;
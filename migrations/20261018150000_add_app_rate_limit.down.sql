@@ -0,0 +1,2 @@
+-- Add down migration script here
+ALTER TABLE apps DROP COLUMN rate_limit;
@@ -0,0 +1,2 @@
+-- Add up migration script here
+ALTER TABLE apps ADD COLUMN rate_limit INTEGER DEFAULT NULL;
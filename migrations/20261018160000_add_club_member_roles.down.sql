@@ -0,0 +1,2 @@
+-- Add down migration script here
+ALTER TABLE club_members DROP COLUMN role;
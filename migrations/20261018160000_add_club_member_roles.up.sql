@@ -0,0 +1,2 @@
+-- Add up migration script here
+ALTER TABLE club_members ADD COLUMN role TEXT CHECK(role IN ("admin", "board", "member", "guest")) DEFAULT "member" NOT NULL;
@@ -0,0 +1,9 @@
+-- Add up migration script here
+CREATE TABLE magic_links (
+    token_hash TEXT PRIMARY KEY NOT NULL,
+    club_member_uuid TEXT NOT NULL,
+    expires_at TIMESTAMP NOT NULL,
+    used_at TIMESTAMP DEFAULT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    CONSTRAINT fk_club_member_uuid FOREIGN KEY(club_member_uuid) REFERENCES club_members(uuid) ON DELETE CASCADE
+);
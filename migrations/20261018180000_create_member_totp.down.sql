@@ -0,0 +1,3 @@
+-- Add down migration script here
+DROP TABLE member_recovery_codes;
+DROP TABLE member_totp;
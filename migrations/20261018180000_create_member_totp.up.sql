@@ -0,0 +1,18 @@
+-- Add up migration script here
+CREATE TABLE member_totp (
+    club_member_uuid TEXT PRIMARY KEY NOT NULL,
+    secret TEXT NOT NULL,
+    enabled_at TIMESTAMP DEFAULT NULL,
+    last_used_step INTEGER DEFAULT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    CONSTRAINT fk_club_member_uuid FOREIGN KEY(club_member_uuid) REFERENCES club_members(uuid) ON DELETE CASCADE
+);
+
+CREATE TABLE member_recovery_codes (
+    id INTEGER PRIMARY KEY NOT NULL,
+    club_member_uuid TEXT NOT NULL,
+    code_hash TEXT NOT NULL,
+    used_at TIMESTAMP DEFAULT NULL,
+    CONSTRAINT fk_club_member_uuid FOREIGN KEY(club_member_uuid) REFERENCES club_members(uuid) ON DELETE CASCADE,
+    UNIQUE(club_member_uuid, code_hash)
+);
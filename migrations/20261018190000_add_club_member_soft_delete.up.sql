@@ -0,0 +1,2 @@
+-- Add up migration script here
+ALTER TABLE club_members ADD COLUMN deleted_at TIMESTAMP DEFAULT NULL;
@@ -0,0 +1,3 @@
+-- Add down migration script here
+ALTER TABLE projects DROP COLUMN deleted_at;
+ALTER TABLE projects DROP COLUMN archived;
@@ -0,0 +1,3 @@
+-- Add up migration script here
+ALTER TABLE projects ADD COLUMN archived BOOLEAN NOT NULL DEFAULT FALSE;
+ALTER TABLE projects ADD COLUMN deleted_at TIMESTAMP DEFAULT NULL;
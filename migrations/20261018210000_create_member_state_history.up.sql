@@ -0,0 +1,14 @@
+-- Add up migration script here
+CREATE TABLE member_state_history (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    club_member_uuid TEXT NOT NULL,
+    from_state TEXT NOT NULL,
+    to_state TEXT NOT NULL,
+    overridden BOOLEAN NOT NULL DEFAULT FALSE,
+    changed_by_app TEXT NOT NULL,
+    changed_by_member TEXT DEFAULT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    CONSTRAINT fk_club_member_uuid FOREIGN KEY(club_member_uuid) REFERENCES club_members(uuid) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_member_state_history_member ON member_state_history(club_member_uuid);
@@ -0,0 +1,14 @@
+-- Add up migration script here
+CREATE TABLE project_state_history (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    project_uuid TEXT NOT NULL,
+    from_state TEXT NOT NULL,
+    to_state TEXT NOT NULL,
+    reason TEXT DEFAULT NULL,
+    changed_by_app TEXT NOT NULL,
+    changed_by_member TEXT DEFAULT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    CONSTRAINT fk_project_uuid FOREIGN KEY(project_uuid) REFERENCES projects(uuid) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_project_state_history_project ON project_state_history(project_uuid);
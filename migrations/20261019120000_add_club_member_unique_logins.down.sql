@@ -0,0 +1,6 @@
+-- Add down migration script here
+DROP INDEX club_members_github_id_unique;
+DROP INDEX club_members_github_unique;
+DROP INDEX club_members_email_unique;
+
+ALTER TABLE club_members DROP COLUMN github_id;
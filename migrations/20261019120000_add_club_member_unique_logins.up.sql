@@ -0,0 +1,6 @@
+-- Add up migration script here
+ALTER TABLE club_members ADD COLUMN github_id INTEGER DEFAULT NULL;
+
+CREATE UNIQUE INDEX club_members_email_unique ON club_members(email COLLATE NOCASE) WHERE deleted_at IS NULL;
+CREATE UNIQUE INDEX club_members_github_unique ON club_members(github COLLATE NOCASE) WHERE deleted_at IS NULL;
+CREATE UNIQUE INDEX club_members_github_id_unique ON club_members(github_id) WHERE deleted_at IS NULL;
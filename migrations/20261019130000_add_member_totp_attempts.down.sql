@@ -0,0 +1,4 @@
+-- Add down migration script here
+ALTER TABLE member_totp DROP COLUMN locked_until;
+ALTER TABLE member_totp DROP COLUMN failed_attempts;
+ALTER TABLE member_totp DROP COLUMN challenge_nonce;
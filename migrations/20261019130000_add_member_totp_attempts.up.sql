@@ -0,0 +1,4 @@
+-- Add up migration script here
+ALTER TABLE member_totp ADD COLUMN challenge_nonce TEXT DEFAULT NULL;
+ALTER TABLE member_totp ADD COLUMN failed_attempts INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE member_totp ADD COLUMN locked_until TIMESTAMP DEFAULT NULL;
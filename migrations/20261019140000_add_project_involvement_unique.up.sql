@@ -0,0 +1,7 @@
+-- Add up migration script here
+DELETE FROM project_involvement
+WHERE id NOT IN (
+    SELECT MIN(id) FROM project_involvement GROUP BY project_uuid, club_member_uuid
+);
+
+CREATE UNIQUE INDEX project_involvement_unique ON project_involvement(project_uuid, club_member_uuid);
@@ -0,0 +1,636 @@
+//! Herramienta de administración de la API del ExDev.
+//!
+//! Trabaja directamente sobre la base de datos, usando los mismos modelos que la API, así que no
+//! necesita que la API esté corriendo ni una llave. Sirve para las tareas que no tienen (o no
+//! deberían tener) una ruta, como aplicar las migraciones o crear la primera App:
+//!
+//! ```bash
+//! cargo run --bin exdev-admin -- app create --name "Panel" --scope apps:admin
+//! ```
+//!
+//! Cada comando tiene su ayuda con `--help`.
+
+use std::{fs, path::PathBuf, process::ExitCode};
+
+use anyhow::{bail, Context};
+use chrono::NaiveDateTime;
+use clap::{Args, Parser, Subcommand};
+use dotenv::dotenv;
+use exdev_api::{
+    database::{self, MIGRATOR},
+    key_controller, project_retention_from_env,
+    v1::{
+        models::{
+            auth::{AppModel, Scope},
+            club_member::ClubMemberModel,
+            project::{ProjectModel, ProjectState},
+        },
+        responders::errors::ApiError,
+        schemas::{auth::CreateAppSchema, club_member::CreateMemberSchema, validation::Validate},
+    },
+};
+use serde_json::{Map, Value};
+use sqlx::{migrate::Migrate, Column, Row, SqlitePool, TypeInfo, ValueRef};
+
+/// Administración de la API del ExDev.
+#[derive(Parser)]
+#[command(version)]
+struct Cli {
+    /// URL de la base de datos. Por defecto, la del archivo de ambiente.
+    #[arg(long, env = "DATABASE_URL", global = true)]
+    database_url: Option<String>,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Aplica o revierte las migraciones de la base de datos.
+    #[command(subcommand)]
+    Migrate(MigrateCommand),
+
+    /// Crea y revoca las Apps que consumen la API.
+    #[command(subcommand)]
+    App(AppCommand),
+
+    /// Agrega integrantes del club.
+    #[command(subcommand)]
+    Member(MemberCommand),
+
+    /// Revisa los proyectos del club.
+    #[command(subcommand)]
+    Project(ProjectCommand),
+
+    /// Exporta todas las tablas de la base de datos a un JSON, sin los secretos.
+    Dump {
+        /// Archivo donde se escribe el JSON. Por defecto, se imprime en la consola.
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        /// Incluye los secretos (hashes de las llaves, secretos de la verificación en dos pasos,
+        /// ...), por ejemplo para un respaldo completo. Guarda ese archivo con cuidado.
+        #[arg(long)]
+        include_secrets: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum MigrateCommand {
+    /// Aplica las migraciones pendientes, creando la base de datos si no existe.
+    Run,
+
+    /// Revierte migraciones ya aplicadas.
+    Rollback {
+        /// Versión en la que queda la base de datos; se revierten todas las posteriores. Por
+        /// defecto, solo se revierte la última migración aplicada.
+        #[arg(long)]
+        to: Option<i64>,
+    },
+}
+
+#[derive(Subcommand)]
+enum AppCommand {
+    /// Registra una nueva App e imprime su llave, que no se vuelve a mostrar.
+    Create(CreateAppArgs),
+
+    /// Revoca una App, de forma que su llave deja de funcionar.
+    Revoke {
+        /// UUID de la App.
+        id: String,
+    },
+}
+
+#[derive(Args)]
+struct CreateAppArgs {
+    /// Nombre de la App.
+    #[arg(long)]
+    name: String,
+
+    /// Descripción de la App.
+    #[arg(long)]
+    description: Option<String>,
+
+    /// Permiso de la App (`members:read`, `apps:admin`, ...). Se puede repetir. Por defecto, la
+    /// App solo puede leer.
+    #[arg(long = "scope")]
+    scopes: Vec<Scope>,
+
+    /// Fecha (UTC) desde la cual la llave deja de funcionar, como `2025-12-31T23:59:59`.
+    #[arg(long)]
+    expires_at: Option<NaiveDateTime>,
+
+    /// Peticiones por minuto que puede hacer la App. Por defecto, el límite de la API.
+    #[arg(long)]
+    rate_limit: Option<i64>,
+}
+
+#[derive(Subcommand)]
+enum MemberCommand {
+    /// Agrega un integrante.
+    Create {
+        /// Nombre del integrante.
+        #[arg(long)]
+        name: String,
+
+        /// Cumpleaños del integrante, como `AAAA-MM-DD`.
+        #[arg(long)]
+        birthday: Option<String>,
+
+        /// Email del integrante.
+        #[arg(long)]
+        email: Option<String>,
+
+        /// Nombre de usuario de GitHub del integrante.
+        #[arg(long)]
+        github: Option<String>,
+    },
+
+    /// Agrega los integrantes de un archivo JSON, con una lista de objetos con los mismos campos
+    /// que `POST /v1/members/create`. Si alguno no es válido, no se agrega ninguno.
+    Import {
+        /// Archivo JSON con los integrantes.
+        file: PathBuf,
+    },
+}
+
+#[derive(Subcommand)]
+enum ProjectCommand {
+    /// Lista los proyectos que no están eliminados.
+    List {
+        /// Solo los proyectos en este estado (`InProgress`, `Finished`, ...).
+        #[arg(long)]
+        state: Option<ProjectState>,
+
+        /// Incluye los proyectos archivados.
+        #[arg(long)]
+        archived: bool,
+    },
+
+    /// Elimina definitivamente los proyectos cuyo periodo de retención (`PROJECT_RETENTION_DAYS`)
+    /// ya pasó, junto con sus involucrados. Conviene ejecutarlo periódicamente, por ejemplo con
+    /// cron.
+    Purge,
+}
+
+#[actix_web::main]
+async fn main() -> ExitCode {
+    dotenv().ok();
+
+    let cli = Cli::parse();
+
+    match run(cli).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("Error: {err:#}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Ejecuta el comando pedido.
+async fn run(cli: Cli) -> anyhow::Result<()> {
+    let Some(database_url) = cli.database_url else {
+        bail!("No se ha seteado la URL de la base de datos (DATABASE_URL o --database-url).");
+    };
+
+    let pool = database::connect(&database_url)
+        .await
+        .context("No se pudo conectar con la base de datos")?;
+
+    match cli.command {
+        Command::Migrate(MigrateCommand::Run) => migrate(&pool).await,
+        Command::Migrate(MigrateCommand::Rollback { to }) => rollback(to, &pool).await,
+        Command::App(AppCommand::Create(args)) => create_app(args, &pool).await,
+        Command::App(AppCommand::Revoke { id }) => revoke_app(&id, &pool).await,
+        Command::Member(MemberCommand::Create {
+            name,
+            birthday,
+            email,
+            github,
+        }) => {
+            let member = CreateMemberSchema {
+                name,
+                birthday,
+                email,
+                github,
+            };
+            import_members(vec![member], &pool).await
+        }
+        Command::Member(MemberCommand::Import { file }) => {
+            let content = fs::read_to_string(&file)
+                .with_context(|| format!("No se pudo leer {}", file.display()))?;
+            let members = serde_json::from_str(&content)
+                .with_context(|| format!("{} no es una lista de integrantes", file.display()))?;
+            import_members(members, &pool).await
+        }
+        Command::Project(ProjectCommand::List { state, archived }) => {
+            list_projects(state, archived, &pool).await
+        }
+        Command::Project(ProjectCommand::Purge) => purge_projects(&pool).await,
+        Command::Dump {
+            output,
+            include_secrets,
+        } => dump(output, include_secrets, &pool).await,
+    }
+}
+
+/// Aplica las migraciones pendientes e imprime las que se aplicaron.
+async fn migrate(pool: &SqlitePool) -> anyhow::Result<()> {
+    let applied = database::migrate(pool)
+        .await
+        .context("No se pudieron aplicar las migraciones")?;
+
+    if applied.is_empty() {
+        println!("La base de datos está al día");
+    }
+
+    for migration in applied {
+        println!(
+            "Migración aplicada: {} ({})",
+            migration.version, migration.description
+        );
+    }
+
+    Ok(())
+}
+
+/// Revierte las migraciones aplicadas posteriores a `to`, o solo la última si no se indica.
+async fn rollback(to: Option<i64>, pool: &SqlitePool) -> anyhow::Result<()> {
+    let mut conn = pool.acquire().await?;
+    conn.ensure_migrations_table().await?;
+
+    let mut applied: Vec<i64> = conn
+        .list_applied_migrations()
+        .await?
+        .into_iter()
+        .map(|migration| migration.version)
+        .collect();
+    applied.sort_unstable();
+    drop(conn);
+
+    let target = match to {
+        Some(version) => version,
+        None if applied.is_empty() => bail!("No hay migraciones aplicadas"),
+        None => applied.iter().rev().nth(1).copied().unwrap_or(0),
+    };
+
+    MIGRATOR
+        .undo(pool, target)
+        .await
+        .context("No se pudieron revertir las migraciones")?;
+
+    let reverted: Vec<i64> = applied
+        .into_iter()
+        .rev()
+        .filter(|version| *version > target)
+        .collect();
+
+    if reverted.is_empty() {
+        println!("No hay migraciones posteriores a {target}");
+    }
+
+    for version in reverted {
+        let description = MIGRATOR
+            .iter()
+            .find(|migration| migration.version == version)
+            .map(|migration| migration.description.as_ref())
+            .unwrap_or_default();
+        println!("Migración revertida: {version} ({description})");
+    }
+
+    Ok(())
+}
+
+/// Registra una App e imprime su llave.
+///
+/// A diferencia de `POST /v1/auth/register`, no necesita una llave con `apps:admin`, así que sirve
+/// para crear la primera App de la API.
+async fn create_app(args: CreateAppArgs, pool: &SqlitePool) -> anyhow::Result<()> {
+    let mut scopes = if args.scopes.is_empty() {
+        Scope::DEFAULT.to_vec()
+    } else {
+        args.scopes
+    };
+    scopes.sort_unstable();
+    scopes.dedup();
+
+    let app = CreateAppSchema {
+        name: args.name,
+        description: args.description,
+        scopes: Some(scopes.clone()),
+        expires_at: args.expires_at,
+        rate_limit: args.rate_limit,
+    };
+    validate(&app)?;
+
+    let app_id = uuid::Uuid::new_v4().to_string();
+    let (pak, hash) = key_controller().generate_key_and_hash();
+
+    AppModel::create(&app_id, &app, &hash, &scopes, pool)
+        .await
+        .map_err(api_error)?;
+
+    let scopes: Vec<&str> = scopes.iter().map(Scope::as_str).collect();
+    println!("App creada: {app_id}");
+    println!("Permisos: {}", scopes.join(", "));
+    println!("Llave: {}", pak.to_string());
+    println!("Guarda la llave ahora; no se puede volver a mostrar.");
+
+    Ok(())
+}
+
+/// Revoca una App.
+async fn revoke_app(app_id: &str, pool: &SqlitePool) -> anyhow::Result<()> {
+    let app = AppModel::find_by_id(app_id, pool)
+        .await
+        .map_err(api_error)?;
+
+    if app.deleted_at.is_none() && !AppModel::revoke(app_id, pool).await.map_err(api_error)? {
+        bail!("No se puede revocar la última App activa con el permiso apps:admin");
+    }
+
+    println!("App revocada: {} ({})", app.uuid, app.name);
+
+    Ok(())
+}
+
+/// Valida y agrega integrantes, imprimiendo la UUID de cada uno.
+async fn import_members(members: Vec<CreateMemberSchema>, pool: &SqlitePool) -> anyhow::Result<()> {
+    // Se validan todos antes de agregar alguno, para mostrar todos los problemas de una vez.
+    let mut problems = Vec::new();
+
+    for (index, member) in members.iter().enumerate() {
+        if let Err(err) = validate(member) {
+            problems.push(format!("Integrante {} ({}): {err}", index + 1, member.name));
+        }
+    }
+
+    if !problems.is_empty() {
+        bail!("{}", problems.join("\n"));
+    }
+
+    let names: Vec<String> = members.iter().map(|member| member.name.clone()).collect();
+    let ids = ClubMemberModel::create_many(members, pool)
+        .await
+        .map_err(api_error)?;
+
+    for (id, name) in ids.iter().zip(names) {
+        println!("{id}\t{name}");
+    }
+    println!("Integrantes agregados: {}", ids.len());
+
+    Ok(())
+}
+
+/// Imprime los proyectos, uno por línea, con su UUID, estado y nombre.
+async fn list_projects(
+    state: Option<ProjectState>,
+    archived: bool,
+    pool: &SqlitePool,
+) -> anyhow::Result<()> {
+    let projects: Vec<ProjectModel> = ProjectModel::get_all(pool)
+        .await?
+        .into_iter()
+        .filter(|project| state.is_none_or(|state| project.state() == state))
+        .filter(|project| archived || !project.archived)
+        .collect();
+
+    for project in &projects {
+        let archived = if project.archived { " (archivado)" } else { "" };
+        println!(
+            "{}\t{}\t{}{archived}",
+            project.uuid,
+            project.state().as_str(),
+            project.name
+        );
+    }
+    println!("Proyectos: {}", projects.len());
+
+    Ok(())
+}
+
+/// Elimina definitivamente los proyectos eliminados antes del periodo de retención.
+async fn purge_projects(pool: &SqlitePool) -> anyhow::Result<()> {
+    let retention_start = chrono::Utc::now().naive_utc() - project_retention_from_env()?;
+    let result = ProjectModel::purge_deleted(retention_start, pool).await?;
+
+    println!(
+        "Proyectos eliminados definitivamente: {}",
+        result.rows_affected()
+    );
+
+    Ok(())
+}
+
+/// Columnas con secretos, como `(tabla, columna)`, que `dump` oculta salvo que se pidan.
+///
+/// Con ellas se podría usar la llave de una App, generar códigos de verificación o usar un enlace
+/// de inicio de sesión o un código de recuperación que aún no se ha usado.
+const SECRET_COLUMNS: &[(&str, &str)] = &[
+    ("apps", "api_token"),
+    ("apps", "previous_api_token"),
+    ("member_totp", "secret"),
+    ("member_totp", "challenge_nonce"),
+    ("member_recovery_codes", "code_hash"),
+    ("magic_links", "token_hash"),
+];
+
+/// Valor con el que `dump` reemplaza los secretos.
+const REDACTED: &str = "[oculto]";
+
+/// Exporta la base de datos como JSON, en `output` o en la consola.
+async fn dump(
+    output: Option<PathBuf>,
+    include_secrets: bool,
+    pool: &SqlitePool,
+) -> anyhow::Result<()> {
+    let json = serde_json::to_string_pretty(&dump_tables(include_secrets, pool).await?)?;
+
+    match output {
+        Some(path) => fs::write(&path, json)
+            .with_context(|| format!("No se pudo escribir {}", path.display()))?,
+        None => println!("{json}"),
+    }
+
+    Ok(())
+}
+
+/// Exporta cada tabla como una lista de filas, en un objeto con el nombre de cada tabla.
+///
+/// Los valores se exportan según su tipo en SQLite: los `BLOB` como texto hexadecimal, y las
+/// fechas como el texto en que se guardan. Los secretos de `SECRET_COLUMNS` se reemplazan por
+/// `REDACTED`, salvo con `include_secrets`.
+async fn dump_tables(
+    include_secrets: bool,
+    pool: &SqlitePool,
+) -> anyhow::Result<Map<String, Value>> {
+    let tables: Vec<String> = sqlx::query_scalar(
+        "SELECT name FROM sqlite_master WHERE type = 'table' AND name NOT LIKE 'sqlite_%' ORDER BY name",
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let mut dump = Map::new();
+
+    for table in tables {
+        let rows = sqlx::query(&format!("SELECT * FROM \"{}\"", table.replace('"', "\"\"")))
+            .fetch_all(pool)
+            .await?;
+
+        let rows = rows
+            .iter()
+            .map(|row| {
+                row.columns()
+                    .iter()
+                    .map(|column| {
+                        let mut value = column_value(row, column.ordinal())?;
+
+                        if !include_secrets
+                            && !value.is_null()
+                            && SECRET_COLUMNS.contains(&(table.as_str(), column.name()))
+                        {
+                            value = Value::from(REDACTED);
+                        }
+
+                        Ok((column.name().to_owned(), value))
+                    })
+                    .collect::<Result<Map<String, Value>, sqlx::Error>>()
+                    .map(Value::Object)
+            })
+            .collect::<Result<Vec<Value>, sqlx::Error>>()?;
+
+        dump.insert(table, Value::Array(rows));
+    }
+
+    Ok(dump)
+}
+
+/// Valor de una columna de una fila, según el tipo del valor guardado.
+fn column_value(row: &sqlx::sqlite::SqliteRow, index: usize) -> Result<Value, sqlx::Error> {
+    let raw = row.try_get_raw(index)?;
+
+    if raw.is_null() {
+        return Ok(Value::Null);
+    }
+
+    let value = match raw.type_info().name() {
+        "INTEGER" => Value::from(row.try_get::<i64, _>(index)?),
+        "REAL" => Value::from(row.try_get::<f64, _>(index)?),
+        "BLOB" => Value::from(hex::encode(row.try_get::<Vec<u8>, _>(index)?)),
+        _ => Value::from(row.try_get::<String, _>(index)?),
+    };
+
+    Ok(value)
+}
+
+/// Valida un esquema, con los mismos mensajes que recibiría un cliente de la API.
+fn validate(schema: &impl Validate) -> anyhow::Result<()> {
+    schema.validate().map_err(api_error)
+}
+
+/// Convierte un error de la API en uno para la consola, incluyendo los problemas de cada campo.
+fn api_error(err: impl Into<ApiError>) -> anyhow::Error {
+    match err.into() {
+        ApiError::Validation(errors) => {
+            let fields: Vec<String> = errors
+                .iter()
+                .map(|error| format!("{}: {}", error.field, error.message))
+                .collect();
+            anyhow::anyhow!("Los datos no son válidos ({})", fields.join("; "))
+        }
+        ApiError::Internal(detail) => anyhow::anyhow!("Error interno: {detail}"),
+        err => anyhow::anyhow!("{err}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use exdev_api::{
+        database, key_controller,
+        v1::models::{magic_link::MagicLinkModel, totp::TotpModel},
+    };
+    use serde_json::Value;
+    use sqlx::{sqlite::SqlitePoolOptions, SqlitePool};
+
+    use super::{dump_tables, REDACTED, SECRET_COLUMNS};
+
+    /// Base de datos en memoria con todas las migraciones aplicadas.
+    async fn pool() -> SqlitePool {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        database::migrate(&pool).await.unwrap();
+
+        pool
+    }
+
+    /// Cada columna de `SECRET_COLUMNS` existe, para que un cambio de nombre no deje un secreto
+    /// sin ocultar.
+    #[actix_web::test]
+    async fn secret_columns_exist() {
+        let pool = pool().await;
+
+        for (table, column) in SECRET_COLUMNS {
+            let count: i64 =
+                sqlx::query_scalar("SELECT COUNT(*) FROM pragma_table_info(?) WHERE name = ?")
+                    .bind(table)
+                    .bind(column)
+                    .fetch_one(&pool)
+                    .await
+                    .unwrap();
+            assert_eq!(count, 1, "{table}.{column} no existe");
+        }
+    }
+
+    /// Los secretos se ocultan salvo que se pidan, y el resto de los valores se exporta igual.
+    #[actix_web::test]
+    async fn dump_hides_secrets() {
+        let pool = pool().await;
+        let app_id = uuid::Uuid::new_v4().to_string();
+        let member_id = uuid::Uuid::new_v4().to_string();
+        let (_, api_token) = key_controller().generate_key_and_hash();
+        let link_hash = MagicLinkModel::hash_token("token-del-enlace");
+        let totp_secret = TotpModel::generate_secret();
+
+        sqlx::query("INSERT INTO apps (uuid, name, api_token) VALUES (?, 'Panel', ?)")
+            .bind(&app_id)
+            .bind(&api_token)
+            .execute(&pool)
+            .await
+            .unwrap();
+        sqlx::query("INSERT INTO club_members (uuid, name) VALUES (?, 'Integrante')")
+            .bind(&member_id)
+            .execute(&pool)
+            .await
+            .unwrap();
+        let expires_at = chrono::Utc::now().naive_utc() + chrono::Duration::minutes(15);
+        MagicLinkModel::create(&link_hash, &member_id, expires_at, &pool)
+            .await
+            .unwrap();
+        TotpModel::start_enrolment(&member_id, &totp_secret, &pool)
+            .await
+            .unwrap();
+
+        let secrets = [&api_token, &link_hash, &totp_secret];
+
+        let dump = dump_tables(false, &pool).await.unwrap();
+        let json = Value::Object(dump.clone()).to_string();
+        for secret in secrets {
+            assert!(!json.contains(secret.as_str()));
+        }
+        assert_eq!(dump["apps"][0]["api_token"], REDACTED);
+        assert_eq!(dump["apps"][0]["previous_api_token"], Value::Null);
+        assert_eq!(dump["apps"][0]["name"], "Panel");
+        assert_eq!(dump["magic_links"][0]["token_hash"], REDACTED);
+        assert_eq!(dump["magic_links"][0]["club_member_uuid"], member_id);
+        assert_eq!(dump["member_totp"][0]["secret"], REDACTED);
+
+        let dump = dump_tables(true, &pool).await.unwrap();
+        assert_eq!(dump["apps"][0]["api_token"], api_token);
+        assert_eq!(dump["magic_links"][0]["token_hash"], link_hash);
+        assert_eq!(dump["member_totp"][0]["secret"], totp_secret);
+    }
+}
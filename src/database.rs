@@ -0,0 +1,50 @@
+//! Conexión con la base de datos y sus migraciones.
+//!
+//! Las migraciones de la carpeta `migrations/` se incluyen en el binario al compilar, de forma
+//! que la API pueda dejar su base de datos al día al iniciar, sin necesitar `sqlx-cli`.
+
+use std::{collections::HashSet, str::FromStr};
+
+use sqlx::{
+    migrate::{Migrate, MigrateError, Migration, Migrator},
+    sqlite::SqliteConnectOptions,
+    SqlitePool,
+};
+
+/// Migraciones de la carpeta `migrations/`.
+pub static MIGRATOR: Migrator = sqlx::migrate!();
+
+/// Se conecta con la base de datos SQLite de `url`, creando el archivo si no existe.
+pub async fn connect(url: &str) -> Result<SqlitePool, sqlx::Error> {
+    let options = SqliteConnectOptions::from_str(url)?.create_if_missing(true);
+
+    SqlitePool::connect_with(options).await
+}
+
+/// Aplica las migraciones pendientes.
+///
+/// Entrega las migraciones que se aplicaron, de la más antigua a la más reciente; si la base de
+/// datos ya estaba al día, la lista viene vacía. Falla si una migración ya aplicada fue
+/// modificada o ya no existe.
+pub async fn migrate(pool: &SqlitePool) -> Result<Vec<&'static Migration>, MigrateError> {
+    let mut conn = pool.acquire().await?;
+    conn.ensure_migrations_table().await?;
+
+    let applied: HashSet<i64> = conn
+        .list_applied_migrations()
+        .await?
+        .into_iter()
+        .map(|migration| migration.version)
+        .collect();
+    drop(conn);
+
+    let pending = MIGRATOR
+        .iter()
+        .filter(|migration| migration.migration_type.is_up_migration())
+        .filter(|migration| !applied.contains(&migration.version))
+        .collect();
+
+    MIGRATOR.run(pool).await?;
+
+    Ok(pending)
+}
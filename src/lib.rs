@@ -0,0 +1,114 @@
+//! API centralizada y multipropósito del ExDev.
+//!
+//! Esta API fue diseñada con el propósito de ser una única fuente central de
+//! todos los procesos que ocurren en el club.
+//!
+//! Hay algunas decisiones de 'filosofía' que se tomaron para llegar a este diseño;
+//! partiendo desde el lenguaje elegido. Se eligió Rust como lenguaje de desarrollo de esta
+//! API debido a su robustez a lo largo del tiempo. En un principio, si esta API es bien
+//! diseñada, no será necesario tocarla en mucho tiempo.
+//!
+//! El mayor problema que enfrenta esto es que la rotación de personas del club es muy alta
+//! por lo que la documentación **tiene** que estar a la par. Esta es otra buena razón para
+//! ocupar Rust. Si estás leyendo esto para aprender a usar la API, es porque tomamos una buena
+//! decisión!
+//!
+//! La API se compone de dos binarios que comparten esta librería: el servidor (`exdev_api`) y
+//! la herramienta de administración (`exdev-admin`).
+//!
+//! Créditos:
+//! - Rafael Morales V. - Inicié el proyecto en 2023!
+
+use prefixed_api_key::PrefixedApiKeyController;
+use rand::rngs::OsRng;
+use sha2::Sha256;
+use sqlx::SqlitePool;
+use v1::{
+    middleware::{
+        member_session::{RedirectAllowlist, TokenSigner},
+        rate_limit::RateLimiter,
+    },
+    models::project::ProjectWorkflow,
+    providers::{github::GithubProvider, mail::Mailer},
+};
+
+pub mod database;
+pub mod v1;
+
+#[cfg(test)]
+mod testing;
+
+/// Controlador de las llaves de la API.
+pub type KeyController = PrefixedApiKeyController<OsRng, Sha256>;
+
+/// Estructura del estado de la aplicación.
+///
+/// Esta estructura contiene una conexión con la base de datos, un controlador para las llaves de la
+/// API y la configuración que necesitan los handlers.
+///
+/// Estos valores son compartidos por toda la API via Actix.
+pub struct AppState {
+    /// Contiene la conexión con la Base de datos.
+    pub pool: SqlitePool,
+    /// Maneja el uso de las llaves de autorización de la aplicación.
+    pub pak_controller: KeyController,
+    /// Segundos durante los cuales una llave regenerada sigue siendo válida, si la petición no
+    /// indica otro valor.
+    pub api_key_grace_period: i64,
+    /// Contadores del límite de peticiones, compartidos por todos los workers.
+    pub rate_limiter: RateLimiter,
+    /// Si la IP de los clientes se toma de los headers del proxy (`X-Forwarded-For`) en vez de la
+    /// conexión.
+    pub trust_proxy: bool,
+    /// Si se puede registrar la primera App sin llave, mientras no exista ninguna.
+    pub allow_bootstrap: bool,
+    /// Firma los tokens de sesión de los integrantes.
+    pub token_signer: TokenSigner,
+    /// URL a las que se puede enviar a un integrante al iniciar sesión.
+    pub allowed_redirects: RedirectAllowlist,
+    /// Cliente de GitHub para el inicio de sesión, si está configurado.
+    pub github: Option<GithubProvider>,
+    /// Envía los correos de la API, como los enlaces de inicio de sesión.
+    pub mailer: Mailer,
+    /// Duración de los enlaces de inicio de sesión enviados por correo.
+    pub magic_link_ttl: chrono::Duration,
+    /// Tiempo durante el cual se puede restaurar un proyecto eliminado.
+    pub project_retention: chrono::Duration,
+    /// Cambios de estado permitidos para los proyectos.
+    pub project_workflow: ProjectWorkflow,
+}
+
+/// Máximo de días de `PROJECT_RETENTION_DAYS`.
+pub const MAX_PROJECT_RETENTION_DAYS: i64 = 3650;
+
+/// Lee de `PROJECT_RETENTION_DAYS` el tiempo durante el cual se puede restaurar un proyecto
+/// eliminado (30 días por defecto).
+///
+/// El servidor lo usa para restaurar proyectos y `exdev-admin` para eliminarlos definitivamente,
+/// así que ambos deben leer el mismo valor. Falla si no está entre 0 y
+/// [`MAX_PROJECT_RETENTION_DAYS`] días.
+pub fn project_retention_from_env() -> anyhow::Result<chrono::Duration> {
+    let days = std::env::var("PROJECT_RETENTION_DAYS")
+        .unwrap_or(String::from("30"))
+        .parse::<i64>()?;
+
+    if !(0..=MAX_PROJECT_RETENTION_DAYS).contains(&days) {
+        anyhow::bail!(
+            "PROJECT_RETENTION_DAYS debe estar entre 0 y {MAX_PROJECT_RETENTION_DAYS} días"
+        );
+    }
+
+    Ok(chrono::Duration::days(days))
+}
+
+/// Crea el controlador de las llaves de la API.
+///
+/// El servidor y `exdev-admin` deben usar la misma configuración, o las llaves creadas por uno no
+/// serían aceptadas por el otro.
+pub fn key_controller() -> KeyController {
+    PrefixedApiKeyController::configure()
+        .prefix("ExDevUtem".to_owned())
+        .seam_defaults()
+        .finalize()
+        .expect("Could not create Prefixed Api Key Controller")
+}
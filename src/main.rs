@@ -1,40 +1,23 @@
-//! API centralizada y multipropósito del ExDev.
+//! Servidor de la API del ExDev.
 //!
-//! Esta API fue diseñada con el propósito de ser una única fuente central de
-//! todos los procesos que ocurren en el club.
-//!
-//! Hay algunas decisiones de 'filosofía' que se tomaron para llegar a este diseño;
-//! partiendo desde el lenguaje elegido. Se eligió Rust como lenguaje de desarrollo de esta
-//! API debido a su robustez a lo largo del tiempo. En un principio, si esta API es bien
-//! diseñada, no será necesario tocarla en mucho tiempo.
-//!
-//! El mayor problema que enfrenta esto es que la rotación de personas del club es muy alta
-//! por lo que la documentación **tiene** que estar a la par. Esta es otra buena razón para
-//! ocupar Rust. Si estás leyendo esto para aprender a usar la API, es porque tomamos una buena
-//! decisión!
-//!
-//! Créditos:
-//! - Rafael Morales V. - Inicié el proyecto en 2023!
+//! Lee la configuración del ambiente, prepara la base de datos y sirve las rutas de la librería
+//! `exdev_api`.
 
 use actix_web::{web, App, HttpServer};
 use dotenv::dotenv;
-use prefixed_api_key::PrefixedApiKeyController;
-use sqlx::SqlitePool;
-
-pub mod v1;
-
-/// Estructura del estado de la aplicación.
-///
-/// Existen solo dos valores para esta estructura en este momento; uno es una conexión con la base
-/// de datos, y el otro es un controlador para las llaves de la API.
-///
-/// Estos valores son compartidos por toda la API via Actix.
-pub struct AppState {
-    /// Contiene la conexión con la Base de datos.
-    pool: SqlitePool,
-    /// Maneja el uso de las llaves de autorización de la aplicación.
-    pak_controller: PrefixedApiKeyController<rand::rngs::OsRng, sha2::Sha256>,
-}
+use exdev_api::{
+    database, key_controller, project_retention_from_env,
+    v1::{
+        self,
+        middleware::{
+            member_session::{RedirectAllowlist, TokenSigner},
+            rate_limit::RateLimiter,
+        },
+        models::project::ProjectWorkflow,
+        providers::{github::GithubProvider, mail::Mailer},
+    },
+    AppState,
+};
 
 /// Función principal de la API
 ///
@@ -42,7 +25,7 @@ pub struct AppState {
 /// 1. Verifica los valores de ambiente, los cuales puedes revisar en el archivo
 /// `.env.example`
 /// 2. Intenta conectarse con una base de datos SQLite3 siguiendo los valores del archivo de
-///    ambiente.
+///    ambiente, creándola si no existe, y le aplica las migraciones pendientes.
 /// 3. Inicia un controlador de PrefixedApiKey, que maneja las llaves de la API.
 /// 4. Configura una instancia del servidor según las rutas definidas en cada módulo de versión. A
 ///    fecha de esta documentación, solo existe el módulo `v1`.
@@ -60,19 +43,93 @@ pub async fn main() -> std::io::Result<()> {
         .unwrap_or(String::from("8080"))
         .parse::<u16>()
         .expect("No se pudo parsear el puerto");
+    let api_key_grace_period = std::env::var("API_KEY_GRACE_PERIOD")
+        .unwrap_or(String::from("0"))
+        .parse::<i64>()
+        .expect("No se pudo parsear el periodo de gracia de las llaves");
+    let rate_limit = std::env::var("RATE_LIMIT_PER_MINUTE")
+        .unwrap_or(String::from("120"))
+        .parse::<u64>()
+        .expect("No se pudo parsear el límite de peticiones por minuto");
+    let trust_proxy = std::env::var("TRUST_PROXY")
+        .unwrap_or(String::from("false"))
+        .parse::<bool>()
+        .expect("No se pudo parsear TRUST_PROXY");
+    let allow_bootstrap = std::env::var("ALLOW_BOOTSTRAP")
+        .unwrap_or(String::from("false"))
+        .parse::<bool>()
+        .expect("No se pudo parsear ALLOW_BOOTSTRAP");
+    let session_ttl = std::env::var("SESSION_TTL")
+        .unwrap_or(String::from("604800"))
+        .parse::<i64>()
+        .map(chrono::Duration::seconds)
+        .expect("No se pudo parsear la duración de las sesiones");
+    let magic_link_ttl = std::env::var("MAGIC_LINK_TTL")
+        .unwrap_or(String::from("900"))
+        .parse::<i64>()
+        .map(chrono::Duration::seconds)
+        .expect("No se pudo parsear la duración de los enlaces de inicio de sesión");
+    let project_retention = project_retention_from_env()
+        .expect("No se pudo parsear el periodo de retención de los proyectos");
+    let project_workflow = ProjectWorkflow::from_env()
+        .expect("No se pudo parsear los cambios de estado de los proyectos");
+    let api_debug = std::env::var("API_DEBUG")
+        .unwrap_or(String::from("false"))
+        .parse::<bool>()
+        .expect("No se pudo parsear API_DEBUG");
+    let run_migrations = std::env::var("RUN_MIGRATIONS")
+        .unwrap_or(String::from("true"))
+        .parse::<bool>()
+        .expect("No se pudo parsear RUN_MIGRATIONS");
+
+    // El detalle de los errores internos solo se entrega a los clientes si se pidió.
+    v1::responders::errors::ApiError::show_debug(api_debug);
 
     // Se instancia una conexión a la base de datos a partir de la configuración del archivo
-    // ambiente.
-    let pool = SqlitePool::connect(&database_url)
+    // ambiente. Si el archivo de la base de datos no existe, se crea.
+    let pool = database::connect(&database_url)
         .await
         .expect("Could not connect to Database");
 
+    // Se aplican las migraciones pendientes, a menos que se desactive con `RUN_MIGRATIONS`.
+    if run_migrations {
+        let applied = database::migrate(&pool)
+            .await
+            .expect("No se pudieron aplicar las migraciones");
+
+        if applied.is_empty() {
+            println!("La base de datos está al día");
+        }
+
+        for migration in applied {
+            println!(
+                "Migración aplicada: {} ({})",
+                migration.version, migration.description
+            );
+        }
+    }
+
     // Se crea un controlador de PrefixedApiKey para la API.
-    let pak_controller = PrefixedApiKeyController::configure()
-        .prefix("ExDevUtem".to_owned())
-        .seam_defaults()
-        .finalize()
-        .expect("Could not create Prefixed Api Key Controller");
+    let pak_controller = key_controller();
+
+    // Se crea el firmador de sesiones. Sin un secreto configurado, las sesiones no sobreviven a
+    // un reinicio del servidor.
+    let token_signer =
+        TokenSigner::from_env(session_ttl).expect("No se pudo configurar SESSION_SECRET");
+
+    // Las URL a las que se puede enviar a un integrante al iniciar sesión.
+    let allowed_redirects = RedirectAllowlist::from_env()
+        .expect("No se pudo parsear las URL de redirección permitidas");
+
+    // El inicio de sesión con GitHub solo se habilita si está configurado.
+    let github = GithubProvider::from_env();
+
+    // Se configura el envío de correos. Por defecto, los correos se imprimen en la consola.
+    let mailer = Mailer::from_env().expect("No se pudo configurar el envío de correos");
+
+    // Los contadores del límite de peticiones se crean una sola vez para que todos los workers
+    // compartan los mismos.
+    let rate_limiter = RateLimiter::new(rate_limit);
 
     // Se instancia el servidor de Actix.
     let server = HttpServer::new(move || {
@@ -82,7 +139,20 @@ pub async fn main() -> std::io::Result<()> {
             .app_data(web::Data::new(AppState {
                 pool: pool.clone(),
                 pak_controller: pak_controller.clone(),
+                api_key_grace_period,
+                rate_limiter: rate_limiter.clone(),
+                trust_proxy,
+                allow_bootstrap,
+                token_signer: token_signer.clone(),
+                allowed_redirects: allowed_redirects.clone(),
+                github: github.clone(),
+                mailer: mailer.clone(),
+                magic_link_ttl,
+                project_retention,
+                project_workflow: project_workflow.clone(),
             }))
+            // La documentación de la API va antes que sus rutas, para que no pida una llave.
+            .service(v1::openapi::docs())
             // Se agregan todas las rutas del módulo `v1`.
             .service(v1::routes())
     })
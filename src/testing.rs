@@ -0,0 +1,104 @@
+//! Utilidades para las pruebas de la API.
+//!
+//! Cada prueba trabaja sobre su propia base de datos en memoria, con todas las migraciones
+//! aplicadas, y con un `AppState` con la configuración por defecto del servidor.
+
+use actix_web::web;
+use sqlx::{sqlite::SqlitePoolOptions, SqlitePool};
+
+use crate::{
+    key_controller,
+    v1::{
+        middleware::{
+            member_session::{RedirectAllowlist, TokenSigner},
+            rate_limit::RateLimiter,
+        },
+        models::{
+            auth::{AppModel, Scope},
+            project::ProjectWorkflow,
+        },
+        providers::mail::{MailTransport, Mailer},
+        schemas::auth::CreateAppSchema,
+    },
+    AppState,
+};
+
+/// Peticiones por minuto de las Apps de prueba.
+pub const RATE_LIMIT: u64 = 120;
+
+/// Crea una base de datos en memoria con todas las migraciones aplicadas.
+///
+/// Se usa una sola conexión, para que todas las consultas vean la misma base de datos.
+pub async fn pool() -> SqlitePool {
+    let pool = SqlitePoolOptions::new()
+        .max_connections(1)
+        .connect("sqlite::memory:")
+        .await
+        .expect("La base de datos en memoria debería abrirse");
+    crate::database::migrate(&pool)
+        .await
+        .expect("Las migraciones deberían aplicarse");
+
+    pool
+}
+
+/// Estado de la API sobre `pool`, con la configuración por defecto del servidor.
+pub fn state(pool: &SqlitePool) -> web::Data<AppState> {
+    web::Data::new(app_state(pool))
+}
+
+/// Igual que [`state`], pero sin envolver, para las pruebas que cambian la configuración.
+pub fn app_state(pool: &SqlitePool) -> AppState {
+    AppState {
+        pool: pool.clone(),
+        pak_controller: key_controller(),
+        api_key_grace_period: 0,
+        rate_limiter: RateLimiter::new(RATE_LIMIT),
+        trust_proxy: false,
+        allow_bootstrap: false,
+        token_signer: TokenSigner::random(chrono::Duration::hours(1)),
+        allowed_redirects: RedirectAllowlist::default(),
+        github: None,
+        mailer: Mailer::new(
+            "ExDev <no-reply@exdev.cl>".parse().unwrap(),
+            MailTransport::Stdout,
+        ),
+        magic_link_ttl: chrono::Duration::minutes(15),
+        project_retention: chrono::Duration::days(30),
+        project_workflow: ProjectWorkflow::default(),
+    }
+}
+
+/// Registra una App con los permisos indicados y entrega su UUID y su llave.
+pub async fn create_app(pool: &SqlitePool, scopes: &[Scope]) -> (String, String) {
+    let app_id = uuid::Uuid::new_v4().to_string();
+    let (pak, hash) = key_controller().generate_key_and_hash();
+    let app = CreateAppSchema {
+        name: String::from("App de prueba"),
+        description: None,
+        scopes: Some(scopes.to_vec()),
+        expires_at: None,
+        rate_limit: None,
+    };
+
+    AppModel::create(&app_id, &app, &hash, scopes, pool)
+        .await
+        .expect("La App debería crearse");
+
+    (app_id, pak.to_string())
+}
+
+/// Registra un integrante con el rol indicado (`"member"`, `"board"`, ...) y entrega su UUID.
+pub async fn create_member(pool: &SqlitePool, role: &str) -> String {
+    let member_id = uuid::Uuid::new_v4().to_string();
+
+    sqlx::query("INSERT INTO club_members (uuid, name, role) VALUES (?, ?, ?)")
+        .bind(&member_id)
+        .bind("Integrante de prueba")
+        .bind(role)
+        .execute(pool)
+        .await
+        .expect("El integrante debería crearse");
+
+    member_id
+}
@@ -0,0 +1,391 @@
+//! Autenticación de Apps via llaves de la API.
+//!
+//! Toda petición a `/v1` debe traer el header `Authorization` con la llave que se le entregó a la
+//! App al registrarse (`Authorization: Bearer ExDevUtem_...`). El middleware busca la App dueña de
+//! esa llave y la deja guardada en la petición, junto con sus permisos, de forma que cualquier
+//! handler pueda pedirla con el extractor [`AuthenticatedApp`].
+//!
+//! Los handlers declaran el permiso que necesitan pidiendo el extractor [`Scoped`] en vez de
+//! [`AuthenticatedApp`], por ejemplo `Scoped<MembersWrite>`.
+//!
+//! Cuando una llave es aceptada se registra cuándo y desde qué IP fue usada, a lo más una vez por
+//! minuto. Las llaves con fecha de expiración dejan de ser aceptadas una vez que esta pasa.
+//!
+//! La única excepción es `POST /v1/auth/register` cuando aún no existe ninguna App. Sin esto no
+//! habría forma de crear la primera llave. Las Apps revocadas siguen contando, así que revocar
+//! todas las llaves no vuelve a abrir el registro.
+
+use std::future::{ready, Future, Ready};
+use std::marker::PhantomData;
+use std::pin::Pin;
+use std::rc::Rc;
+
+use actix_web::{
+    dev::{forward_ready, Payload, Service, ServiceRequest, ServiceResponse, Transform},
+    http::{header::AUTHORIZATION, Method},
+    web, Error, FromRequest, HttpMessage, HttpRequest,
+};
+use prefixed_api_key::PrefixedApiKey;
+
+use crate::{
+    v1::{
+        middleware::client_ip,
+        models::auth::{AppModel, Scope},
+        responders::errors::ApiError,
+    },
+    AppState,
+};
+
+/// Ruta que puede ser llamada sin llave mientras no exista ninguna App, si `ALLOW_BOOTSTRAP` lo
+/// permite.
+const BOOTSTRAP_PATH: &str = "/v1/auth/register";
+
+/// Error de las peticiones que no traen una llave de la API.
+pub fn missing_key() -> ApiError {
+    ApiError::Unauthorized(String::from("Se requiere una llave de la API"))
+}
+
+/// Error de las llaves que no tienen la forma de una llave de la API.
+fn malformed_key() -> ApiError {
+    ApiError::Unauthorized(String::from(
+        "La llave de la API no tiene un formato válido",
+    ))
+}
+
+/// Middleware que exige una llave de la API válida.
+///
+/// Se registra con `wrap` sobre el scope que se quiera proteger.
+pub struct ApiKeyAuth;
+
+impl<S, B> Transform<S, ServiceRequest> for ApiKeyAuth
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Transform = ApiKeyAuthMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(ApiKeyAuthMiddleware {
+            service: Rc::new(service),
+        }))
+    }
+}
+
+/// Servicio generado por [`ApiKeyAuth`] para cada worker de Actix.
+pub struct ApiKeyAuthMiddleware<S> {
+    service: Rc<S>,
+}
+
+impl<S, B> Service<ServiceRequest> for ApiKeyAuthMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let service = Rc::clone(&self.service);
+
+        Box::pin(async move {
+            let data = req
+                .app_data::<web::Data<AppState>>()
+                .expect("AppState no fue registrado en la App")
+                .clone();
+
+            // Si el límite de peticiones ya identificó a la App, no se vuelve a buscar.
+            if req.extensions().contains::<AuthenticatedApp>() {
+                return service.call(req).await;
+            }
+
+            match authenticate(&req, &data).await? {
+                Some(app) => {
+                    req.extensions_mut().insert(app);
+                }
+                None => {
+                    // Se deja pasar el registro de la primera App.
+                    if !is_bootstrap(&req, &data).await? {
+                        return Err(missing_key().into());
+                    }
+                }
+            }
+
+            service.call(req).await
+        })
+    }
+}
+
+/// Busca la App que corresponde a la llave del header `Authorization`, junto con sus permisos, y
+/// registra el uso de la llave.
+///
+/// Entrega `None` si la petición no trae el header.
+pub async fn authenticate(
+    req: &ServiceRequest,
+    data: &AppState,
+) -> Result<Option<AuthenticatedApp>, ApiError> {
+    let Some(header) = req.headers().get(AUTHORIZATION) else {
+        return Ok(None);
+    };
+    let header = header.to_str().map_err(|_| malformed_key())?;
+
+    let raw_key = header.strip_prefix("Bearer ").unwrap_or(header).trim();
+    let pak = PrefixedApiKey::from_string(raw_key).map_err(|_| malformed_key())?;
+
+    let hash = data.pak_controller.long_token_hashed(&pak);
+
+    // Una llave que no es de ninguna App activa es una llave inválida, no un recurso que no
+    // existe.
+    let app = match AppModel::find_by_key_hash(&hash, &data.pool).await {
+        Ok(app) => app,
+        Err(sqlx::Error::RowNotFound) => {
+            return Err(ApiError::Unauthorized(String::from(
+                "La llave de la API no es válida",
+            )))
+        }
+        Err(err) => return Err(err.into()),
+    };
+
+    if let Some(expires_at) = app.expires_at.filter(|_| app.is_expired()) {
+        return Err(ApiError::ExpiredKey(expires_at));
+    }
+
+    if app.needs_touch() {
+        let ip = client_ip(req, data);
+        AppModel::touch(&app.uuid, ip.as_deref(), &data.pool).await?;
+    }
+
+    let scopes = AppModel::get_scopes(&app.uuid, &data.pool).await?;
+
+    Ok(Some(AuthenticatedApp { app, scopes }))
+}
+
+/// Indica si la petición es el registro de la primera App de la API, y si está permitido.
+///
+/// Esta revisión solo evita consultar la base de datos en cada petición sin llave; el registro
+/// vuelve a revisar que no existan Apps al guardar la nueva.
+async fn is_bootstrap(req: &ServiceRequest, data: &AppState) -> Result<bool, ApiError> {
+    if !data.allow_bootstrap || req.method() != Method::POST || req.path() != BOOTSTRAP_PATH {
+        return Ok(false);
+    }
+
+    Ok(AppModel::count(&data.pool).await? == 0)
+}
+
+/// Extractor de la App que hizo la petición.
+///
+/// Solo puede usarse en handlers protegidos por [`ApiKeyAuth`]. Si la App no está en la
+/// petición (por ejemplo, durante el registro de la primera App), el extractor falla con un 401.
+#[derive(Debug, Clone)]
+pub struct AuthenticatedApp {
+    /// La App dueña de la llave.
+    pub app: AppModel,
+    /// Los permisos de la App.
+    pub scopes: Vec<Scope>,
+}
+
+impl AuthenticatedApp {
+    /// Verifica que la App tenga un permiso, o genera un error 403.
+    pub fn require(&self, scope: Scope) -> Result<(), ApiError> {
+        if self.scopes.contains(&scope) {
+            Ok(())
+        } else {
+            Err(ApiError::Forbidden(format!(
+                "La App no tiene el permiso {scope}"
+            )))
+        }
+    }
+}
+
+impl FromRequest for AuthenticatedApp {
+    type Error = ApiError;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        ready(
+            req.extensions()
+                .get::<AuthenticatedApp>()
+                .cloned()
+                .ok_or_else(missing_key),
+        )
+    }
+}
+
+/// Permiso que un handler puede exigir via [`Scoped`].
+pub trait RequiredScope {
+    /// El permiso exigido.
+    const SCOPE: Scope;
+}
+
+/// Exige el permiso `members:read`.
+pub struct MembersRead;
+
+/// Exige el permiso `members:write`.
+pub struct MembersWrite;
+
+/// Exige el permiso `projects:read`.
+pub struct ProjectsRead;
+
+/// Exige el permiso `projects:write`.
+pub struct ProjectsWrite;
+
+/// Exige el permiso `apps:admin`.
+pub struct AppsAdmin;
+
+impl RequiredScope for MembersRead {
+    const SCOPE: Scope = Scope::MembersRead;
+}
+
+impl RequiredScope for MembersWrite {
+    const SCOPE: Scope = Scope::MembersWrite;
+}
+
+impl RequiredScope for ProjectsRead {
+    const SCOPE: Scope = Scope::ProjectsRead;
+}
+
+impl RequiredScope for ProjectsWrite {
+    const SCOPE: Scope = Scope::ProjectsWrite;
+}
+
+impl RequiredScope for AppsAdmin {
+    const SCOPE: Scope = Scope::AppsAdmin;
+}
+
+/// Extractor de la App que hizo la petición, exigiendo que tenga el permiso `S`.
+///
+/// Si la App no tiene el permiso, el handler ni siquiera se ejecuta y se responde con un 403.
+pub struct Scoped<S: RequiredScope>(pub AuthenticatedApp, PhantomData<S>);
+
+impl<S: RequiredScope> FromRequest for Scoped<S> {
+    type Error = ApiError;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        let app = req
+            .extensions()
+            .get::<AuthenticatedApp>()
+            .cloned()
+            .ok_or_else(missing_key);
+
+        ready(app.and_then(|app| {
+            app.require(S::SCOPE)?;
+            Ok(Scoped(app, PhantomData))
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{
+        http::StatusCode,
+        test::{init_service, try_call_service, TestRequest},
+        App,
+    };
+    use sqlx::SqlitePool;
+
+    use crate::{key_controller, testing, v1, v1::models::auth::Scope};
+
+    /// Envía la petición a la API sobre `pool` y entrega el estado de la respuesta.
+    ///
+    /// Igual que el servidor, los errores que llegan hasta la App se convierten en su respuesta.
+    async fn status_of(pool: &SqlitePool, req: TestRequest) -> StatusCode {
+        let app = init_service(
+            App::new()
+                .app_data(testing::state(pool))
+                .service(v1::routes()),
+        )
+        .await;
+
+        match try_call_service(&app, req.to_request()).await {
+            Ok(res) => res.status(),
+            Err(err) => err.error_response().status(),
+        }
+    }
+
+    /// Petición a la lista de Apps con la llave indicada.
+    fn list_apps(key: &str) -> TestRequest {
+        TestRequest::get()
+            .uri("/v1/auth/apps")
+            .insert_header(("Authorization", format!("Bearer {key}")))
+    }
+
+    /// Sin llave, con una llave mal formada o con una llave que no es de ninguna App se responde
+    /// con un 401, incluso en rutas que no existen.
+    #[actix_web::test]
+    async fn requests_without_a_valid_key_are_rejected() {
+        let pool = testing::pool().await;
+        testing::create_app(&pool, &Scope::ALL).await;
+        let (unknown_key, _) = key_controller().generate_key_and_hash();
+
+        let requests = [
+            TestRequest::get().uri("/v1/auth/apps"),
+            TestRequest::get().uri("/v1/no-existe"),
+            list_apps("no-es-una-llave"),
+            list_apps(&unknown_key.to_string()),
+        ];
+
+        for req in requests {
+            assert_eq!(status_of(&pool, req).await, StatusCode::UNAUTHORIZED);
+        }
+    }
+
+    /// Con una llave válida la petición llega al handler, con o sin el prefijo `Bearer`.
+    #[actix_web::test]
+    async fn valid_key_is_accepted() {
+        let pool = testing::pool().await;
+        let (_, key) = testing::create_app(&pool, &Scope::ALL).await;
+
+        assert_eq!(status_of(&pool, list_apps(&key)).await, StatusCode::OK);
+
+        let req = TestRequest::get()
+            .uri("/v1/auth/apps")
+            .insert_header(("Authorization", key));
+        assert_eq!(status_of(&pool, req).await, StatusCode::OK);
+    }
+
+    /// El registro sin llave se rechaza si `ALLOW_BOOTSTRAP` no está activado, aunque no exista
+    /// ninguna App.
+    #[actix_web::test]
+    async fn register_without_key_is_rejected_by_default() {
+        let pool = testing::pool().await;
+
+        let req = TestRequest::post()
+            .uri("/v1/auth/register")
+            .set_json(serde_json::json!({ "name": "Primera App" }));
+        assert_eq!(status_of(&pool, req).await, StatusCode::UNAUTHORIZED);
+    }
+
+    /// `Scoped<S>` rechaza con un 403 a las Apps que no tienen el permiso `S`, y deja pasar a
+    /// las que sí lo tienen.
+    #[actix_web::test]
+    async fn scoped_requires_the_scope() {
+        let pool = testing::pool().await;
+        let (_, reader) = testing::create_app(&pool, &[Scope::MembersRead]).await;
+        let (_, admin) = testing::create_app(&pool, &[Scope::AppsAdmin]).await;
+
+        assert_eq!(
+            status_of(&pool, list_apps(&reader)).await,
+            StatusCode::FORBIDDEN
+        );
+        assert_eq!(status_of(&pool, list_apps(&admin)).await, StatusCode::OK);
+    }
+
+    /// Una App sin permisos no puede usar ninguna ruta protegida por `Scoped`.
+    #[actix_web::test]
+    async fn app_without_scopes_is_forbidden() {
+        let pool = testing::pool().await;
+        let (app_id, key) = testing::create_app(&pool, &[]).await;
+
+        let req = TestRequest::post()
+            .uri(&format!("/v1/auth/revoke/{app_id}"))
+            .insert_header(("Authorization", format!("Bearer {key}")));
+        assert_eq!(status_of(&pool, req).await, StatusCode::FORBIDDEN);
+    }
+}
@@ -0,0 +1,246 @@
+//! Sesiones de integrantes del club.
+//!
+//! Además de la llave de la App, una petición puede traer el header `X-Session-Token` con la
+//! sesión de un integrante, que se obtiene al iniciar sesión (por ejemplo, con GitHub). Los
+//! handlers que actúan en nombre de un integrante lo piden con el extractor [`MemberSession`].
+//!
+//! Las sesiones no se guardan en la base de datos: el token lleva la UUID del integrante y su
+//! fecha de expiración, firmados con HMAC-SHA256 usando el secreto `SESSION_SECRET`. El mismo
+//! mecanismo se usa para firmar otros valores de corta duración, como el `state` de OAuth.
+
+use std::future::Future;
+use std::pin::Pin;
+
+use actix_web::{dev::Payload, web, FromRequest, HttpRequest};
+use chrono::NaiveDateTime;
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use sha2::Sha256;
+
+use crate::{
+    v1::{models::club_member::ClubMemberModel, responders::errors::ApiError},
+    AppState,
+};
+
+/// Header donde se envía el token de sesión.
+pub const SESSION_HEADER: &str = "X-Session-Token";
+
+/// Propósito de los tokens de sesión de integrantes.
+pub const SESSION_PURPOSE: &str = "session";
+
+/// Largo mínimo de `SESSION_SECRET`.
+const MIN_SECRET_LEN: usize = 32;
+
+/// Secretos de ejemplo que nunca se deben usar, como el de `.env.example`.
+const KNOWN_SECRETS: &[&str] = &["cambia-esto", "changeme", "change-me", "secret", "secreto"];
+
+/// Error de los tokens que no son válidos o fueron alterados.
+pub fn invalid_token() -> ApiError {
+    ApiError::Unauthorized(String::from("El token no es válido"))
+}
+
+/// Error de los tokens que ya expiraron.
+pub fn expired_token() -> ApiError {
+    ApiError::Unauthorized(String::from("El token expiró"))
+}
+
+/// Firma y verifica tokens de la API.
+///
+/// Un token tiene la forma `sujeto.expiración.firma`, y la firma incluye además el propósito del
+/// token, de forma que un token firmado para un propósito no sirva para otro.
+#[derive(Clone)]
+pub struct TokenSigner {
+    /// Secreto con el que se firman los tokens.
+    secret: Vec<u8>,
+    /// Duración por defecto de las sesiones.
+    session_ttl: chrono::Duration,
+}
+
+impl TokenSigner {
+    /// Crea un nuevo firmador.
+    pub fn new(secret: impl Into<Vec<u8>>, session_ttl: chrono::Duration) -> TokenSigner {
+        TokenSigner {
+            secret: secret.into(),
+            session_ttl,
+        }
+    }
+
+    /// Crea el firmador a partir de `SESSION_SECRET`.
+    ///
+    /// Si la variable no está definida (o está vacía) se usa un secreto aleatorio. Falla si el
+    /// secreto es uno de ejemplo o tiene menos de 32 caracteres, pues con él cualquiera podría
+    /// firmar sesiones a nombre de cualquier integrante.
+    pub fn from_env(session_ttl: chrono::Duration) -> anyhow::Result<TokenSigner> {
+        let secret = std::env::var("SESSION_SECRET").unwrap_or_default();
+
+        if secret.is_empty() {
+            println!("SESSION_SECRET no está definido; se usará un secreto aleatorio.");
+            return Ok(TokenSigner::random(session_ttl));
+        }
+
+        if KNOWN_SECRETS.contains(&secret.to_lowercase().as_str()) {
+            anyhow::bail!("SESSION_SECRET tiene un valor de ejemplo; genera uno nuevo");
+        }
+
+        if secret.len() < MIN_SECRET_LEN {
+            anyhow::bail!("SESSION_SECRET debe tener al menos {MIN_SECRET_LEN} caracteres");
+        }
+
+        Ok(TokenSigner::new(secret, session_ttl))
+    }
+
+    /// Crea un firmador con un secreto aleatorio.
+    ///
+    /// Los tokens firmados así dejan de ser válidos cuando se reinicia el servidor.
+    pub fn random(session_ttl: chrono::Duration) -> TokenSigner {
+        let mut secret = vec![0u8; 32];
+        rand::rngs::OsRng.fill_bytes(&mut secret);
+
+        TokenSigner::new(secret, session_ttl)
+    }
+
+    /// Firma un sujeto para un propósito, válido durante `ttl`.
+    ///
+    /// Entrega el token y su fecha de expiración (UTC).
+    pub fn sign(
+        &self,
+        purpose: &str,
+        subject: &str,
+        ttl: chrono::Duration,
+    ) -> (String, NaiveDateTime) {
+        let expires_at = chrono::Utc::now().naive_utc() + ttl;
+        let expires = expires_at.timestamp();
+
+        let signature = hex::encode(self.mac(purpose, subject, expires).finalize().into_bytes());
+
+        (format!("{subject}.{expires}.{signature}"), expires_at)
+    }
+
+    /// Firma una sesión para un integrante, con la duración por defecto.
+    pub fn sign_session(&self, member_id: &str) -> (String, NaiveDateTime) {
+        self.sign(SESSION_PURPOSE, member_id, self.session_ttl)
+    }
+
+    /// Verifica un token y entrega su sujeto.
+    ///
+    /// Falla si el token está mal formado, si la firma no corresponde al propósito, o si ya
+    /// expiró.
+    pub fn verify(&self, purpose: &str, token: &str) -> Result<String, ApiError> {
+        let mut parts = token.rsplitn(3, '.');
+        let (Some(signature), Some(expires), Some(subject)) =
+            (parts.next(), parts.next(), parts.next())
+        else {
+            return Err(invalid_token());
+        };
+
+        let expires: i64 = expires.parse().map_err(|_| invalid_token())?;
+        let signature = hex::decode(signature).map_err(|_| invalid_token())?;
+
+        self.mac(purpose, subject, expires)
+            .verify_slice(&signature)
+            .map_err(|_| invalid_token())?;
+
+        if expires <= chrono::Utc::now().timestamp() {
+            return Err(expired_token());
+        }
+
+        Ok(subject.to_owned())
+    }
+
+    /// Calcula el HMAC de un token.
+    fn mac(&self, purpose: &str, subject: &str, expires: i64) -> Hmac<Sha256> {
+        let mut mac = Hmac::<Sha256>::new_from_slice(&self.secret)
+            .expect("HMAC acepta secretos de cualquier largo");
+        mac.update(format!("{purpose}.{subject}.{expires}").as_bytes());
+        mac
+    }
+}
+
+/// URL a las que se puede enviar a un integrante al iniciar sesión.
+///
+/// Sin esta lista, cualquiera podría pedir un enlace de inicio de sesión (o un flujo de GitHub)
+/// que lleve el token del integrante a un sitio propio. Una URL se acepta solo si es igual a una
+/// de la lista, incluyendo su ruta y sus parámetros. Por defecto la lista está vacía.
+#[derive(Clone, Default)]
+pub struct RedirectAllowlist {
+    uris: Vec<reqwest::Url>,
+}
+
+impl RedirectAllowlist {
+    /// Crea la lista con las URL entregadas.
+    pub fn new(uris: Vec<reqwest::Url>) -> RedirectAllowlist {
+        RedirectAllowlist { uris }
+    }
+
+    /// Crea la lista a partir de `ALLOWED_REDIRECT_URIS`, con las URL separadas por comas.
+    ///
+    /// Si la variable no está definida, la lista queda vacía y no se acepta ninguna URL.
+    pub fn from_env() -> anyhow::Result<RedirectAllowlist> {
+        let uris = std::env::var("ALLOWED_REDIRECT_URIS")
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|uri| !uri.is_empty())
+            .map(|uri| {
+                reqwest::Url::parse(uri).map_err(|err| {
+                    anyhow::anyhow!("ALLOWED_REDIRECT_URIS: {uri} no es válida: {err}")
+                })
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        if uris.is_empty() {
+            println!(
+                "ALLOWED_REDIRECT_URIS no está definido; no se podrá iniciar sesión con GitHub ni por correo."
+            );
+        }
+
+        Ok(RedirectAllowlist::new(uris))
+    }
+
+    /// Revisa una URL de redirección y la entrega ya interpretada, si está en la lista.
+    pub fn check(&self, uri: &str) -> Option<reqwest::Url> {
+        let uri = reqwest::Url::parse(uri).ok()?;
+
+        self.uris.contains(&uri).then_some(uri)
+    }
+}
+
+/// Extractor del integrante que inició la sesión.
+///
+/// Falla con un 401 si la petición no trae el header `X-Session-Token`, si el token no es válido
+/// o si el integrante ya no existe.
+pub struct MemberSession {
+    /// El integrante dueño de la sesión.
+    pub member: ClubMemberModel,
+}
+
+impl FromRequest for MemberSession {
+    type Error = ApiError;
+    type Future = Pin<Box<dyn Future<Output = Result<Self, Self::Error>>>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        let token = req
+            .headers()
+            .get(SESSION_HEADER)
+            .and_then(|header| header.to_str().ok())
+            .map(String::from);
+        let data = req.app_data::<web::Data<AppState>>().cloned();
+
+        Box::pin(async move {
+            let token = token.ok_or_else(|| {
+                ApiError::Unauthorized(String::from("Se requiere una sesión de integrante"))
+            })?;
+            let data = data.expect("AppState no fue registrado en la App");
+
+            let member_id = data.token_signer.verify(SESSION_PURPOSE, &token)?;
+            // Si el integrante ya no existe, la sesión simplemente deja de ser válida.
+            let member = match ClubMemberModel::get_one(member_id, &data.pool).await {
+                Ok(member) => member,
+                Err(sqlx::Error::RowNotFound) => return Err(invalid_token()),
+                Err(err) => return Err(err.into()),
+            };
+
+            Ok(MemberSession { member })
+        })
+    }
+}
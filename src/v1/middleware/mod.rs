@@ -0,0 +1,27 @@
+//! Middlewares de la API.
+//!
+//! Los middlewares envuelven a los handlers y se ejecutan antes que ellos. Acá vive todo lo que
+//! tiene que ocurrir en cada petición sin importar la ruta, como verificar quién está llamando a
+//! la API y cuántas veces lo ha hecho, junto con los extractores que identifican a la App y al
+//! integrante que hacen la petición.
+
+pub mod api_key;
+pub mod member_session;
+pub mod rate_limit;
+
+use actix_web::dev::ServiceRequest;
+
+use crate::AppState;
+
+/// IP del cliente que hace la petición.
+///
+/// Por defecto es la IP de la conexión, pues los headers `Forwarded` y `X-Forwarded-For` los puede
+/// inventar cualquier cliente. Solo si la API está detrás de un proxy de confianza
+/// (`TRUST_PROXY=true`) se usa la IP que indica el proxy en esos headers.
+pub fn client_ip(req: &ServiceRequest, data: &AppState) -> Option<String> {
+    if data.trust_proxy {
+        return req.connection_info().realip_remote_addr().map(String::from);
+    }
+
+    req.peer_addr().map(|addr| addr.ip().to_string())
+}
@@ -0,0 +1,313 @@
+//! Límite de peticiones por minuto.
+//!
+//! Cada App puede hacer una cantidad limitada de peticiones por minuto. El límite se guarda en la
+//! fila de la App (`apps.rate_limit`), y si no existe se usa el límite por defecto configurado en
+//! `RATE_LIMIT_PER_MINUTE`. Las peticiones que llegan sin App (como el registro de la primera App)
+//! o con una llave inválida se cuentan según la IP del cliente (ver [`super::client_ip`]).
+//!
+//! Todas las respuestas incluyen los headers `X-RateLimit-Limit`, `X-RateLimit-Remaining` y
+//! `X-RateLimit-Reset`. Cuando se supera el límite, se responde con un 429 y el header
+//! `Retry-After`.
+//!
+//! Los contadores viven en memoria, así que se reinician junto con el servidor.
+
+use std::collections::HashMap;
+use std::future::{ready, Future, Ready};
+use std::pin::Pin;
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use actix_web::{
+    dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
+    error::InternalError,
+    http::header::{HeaderMap, HeaderName, HeaderValue},
+    web, Error, HttpMessage,
+};
+
+use crate::{
+    v1::{
+        middleware::{api_key::authenticate, client_ip},
+        responders::errors::ApiError,
+    },
+    AppState,
+};
+
+/// Duración de cada ventana de conteo.
+const WINDOW: Duration = Duration::from_secs(60);
+
+/// Cantidad de contadores a partir de la cual se limpian los de ventanas ya terminadas.
+const PRUNE_THRESHOLD: usize = 1024;
+
+/// Estado de un cliente dentro de la ventana actual.
+#[derive(Debug, Clone, Copy)]
+pub struct RateLimitStatus {
+    /// Peticiones permitidas por ventana.
+    pub limit: u64,
+    /// Peticiones que quedan en la ventana actual.
+    pub remaining: u64,
+    /// Segundos que faltan para que empiece la siguiente ventana.
+    pub reset: u64,
+}
+
+impl RateLimitStatus {
+    /// Agrega los headers `X-RateLimit-*` a una respuesta.
+    pub fn apply_headers(&self, headers: &mut HeaderMap) {
+        let values = [
+            ("x-ratelimit-limit", self.limit),
+            ("x-ratelimit-remaining", self.remaining),
+            ("x-ratelimit-reset", self.reset),
+        ];
+
+        for (name, value) in values {
+            headers.insert(HeaderName::from_static(name), HeaderValue::from(value));
+        }
+    }
+}
+
+/// Contadores de peticiones de todos los clientes.
+///
+/// Se comparte entre todos los workers de Actix via el [`AppState`].
+#[derive(Clone)]
+pub struct RateLimiter {
+    /// Límite para los clientes que no tienen uno propio.
+    default_limit: u64,
+    /// Inicio de la ventana actual y peticiones hechas, según cada cliente.
+    windows: Arc<Mutex<HashMap<String, (Instant, u64)>>>,
+}
+
+impl RateLimiter {
+    /// Crea un nuevo conjunto de contadores con el límite por defecto entregado.
+    pub fn new(default_limit: u64) -> RateLimiter {
+        RateLimiter {
+            default_limit,
+            windows: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Cuenta una petición del cliente `key`.
+    ///
+    /// Si el cliente aún puede hacer peticiones se entrega su estado, y si no, un error 429 con el
+    /// mismo estado.
+    pub fn hit(&self, key: &str, limit: Option<u64>) -> Result<RateLimitStatus, ApiError> {
+        let limit = limit.unwrap_or(self.default_limit);
+        let now = Instant::now();
+
+        let mut windows = self.windows.lock().expect("El mutex del rate limiter fue envenenado");
+
+        if windows.len() > PRUNE_THRESHOLD {
+            windows.retain(|_, (start, _)| now.duration_since(*start) < WINDOW);
+        }
+
+        let (start, count) = windows.entry(key.to_owned()).or_insert((now, 0));
+        if now.duration_since(*start) >= WINDOW {
+            *start = now;
+            *count = 0;
+        }
+
+        let reset = WINDOW
+            .saturating_sub(now.duration_since(*start))
+            .as_secs()
+            .max(1);
+
+        if *count >= limit {
+            return Err(ApiError::TooManyRequests(RateLimitStatus {
+                limit,
+                remaining: 0,
+                reset,
+            }));
+        }
+
+        *count += 1;
+
+        Ok(RateLimitStatus {
+            limit,
+            remaining: limit - *count,
+            reset,
+        })
+    }
+}
+
+/// Middleware que aplica el límite de peticiones.
+///
+/// Debe ejecutarse antes que [`super::api_key::ApiKeyAuth`], para que también se cuenten las
+/// peticiones con una llave inválida, por lo que en Actix se registra con `wrap` *después* que
+/// este. Para saber a qué App cobrarle la petición busca la llave él mismo, y deja la App
+/// guardada en la petición para que [`super::api_key::ApiKeyAuth`] no la vuelva a buscar.
+pub struct RateLimit;
+
+impl<S, B> Transform<S, ServiceRequest> for RateLimit
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Transform = RateLimitMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RateLimitMiddleware {
+            service: Rc::new(service),
+        }))
+    }
+}
+
+/// Servicio generado por [`RateLimit`] para cada worker de Actix.
+pub struct RateLimitMiddleware<S> {
+    service: Rc<S>,
+}
+
+impl<S, B> Service<ServiceRequest> for RateLimitMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let service = Rc::clone(&self.service);
+
+        Box::pin(async move {
+            let data = req
+                .app_data::<web::Data<AppState>>()
+                .expect("AppState no fue registrado en la App")
+                .clone();
+
+            // Las peticiones sin llave o con una llave que no sirve se cuentan según la IP. El
+            // error de la llave lo responde `ApiKeyAuth`.
+            let (key, limit) = match authenticate(&req, &data).await {
+                Ok(Some(app)) => {
+                    let key = format!("app:{}", app.app.uuid);
+                    let limit = app.app.rate_limit.map(|limit| limit.max(0) as u64);
+                    req.extensions_mut().insert(app);
+
+                    (key, limit)
+                }
+                Ok(None) | Err(_) => (
+                    format!("ip:{}", client_ip(&req, &data).unwrap_or_default()),
+                    None,
+                ),
+            };
+
+            let status = data.rate_limiter.hit(&key, limit)?;
+
+            // Los errores de los middlewares internos, como el de una llave inválida, se
+            // convierten aquí en su respuesta para que también lleven los headers.
+            let mut res = match service.call(req).await {
+                Ok(res) => res,
+                Err(err) => {
+                    let mut res = err.error_response();
+                    status.apply_headers(res.headers_mut());
+
+                    return Err(InternalError::from_response(err, res).into());
+                }
+            };
+            status.apply_headers(res.headers_mut());
+
+            Ok(res)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use actix_web::{
+        http::StatusCode,
+        test::{init_service, try_call_service, TestRequest},
+        App,
+    };
+
+    use super::{RateLimiter, WINDOW};
+    use crate::{testing, v1, v1::responders::errors::ApiError};
+
+    /// Mueve el inicio de la ventana actual de `key` una ventana completa hacia atrás.
+    fn expire_window(limiter: &RateLimiter, key: &str) {
+        let mut windows = limiter.windows.lock().unwrap();
+        let (start, _) = windows
+            .get_mut(key)
+            .expect("El cliente debería tener una ventana");
+        *start = Instant::now() - WINDOW;
+    }
+
+    /// Se rechaza la petición que supera el límite, sin afectar a los demás clientes.
+    #[test]
+    fn limit_is_enforced_per_client() {
+        let limiter = RateLimiter::new(10);
+
+        assert_eq!(limiter.hit("app:a", Some(2)).unwrap().remaining, 1);
+        assert_eq!(limiter.hit("app:a", Some(2)).unwrap().remaining, 0);
+        match limiter.hit("app:a", Some(2)) {
+            Err(ApiError::TooManyRequests(status)) => {
+                assert_eq!(status.limit, 2);
+                assert_eq!(status.remaining, 0);
+                assert!(status.reset >= 1);
+            }
+            _ => panic!("La tercera petición debería superar el límite"),
+        }
+
+        // Sin límite propio se usa el por defecto.
+        assert_eq!(limiter.hit("app:b", None).unwrap().remaining, 9);
+    }
+
+    /// Cuando termina la ventana el contador vuelve a empezar.
+    #[test]
+    fn window_resets_after_a_minute() {
+        let limiter = RateLimiter::new(10);
+
+        limiter.hit("ip:127.0.0.1", Some(1)).unwrap();
+        assert!(limiter.hit("ip:127.0.0.1", Some(1)).is_err());
+
+        expire_window(&limiter, "ip:127.0.0.1");
+
+        let status = limiter.hit("ip:127.0.0.1", Some(1)).unwrap();
+        assert_eq!(status.remaining, 0);
+        assert!(status.reset >= WINDOW.as_secs() - 1);
+    }
+
+    /// Los errores de los middlewares internos también llevan los headers del límite.
+    #[actix_web::test]
+    async fn rejected_key_gets_rate_limit_headers() {
+        let pool = testing::pool().await;
+        let app = init_service(
+            App::new()
+                .app_data(testing::state(&pool))
+                .service(v1::routes()),
+        )
+        .await;
+
+        let req = TestRequest::get()
+            .uri("/v1/members")
+            .insert_header(("Authorization", "Bearer llave-invalida"))
+            .to_request();
+        // Igual que el servidor, los errores que llegan hasta la App se convierten en su respuesta.
+        let res = match try_call_service(&app, req).await {
+            Ok(res) => res.into_parts().1.map_into_boxed_body(),
+            Err(err) => err.error_response(),
+        };
+
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+        let header = |name: &str| {
+            res.headers()
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_owned)
+        };
+        assert_eq!(
+            header("x-ratelimit-limit"),
+            Some(testing::RATE_LIMIT.to_string())
+        );
+        assert_eq!(
+            header("x-ratelimit-remaining"),
+            Some((testing::RATE_LIMIT - 1).to_string())
+        );
+        assert!(header("x-ratelimit-reset").is_some());
+    }
+}
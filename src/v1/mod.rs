@@ -2,8 +2,9 @@
 //!
 //! Cada módulo de la API contiene los modelos de conexión con la BDD, los handles (en la carpeta
 //! res) para cada respuesta de la API, los responders (o estructuras diseñadas para tener
-//! respuestas estándar) y los schemas (estructuras para las respuestas específicas de cada
-//! handler.)
+//! respuestas estándar), los schemas (estructuras para las respuestas específicas de cada
+//! handler), los middlewares que se ejecutan antes de cada handler y las políticas que deciden
+//! quién puede hacer qué.
 //!
 //! La idea es que, en caso de necesitar cambiar las rutas, eliminar algunas, o qué se yo, una vez
 //! que la API esté en "producción", podamos crear un nuevo módulo copia de este, cosa de que el
@@ -11,7 +12,11 @@
 
 use actix_web::web;
 
+pub mod middleware;
 pub mod models;
+pub mod openapi;
+pub mod policy;
+pub mod providers;
 pub mod res;
 pub mod responders;
 pub mod schemas;
@@ -21,9 +26,33 @@ pub mod schemas;
 /// Esta función agrupa todas las rutas dentro del módulo "res" bajo el scope `/v1`. En términos
 /// prácticos, esto significa que todas las rutas de este módulo empiezan con este `/v1` antes del
 /// resto de su URL.
-pub fn routes() -> actix_web::Scope {
+///
+/// Todo el scope está protegido por [`middleware::api_key::ApiKeyAuth`], así que cada petición
+/// necesita una llave de la API válida, y por [`middleware::rate_limit::RateLimit`], que limita
+/// la cantidad de peticiones por minuto de cada App.
+///
+/// Los errores al leer los cuerpos JSON y los parámetros de la URL se responden como errores de
+/// validación (ver [`schemas::validation`]).
+pub fn routes() -> actix_web::Scope<
+    impl actix_web::dev::ServiceFactory<
+        actix_web::dev::ServiceRequest,
+        Config = (),
+        Response = actix_web::dev::ServiceResponse,
+        Error = actix_web::Error,
+        InitError = (),
+    >,
+> {
+    // Actix ejecuta primero el último middleware registrado, así que las peticiones se cuentan
+    // antes de verificar la llave, incluso las que traen una llave inválida.
     web::scope("/v1")
+        .app_data(web::JsonConfig::default().error_handler(schemas::validation::json_error_handler))
+        .app_data(
+            web::QueryConfig::default().error_handler(schemas::validation::query_error_handler),
+        )
+        .wrap(middleware::api_key::ApiKeyAuth)
+        .wrap(middleware::rate_limit::RateLimit)
         .service(res::add_member_routes())
         .service(res::add_auth_routes())
         .service(res::add_project_routes())
+        .service(res::add_me_routes())
 }
@@ -5,14 +5,20 @@
 //!
 //! Finalmente, corresponde a cualquier software que consuma los endpoints de esta API.
 
+use std::{collections::HashMap, str::FromStr};
+
 use chrono::NaiveDateTime;
 use serde::{Deserialize, Serialize};
+use sqlx::{sqlite::SqliteQueryResult, QueryBuilder, Sqlite};
+use utoipa::ToSchema;
+
+use crate::v1::schemas::auth::{AppDocument, CreateAppSchema};
 
 /// Modelo de una fila de una aplicación.
 ///
 /// Todos estos campos corresponden directamente con las columnas de la tabla de apps en la base de
 /// datos.
-#[derive(Debug, Serialize, Deserialize, sqlx::FromRow)]
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
 pub struct AppModel {
     /// Identificador único de la APP.
     pub uuid: String,
@@ -29,4 +35,471 @@ pub struct AppModel {
     pub updated_at: NaiveDateTime,
     /// Timestamp opcional que indica cuando fue eliminada esta App.
     pub deleted_at: Option<NaiveDateTime>,
+    /// El token anterior de la App, que sigue siendo válido por un tiempo después de regenerar la
+    /// llave.
+    pub previous_api_token: Option<String>,
+    /// Timestamp hasta el cual el token anterior sigue siendo válido.
+    pub previous_api_token_expires_at: Option<NaiveDateTime>,
+    /// Timestamp opcional desde el cual la llave de la App deja de funcionar.
+    pub expires_at: Option<NaiveDateTime>,
+    /// Timestamp de la última vez que la llave de la App fue usada.
+    pub last_used_at: Option<NaiveDateTime>,
+    /// IP desde la cual la llave de la App fue usada por última vez.
+    pub last_used_ip: Option<String>,
+    /// Cantidad de peticiones por minuto que puede hacer la App. Si no existe, se usa el límite
+    /// por defecto de la API.
+    pub rate_limit: Option<i64>,
+}
+
+/// Permisos que puede tener una App sobre la API.
+///
+/// Cada handler declara el permiso que necesita, de forma que, por ejemplo, la página del club
+/// pueda tener una llave de solo lectura mientras que el panel de administración puede escribir.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, ToSchema)]
+pub enum Scope {
+    /// Leer a los integrantes del club.
+    #[serde(rename = "members:read")]
+    MembersRead,
+
+    /// Crear, actualizar y eliminar integrantes del club.
+    #[serde(rename = "members:write")]
+    MembersWrite,
+
+    /// Leer los proyectos del club.
+    #[serde(rename = "projects:read")]
+    ProjectsRead,
+
+    /// Crear, actualizar y eliminar proyectos del club.
+    #[serde(rename = "projects:write")]
+    ProjectsWrite,
+
+    /// Administrar las Apps que consumen la API.
+    #[serde(rename = "apps:admin")]
+    AppsAdmin,
+}
+
+impl Scope {
+    /// Todos los permisos existentes.
+    pub const ALL: [Scope; 5] = [
+        Scope::MembersRead,
+        Scope::MembersWrite,
+        Scope::ProjectsRead,
+        Scope::ProjectsWrite,
+        Scope::AppsAdmin,
+    ];
+
+    /// Permisos que recibe una App si no pide ninguno al registrarse: solo lectura.
+    pub const DEFAULT: [Scope; 2] = [Scope::MembersRead, Scope::ProjectsRead];
+
+    /// Nombre del permiso tal como se guarda en la base de datos.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Scope::MembersRead => "members:read",
+            Scope::MembersWrite => "members:write",
+            Scope::ProjectsRead => "projects:read",
+            Scope::ProjectsWrite => "projects:write",
+            Scope::AppsAdmin => "apps:admin",
+        }
+    }
+}
+
+impl std::fmt::Display for Scope {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl FromStr for Scope {
+    type Err = anyhow::Error;
+
+    /// Convierte un &str en un Scope.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Scope::ALL
+            .into_iter()
+            .find(|scope| scope.as_str() == s)
+            .ok_or_else(|| anyhow::anyhow!("No existe el permiso {s}."))
+    }
+}
+
+impl AppModel {
+    /// Busca la App dueña de una llave de la API.
+    ///
+    /// Las llaves nunca se guardan en texto plano, por lo que se compara el hash de la parte
+    /// secreta de la llave con el hash guardado en la fila de cada App. Las Apps eliminadas no son
+    /// consideradas, por lo que sus llaves dejan de funcionar.
+    ///
+    /// Si la llave fue regenerada hace poco, la llave anterior sigue funcionando hasta que termine
+    /// su periodo de gracia.
+    pub async fn find_by_key_hash(
+        hash: &str,
+        pool: &sqlx::SqlitePool,
+    ) -> Result<AppModel, sqlx::Error> {
+        let now = chrono::Utc::now().naive_utc();
+
+        sqlx::query_as!(
+            AppModel,
+            r#"SELECT * FROM apps
+            WHERE deleted_at IS NULL
+            AND (api_token = ?
+                OR (previous_api_token = ? AND previous_api_token_expires_at > ?))"#,
+            hash,
+            hash,
+            now
+        )
+        .fetch_one(pool)
+        .await
+    }
+
+    /// Obtiene una App según su UUID.
+    pub async fn find_by_id(app_id: &str, pool: &sqlx::SqlitePool) -> Result<AppModel, sqlx::Error> {
+        sqlx::query_as!(AppModel, "SELECT * FROM apps WHERE uuid = ?", app_id)
+            .fetch_one(pool)
+            .await
+    }
+
+    /// Reemplaza el token de una App.
+    ///
+    /// El token anterior se guarda y sigue siendo válido hasta `grace_until`. Si no se entrega
+    /// un límite, el token anterior deja de funcionar inmediatamente.
+    pub async fn rotate_key(
+        app: &AppModel,
+        new_hash: &str,
+        grace_until: Option<NaiveDateTime>,
+        pool: &sqlx::SqlitePool,
+    ) -> Result<SqliteQueryResult, sqlx::Error> {
+        let previous = grace_until.map(|_| app.api_token.to_owned());
+
+        sqlx::query!(
+            r#"UPDATE apps
+            SET api_token = ?, previous_api_token = ?, previous_api_token_expires_at = ?,
+                updated_at = CURRENT_TIMESTAMP
+            WHERE uuid = ?"#,
+            new_hash,
+            previous,
+            grace_until,
+            app.uuid
+        )
+        .execute(pool)
+        .await
+    }
+
+    /// Indica si la llave de la App ya expiró.
+    pub fn is_expired(&self) -> bool {
+        self.expires_at
+            .is_some_and(|expires_at| expires_at <= chrono::Utc::now().naive_utc())
+    }
+
+    /// Indica si se debe registrar un nuevo uso de la llave con [`AppModel::touch`].
+    ///
+    /// Para no escribir en la base de datos en cada petición, el uso se registra a lo más una vez
+    /// por minuto, así que `last_used_at` y `last_used_ip` pueden tener hasta un minuto de atraso.
+    pub fn needs_touch(&self) -> bool {
+        self.last_used_at.is_none_or(|last_used_at| {
+            chrono::Utc::now().naive_utc() - last_used_at >= chrono::Duration::minutes(1)
+        })
+    }
+
+    /// Registra que la llave de la App fue usada ahora desde la IP entregada.
+    pub async fn touch(
+        app_id: &str,
+        ip: Option<&str>,
+        pool: &sqlx::SqlitePool,
+    ) -> Result<SqliteQueryResult, sqlx::Error> {
+        let now = chrono::Utc::now().naive_utc();
+
+        sqlx::query!(
+            "UPDATE apps SET last_used_at = ?, last_used_ip = ? WHERE uuid = ?",
+            now,
+            ip,
+            app_id
+        )
+        .execute(pool)
+        .await
+    }
+
+    /// Cuenta cuántas Apps existen en la base de datos, incluyendo las revocadas.
+    pub async fn count(pool: &sqlx::SqlitePool) -> Result<i32, sqlx::Error> {
+        sqlx::query_scalar!("SELECT COUNT(*) FROM apps")
+            .fetch_one(pool)
+            .await
+    }
+
+    /// Obtiene todas las Apps.
+    ///
+    /// Las Apps revocadas solo se incluyen si se pide explícitamente. Si se entrega `unused_since`,
+    /// solo se incluyen las Apps que no han usado su llave desde esa fecha; las Apps que nunca la
+    /// han usado cuentan desde su creación.
+    pub async fn get_all(
+        include_revoked: bool,
+        unused_since: Option<NaiveDateTime>,
+        pool: &sqlx::SqlitePool,
+    ) -> Result<Vec<AppModel>, sqlx::Error> {
+        sqlx::query_as!(
+            AppModel,
+            r#"SELECT * FROM apps
+            WHERE (deleted_at IS NULL OR ?)
+            AND (? IS NULL OR COALESCE(last_used_at, created_at) < ?)
+            ORDER BY created_at"#,
+            include_revoked,
+            unused_since,
+            unused_since
+        )
+        .fetch_all(pool)
+        .await
+    }
+
+    /// Registra una nueva App, con el hash de su llave y sus permisos.
+    pub async fn create(
+        app_id: &str,
+        app: &CreateAppSchema,
+        api_token: &str,
+        scopes: &[Scope],
+        pool: &sqlx::SqlitePool,
+    ) -> Result<(), sqlx::Error> {
+        let mut tx = pool.begin().await?;
+
+        sqlx::query(
+            r#"
+        INSERT INTO apps(uuid, name, description, api_token, expires_at, rate_limit)
+        VALUES (?, ?, ?, ?, ?, ?);"#,
+        )
+        .bind(app_id)
+        .bind(&app.name)
+        .bind(&app.description)
+        .bind(api_token)
+        .bind(app.expires_at)
+        .bind(app.rate_limit)
+        .execute(&mut *tx)
+        .await?;
+
+        AppModel::grant_scopes(app_id, scopes, &mut tx).await?;
+
+        tx.commit().await
+    }
+
+    /// Registra la primera App de la API, con todos los permisos.
+    ///
+    /// Entrega `false` sin registrarla si ya existe alguna App (incluso revocada). La revisión se
+    /// hace en la misma consulta que el registro, para que dos registros simultáneos no puedan
+    /// crear dos Apps con todos los permisos.
+    pub async fn create_first(
+        app_id: &str,
+        app: &CreateAppSchema,
+        api_token: &str,
+        pool: &sqlx::SqlitePool,
+    ) -> Result<bool, sqlx::Error> {
+        let mut tx = pool.begin().await?;
+
+        let result = sqlx::query(
+            r#"
+        INSERT INTO apps(uuid, name, description, api_token, expires_at, rate_limit)
+        SELECT ?, ?, ?, ?, ?, ?
+        WHERE NOT EXISTS (SELECT 1 FROM apps);"#,
+        )
+        .bind(app_id)
+        .bind(&app.name)
+        .bind(&app.description)
+        .bind(api_token)
+        .bind(app.expires_at)
+        .bind(app.rate_limit)
+        .execute(&mut *tx)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Ok(false);
+        }
+
+        AppModel::grant_scopes(app_id, &Scope::ALL, &mut tx).await?;
+
+        tx.commit().await?;
+
+        Ok(true)
+    }
+
+    /// Revoca una App, de forma que su llave deja de funcionar.
+    ///
+    /// La fila no se elimina, por lo que la App puede ser restaurada más tarde. Entrega `false` sin
+    /// revocarla si es la última App activa (sin revocar ni expirar) con el permiso `apps:admin`,
+    /// pues sin ella nadie podría volver a administrar las Apps desde la API. La revisión se hace
+    /// en la misma consulta que la revocación, para que dos revocaciones simultáneas no puedan
+    /// dejar a la API sin administradores.
+    pub async fn revoke(app_id: &str, pool: &sqlx::SqlitePool) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query!(
+            r#"UPDATE apps SET deleted_at = CURRENT_TIMESTAMP, updated_at = CURRENT_TIMESTAMP
+            WHERE uuid = ? AND deleted_at IS NULL
+            AND (
+                NOT EXISTS (
+                    SELECT 1 FROM app_scopes WHERE app_uuid = ? AND scope = 'apps:admin'
+                )
+                OR EXISTS (
+                    SELECT 1 FROM apps AS other
+                    JOIN app_scopes ON app_scopes.app_uuid = other.uuid
+                    WHERE app_scopes.scope = 'apps:admin'
+                    AND other.uuid <> ?
+                    AND other.deleted_at IS NULL
+                    AND (other.expires_at IS NULL OR other.expires_at > CURRENT_TIMESTAMP)
+                )
+            )"#,
+            app_id,
+            app_id,
+            app_id
+        )
+        .execute(pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Actualiza el nombre, la descripción y el límite de peticiones de una App.
+    ///
+    /// `changes` tiene todos los datos nuevos de la App, así que un valor en `None` borra el
+    /// anterior.
+    pub async fn update(
+        app_id: &str,
+        changes: AppDocument,
+        pool: &sqlx::SqlitePool,
+    ) -> Result<AppModel, sqlx::Error> {
+        sqlx::query!(
+            r#"UPDATE apps SET name = ?, description = ?, rate_limit = ?, updated_at = CURRENT_TIMESTAMP
+            WHERE uuid = ?"#,
+            changes.name,
+            changes.description,
+            changes.rate_limit,
+            app_id
+        )
+        .execute(pool)
+        .await?;
+
+        AppModel::find_by_id(app_id, pool).await
+    }
+
+    /// Restaura una App revocada, de forma que su llave vuelve a funcionar.
+    pub async fn restore(app_id: &str, pool: &sqlx::SqlitePool) -> Result<AppModel, sqlx::Error> {
+        sqlx::query!(
+            r#"UPDATE apps SET deleted_at = NULL, updated_at = CURRENT_TIMESTAMP WHERE uuid = ?"#,
+            app_id
+        )
+        .execute(pool)
+        .await?;
+
+        AppModel::find_by_id(app_id, pool).await
+    }
+
+    /// Obtiene los permisos de una App.
+    ///
+    /// Si en la base de datos hay algún permiso que ya no existe, simplemente se ignora.
+    pub async fn get_scopes(
+        app_id: &str,
+        pool: &sqlx::SqlitePool,
+    ) -> Result<Vec<Scope>, sqlx::Error> {
+        let scopes = sqlx::query_scalar!("SELECT scope FROM app_scopes WHERE app_uuid = ?", app_id)
+            .fetch_all(pool)
+            .await?;
+
+        Ok(scopes
+            .iter()
+            .filter_map(|scope| scope.parse().ok())
+            .collect())
+    }
+
+    /// Obtiene los permisos de todas las Apps, agrupados según la UUID de cada App.
+    pub async fn get_all_scopes(
+        pool: &sqlx::SqlitePool,
+    ) -> Result<HashMap<String, Vec<Scope>>, sqlx::Error> {
+        let rows = sqlx::query!("SELECT app_uuid, scope FROM app_scopes ORDER BY id")
+            .fetch_all(pool)
+            .await?;
+
+        let mut scopes: HashMap<String, Vec<Scope>> = HashMap::new();
+        for row in rows {
+            if let Ok(scope) = row.scope.parse() {
+                scopes.entry(row.app_uuid).or_default().push(scope);
+            }
+        }
+
+        Ok(scopes)
+    }
+
+    /// Otorga permisos a una App.
+    ///
+    /// Los permisos que la App ya tenía se ignoran.
+    pub async fn grant_scopes(
+        app_id: &str,
+        scopes: &[Scope],
+        conn: &mut sqlx::SqliteConnection,
+    ) -> Result<(), sqlx::Error> {
+        if scopes.is_empty() {
+            return Ok(());
+        }
+
+        let mut qb: QueryBuilder<Sqlite> =
+            QueryBuilder::new("INSERT OR IGNORE INTO app_scopes(app_uuid, scope) ");
+
+        qb.push_values(scopes, |mut b, scope| {
+            b.push_bind(app_id).push_bind(scope.as_str());
+        });
+
+        qb.build().execute(conn).await?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, Utc};
+
+    use super::{AppModel, Scope};
+    use crate::{key_controller, testing};
+
+    /// Hash de una llave entregada por [`testing::create_app`].
+    fn hash_of(key: &str) -> String {
+        let pak = prefixed_api_key::PrefixedApiKey::from_string(key).unwrap();
+        key_controller().long_token_hashed(&pak)
+    }
+
+    /// Rota la llave de la App y entrega el hash de la nueva.
+    async fn rotate(app_id: &str, grace: Option<Duration>, pool: &sqlx::SqlitePool) -> String {
+        let app = AppModel::find_by_id(app_id, pool).await.unwrap();
+        let (_, new_hash) = key_controller().generate_key_and_hash();
+        let grace_until = grace.map(|grace| Utc::now().naive_utc() + grace);
+
+        AppModel::rotate_key(&app, &new_hash, grace_until, pool)
+            .await
+            .unwrap();
+
+        new_hash
+    }
+
+    /// Durante el periodo de gracia se aceptan tanto la llave nueva como la anterior.
+    #[actix_web::test]
+    async fn previous_key_works_during_grace_period() {
+        let pool = testing::pool().await;
+        let (app_id, key) = testing::create_app(&pool, &Scope::ALL).await;
+
+        let new_hash = rotate(&app_id, Some(Duration::hours(1)), &pool).await;
+
+        for hash in [hash_of(&key), new_hash] {
+            let app = AppModel::find_by_key_hash(&hash, &pool).await.unwrap();
+            assert_eq!(app.uuid, app_id);
+        }
+    }
+
+    /// Una vez terminado el periodo de gracia, o sin periodo de gracia, solo se acepta la llave
+    /// nueva.
+    #[actix_web::test]
+    async fn previous_key_is_rejected_after_grace_period() {
+        let pool = testing::pool().await;
+
+        for grace in [Some(Duration::seconds(-1)), None] {
+            let (app_id, key) = testing::create_app(&pool, &Scope::ALL).await;
+            let new_hash = rotate(&app_id, grace, &pool).await;
+
+            assert!(matches!(
+                AppModel::find_by_key_hash(&hash_of(&key), &pool).await,
+                Err(sqlx::Error::RowNotFound)
+            ));
+            assert!(AppModel::find_by_key_hash(&new_hash, &pool).await.is_ok());
+        }
+    }
 }
@@ -3,11 +3,20 @@
 //! Este modelo corresponde a la abstracción de la tabla homónima, y que se usa para trabajar con
 //! el estado de los miembros del club ExDev.
 
+use std::str::FromStr;
+
 use chrono::NaiveDateTime;
 use serde::{Deserialize, Serialize};
-use sqlx::sqlite::SqliteQueryResult;
+use sqlx::{sqlite::SqliteQueryResult, QueryBuilder, Sqlite};
+use utoipa::ToSchema;
 
-use crate::v1::schemas::club_member::{CreateMemberSchema, UpdateMemberSchema};
+use crate::v1::{
+    models::{member_state_history::MemberStateChangeModel, ChangeAuthor},
+    schemas::{
+        club_member::{CreateMemberSchema, MemberDocument, MemberListQuery},
+        pagination::{like_pattern, PageRequest, PageResult},
+    },
+};
 
 /// Modelo de un miembro del club.
 ///
@@ -31,12 +40,25 @@ pub struct ClubMemberModel {
     pub created_at: NaiveDateTime,
     /// Fecha de la Última modificación de este fila.
     pub updated_at: NaiveDateTime,
+    /// El rol del integrante dentro del club, detallado más a fondo en su Enum.
+    pub role: String,
+    /// Fecha en que se eliminó al integrante, si fue eliminado. Los integrantes eliminados no
+    /// aparecen en la API, pero se pueden restaurar.
+    pub deleted_at: Option<NaiveDateTime>,
+    /// ID numérico de la cuenta de GitHub del integrante. Se guarda la primera vez que inicia
+    /// sesión con GitHub y, a diferencia del nombre de usuario, no cambia.
+    pub github_id: Option<i64>,
 }
 
 /// Estado de un miembro del club.
 ///
-/// Se reconocen actualmente 4 estados distintos de un miembro del Exdev.
-#[derive(Debug, Serialize, Deserialize)]
+/// Se reconocen actualmente 4 estados distintos de un miembro del Exdev. Los cambios de estado
+/// permitidos son:
+///
+/// - `Active` y `Unactive` pueden pasar a cualquier otro estado.
+/// - `NoLongerAMember` puede volver a `Active` o `Unactive`, si la persona vuelve al club.
+/// - `Graduated` es final. Solo un administrador puede sacar a alguien de ahí.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
 pub enum MemberState {
     // Integrante Activo del club.
     Active,
@@ -48,21 +70,35 @@ pub enum MemberState {
     NoLongerAMember,
 }
 
-// FIX: Esta implementación está mal, pues puede fallar. From se supone que es para
-// implementaciones perfectas, pero al ser este un parseo, no siempre se dará que es perfecto. El
-// caso obvio es el que es tratado en "_" del match. Deberíamos convertir esto en una
-// implementación de "TryFrom".
-impl From<String> for MemberState {
+impl MemberState {
+    /// Indica si un integrante puede pasar de este estado a `next`.
+    ///
+    /// Quedarse en el mismo estado siempre está permitido.
+    pub fn can_transition_to(&self, next: MemberState) -> bool {
+        use MemberState as ms;
+
+        match (self, next) {
+            (current, next) if *current == next => true,
+            (ms::Active | ms::Unactive, _) => true,
+            (ms::NoLongerAMember, ms::Active | ms::Unactive) => true,
+            (ms::NoLongerAMember, _) | (ms::Graduated, _) => false,
+        }
+    }
+}
+
+impl TryFrom<String> for MemberState {
+    type Error = anyhow::Error;
+
     /// Convierte un String a un Estado de un miembro.
-    fn from(input: String) -> MemberState {
+    fn try_from(input: String) -> Result<MemberState, Self::Error> {
         use MemberState as ms;
 
         match input.as_str() {
-            "Active" => ms::Active,
-            "Unactive" => ms::Unactive,
-            "Graduated" => ms::Graduated,
-            "NoLongerAMember" => ms::NoLongerAMember,
-            _ => ms::Unactive,
+            "Active" => Ok(ms::Active),
+            "Unactive" => Ok(ms::Unactive),
+            "Graduated" => Ok(ms::Graduated),
+            "NoLongerAMember" => Ok(ms::NoLongerAMember),
+            _ => Err(anyhow::anyhow!("No existe el estado {input}.")),
         }
     }
 }
@@ -81,15 +117,120 @@ impl From<MemberState> for String {
     }
 }
 
+/// Rol de un integrante dentro del club.
+///
+/// Los roles están ordenados de menor a mayor, de forma que un rol tiene todos los privilegios de
+/// los roles menores que él.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    /// Invitado; puede ver la información del club, pero no modificarla.
+    Guest,
+    /// Integrante normal del club.
+    Member,
+    /// Integrante de la directiva del club.
+    Board,
+    /// Administrador de la API.
+    Admin,
+}
+
+impl Role {
+    /// Nombre del rol tal como se guarda en la base de datos.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::Guest => "guest",
+            Role::Member => "member",
+            Role::Board => "board",
+            Role::Admin => "admin",
+        }
+    }
+}
+
+impl FromStr for Role {
+    type Err = anyhow::Error;
+
+    /// Convierte un &str en un Role.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "guest" => Ok(Role::Guest),
+            "member" => Ok(Role::Member),
+            "board" => Ok(Role::Board),
+            "admin" => Ok(Role::Admin),
+            _ => Err(anyhow::anyhow!("No existe el rol {s}.")),
+        }
+    }
+}
+
 impl ClubMemberModel {
+    /// Obtiene el rol del integrante.
+    ///
+    /// La base de datos solo acepta roles válidos, pero si de alguna forma hubiera uno
+    /// desconocido se trata como invitado, que es el rol con menos privilegios.
+    pub fn role(&self) -> Role {
+        self.role.parse().unwrap_or(Role::Guest)
+    }
+
+    /// Obtiene el estado del integrante.
+    ///
+    /// Al igual que con el rol, la base de datos solo acepta estados válidos; si de alguna forma
+    /// hubiera uno desconocido, se trata como inactivo.
+    pub fn state(&self) -> MemberState {
+        MemberState::try_from(self.state.clone()).unwrap_or(MemberState::Unactive)
+    }
+
     /// Obtiene todos los integrantes del club.
     ///
     /// Esta función no filtra bajo ningún criterio (o no aún anyways), sino que entrega todos los
-    /// integrantes del club sin más.
+    /// integrantes del club sin más, excepto los eliminados.
     pub async fn get_all(pool: &sqlx::SqlitePool) -> Result<Vec<ClubMemberModel>, sqlx::Error> {
-        sqlx::query_as!(ClubMemberModel, "SELECT * FROM club_members")
-            .fetch_all(pool)
-            .await
+        sqlx::query_as!(
+            ClubMemberModel,
+            "SELECT * FROM club_members WHERE deleted_at IS NULL"
+        )
+        .fetch_all(pool)
+        .await
+    }
+
+    /// Busca integrantes según los filtros de un listado, y entrega una página de ellos.
+    pub async fn search(
+        filters: &MemberListQuery,
+        page: &PageRequest,
+        pool: &sqlx::SqlitePool,
+    ) -> Result<PageResult<ClubMemberModel>, sqlx::Error> {
+        let mut count = QueryBuilder::new(
+            "SELECT COUNT(*) FROM club_members WHERE club_members.deleted_at IS NULL",
+        );
+        ClubMemberModel::push_filters(&mut count, filters);
+        let total: i64 = count.build_query_scalar().fetch_one(pool).await?;
+
+        let mut query =
+            QueryBuilder::new("SELECT * FROM club_members WHERE club_members.deleted_at IS NULL");
+        ClubMemberModel::push_filters(&mut query, filters);
+        page.push_sql(&mut query, "club_members", filters.sort.column());
+        let members = query.build_query_as().fetch_all(pool).await?;
+
+        Ok(page.finish(members, total))
+    }
+
+    /// Agrega las condiciones de los filtros de un listado a una consulta.
+    fn push_filters(query: &mut QueryBuilder<Sqlite>, filters: &MemberListQuery) {
+        if let Some(state) = &filters.state {
+            query
+                .push(" AND club_members.state = ")
+                .push_bind(String::from(*state));
+        }
+
+        if let Some(search) = filters.search.as_deref().filter(|s| !s.is_empty()) {
+            let pattern = like_pattern(search);
+            query
+                .push(" AND (club_members.name LIKE ")
+                .push_bind(pattern.clone())
+                .push(" ESCAPE '\\' OR club_members.email LIKE ")
+                .push_bind(pattern.clone())
+                .push(" ESCAPE '\\' OR club_members.github LIKE ")
+                .push_bind(pattern)
+                .push(" ESCAPE '\\')");
+        }
     }
 
     /// Obtiene un único integrante del club según su UUID.
@@ -100,13 +241,75 @@ impl ClubMemberModel {
         let member_id: String = member_id.into();
         sqlx::query_as!(
             ClubMemberModel,
-            "SELECT * FROM club_members WHERE uuid = ?",
+            "SELECT * FROM club_members WHERE uuid = ? AND deleted_at IS NULL",
             member_id
         )
         .fetch_one(pool)
         .await
     }
 
+    /// Obtiene al integrante del club dueño de una cuenta de GitHub.
+    ///
+    /// La cuenta se identifica por su ID numérico, que no cambia aunque se cambie el nombre de
+    /// usuario. Si ningún integrante tiene ese ID, se busca a uno sin ID con ese nombre de usuario
+    /// (sin distinguir mayúsculas de minúsculas, igual que GitHub) y se le asocia la cuenta, de
+    /// forma que desde entonces solo esa cuenta pueda iniciar sesión por él.
+    pub async fn find_by_github(
+        github_id: i64,
+        login: &str,
+        pool: &sqlx::SqlitePool,
+    ) -> Result<ClubMemberModel, sqlx::Error> {
+        let member = sqlx::query_as!(
+            ClubMemberModel,
+            "SELECT * FROM club_members WHERE github_id = ? AND deleted_at IS NULL",
+            github_id
+        )
+        .fetch_optional(pool)
+        .await?;
+
+        if let Some(member) = member {
+            return Ok(member);
+        }
+
+        // Si otra petición asoció una cuenta entre la búsqueda y ahora, no se cambia.
+        let result = sqlx::query!(
+            r#"UPDATE club_members SET github_id = ?
+            WHERE github = ? COLLATE NOCASE AND github_id IS NULL AND deleted_at IS NULL"#,
+            github_id,
+            login
+        )
+        .execute(pool)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(sqlx::Error::RowNotFound);
+        }
+
+        sqlx::query_as!(
+            ClubMemberModel,
+            "SELECT * FROM club_members WHERE github_id = ? AND deleted_at IS NULL",
+            github_id
+        )
+        .fetch_one(pool)
+        .await
+    }
+
+    /// Obtiene al integrante del club con el email entregado.
+    ///
+    /// La comparación no distingue mayúsculas de minúsculas.
+    pub async fn find_by_email(
+        email: &str,
+        pool: &sqlx::SqlitePool,
+    ) -> Result<ClubMemberModel, sqlx::Error> {
+        sqlx::query_as!(
+            ClubMemberModel,
+            "SELECT * FROM club_members WHERE email = ? COLLATE NOCASE AND deleted_at IS NULL",
+            email
+        )
+        .fetch_one(pool)
+        .await
+    }
+
     /// Crea (o agregar, supongo) un nuevo integrante del club
     pub async fn create(
         member_id: &str,
@@ -118,7 +321,7 @@ impl ClubMemberModel {
     INSERT INTO club_members (uuid, name, birthday, email, github)
     VALUES (?, ?, ?, ?, ?)"#,
         )
-        .bind(member_id.clone())
+        .bind(member_id)
         .bind(value.name)
         .bind(value.birthday)
         .bind(value.email)
@@ -127,39 +330,213 @@ impl ClubMemberModel {
         .await
     }
 
+    /// Agrega varios integrantes del club de una vez, en una sola transacción: si alguno falla, no
+    /// se agrega ninguno.
+    ///
+    /// Entrega las UUID de los nuevos integrantes, en el mismo orden.
+    pub async fn create_many(
+        members: Vec<CreateMemberSchema>,
+        pool: &sqlx::SqlitePool,
+    ) -> Result<Vec<String>, sqlx::Error> {
+        let mut tx = pool.begin().await?;
+        let mut ids = Vec::with_capacity(members.len());
+
+        for member in members {
+            let member_id = uuid::Uuid::new_v4().to_string();
+
+            sqlx::query(
+                r#"
+    INSERT INTO club_members (uuid, name, birthday, email, github)
+    VALUES (?, ?, ?, ?, ?)"#,
+            )
+            .bind(&member_id)
+            .bind(member.name)
+            .bind(member.birthday)
+            .bind(member.email)
+            .bind(member.github)
+            .execute(&mut *tx)
+            .await?;
+
+            ids.push(member_id);
+        }
+
+        tx.commit().await?;
+
+        Ok(ids)
+    }
+
     /// Actualiza los datos de un integrante del club.
+    ///
+    /// `new_data` tiene todos los datos nuevos del integrante, así que un valor en `None` borra
+    /// el dato anterior. Si cambia su usuario de GitHub, se olvida la cuenta asociada, y se asocia
+    /// la nueva la próxima vez que inicie sesión con GitHub.
+    ///
+    /// Si cambia el estado del integrante, el cambio queda en su historial a nombre de `author`.
+    /// Esta función no revisa si el cambio de estado está permitido; eso le toca a quien la llama
+    /// a partir de `member`. Por eso, devuelve `false` sin cambiar nada si mientras tanto el
+    /// integrante cambió de estado o de rol, o fue eliminado.
     pub async fn update(
         member: ClubMemberModel,
-        new_data: UpdateMemberSchema,
+        new_data: MemberDocument,
+        author: &ChangeAuthor<'_>,
         pool: &sqlx::SqlitePool,
-    ) -> Result<SqliteQueryResult, sqlx::Error> {
-        sqlx::query(
+    ) -> Result<bool, sqlx::Error> {
+        let previous_state = member.state();
+        let new_state = new_data.state;
+
+        let mut tx = pool.begin().await?;
+
+        let result = sqlx::query(
             r#"
     UPDATE club_members
-    SET name = ?, birthday = ?, email = ?, github = ?, state = ? WHERE uuid = ?"#,
+    SET name = ?, birthday = ?, email = ?, github = ?, state = ?, role = ?,
+        github_id = CASE WHEN github = ? COLLATE NOCASE THEN github_id ELSE NULL END,
+        updated_at = CURRENT_TIMESTAMP
+    WHERE uuid = ? AND state = ? AND role = ? AND deleted_at IS NULL"#,
         )
-        .bind(new_data.name.to_owned().unwrap_or(member.name))
-        // TODO: Verificar que sea una fecha.
-        .bind(new_data.birthday.to_owned().or(member.birthday))
-        .bind(new_data.email.to_owned().or(member.email))
-        .bind(new_data.github.to_owned().or(member.github))
-        .bind(new_data.state.to_owned().unwrap_or(member.state))
-        .bind(member.uuid)
-        .execute(pool)
+        .bind(new_data.name)
+        .bind(new_data.birthday)
+        .bind(new_data.email)
+        .bind(new_data.github.clone())
+        .bind(String::from(new_state))
+        .bind(new_data.role.as_str())
+        .bind(new_data.github)
+        .bind(&member.uuid)
+        .bind(&member.state)
+        .bind(&member.role)
+        .execute(&mut *tx)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Ok(false);
+        }
+
+        if new_state != previous_state {
+            MemberStateChangeModel::create(
+                &member.uuid,
+                previous_state,
+                new_state,
+                author,
+                &mut tx,
+            )
+            .await?;
+        }
+
+        tx.commit().await?;
+
+        Ok(true)
+    }
+
+    /// Obtiene un integrante eliminado según su UUID.
+    pub async fn get_deleted(
+        member_id: impl Into<String>,
+        pool: &sqlx::SqlitePool,
+    ) -> Result<ClubMemberModel, sqlx::Error> {
+        let member_id: String = member_id.into();
+        sqlx::query_as!(
+            ClubMemberModel,
+            "SELECT * FROM club_members WHERE uuid = ? AND deleted_at IS NOT NULL",
+            member_id
+        )
+        .fetch_one(pool)
         .await
     }
 
     /// Elimina a un integrante del club.
     ///
+    /// La eliminación es 'suave': la fila se mantiene con la fecha de eliminación, de forma que
+    /// no se pierda su historial en los proyectos y se pueda restaurar.
+    pub async fn delete(
+        member: &ClubMemberModel,
+        pool: &sqlx::SqlitePool,
+    ) -> Result<SqliteQueryResult, sqlx::Error> {
+        sqlx::query!(
+            r#"
+    UPDATE club_members SET deleted_at = CURRENT_TIMESTAMP
+    WHERE uuid = ? AND deleted_at IS NULL"#,
+            member.uuid
+        )
+        .execute(pool)
+        .await
+    }
+
+    /// Restaura a un integrante eliminado.
+    pub async fn restore(
+        member: &ClubMemberModel,
+        pool: &sqlx::SqlitePool,
+    ) -> Result<SqliteQueryResult, sqlx::Error> {
+        sqlx::query!(
+            "UPDATE club_members SET deleted_at = NULL WHERE uuid = ?",
+            member.uuid
+        )
+        .execute(pool)
+        .await
+    }
+
+    /// Elimina definitivamente a un integrante del club.
+    ///
     /// Es importante notar que esta función solo los elimina de la base de datos, pero estas
     /// personas seguirán existiendo en la vida real! seguiremos trabajando para que también los
     /// elimine ahí, pero hasta no saber cómo hacer esto, seguiremos como estamos ahora mismo.
-    pub async fn delete(
-        member: &ClubMemberModel,
+    ///
+    /// Junto con el integrante se pierde su participación en los proyectos.
+    pub async fn purge(
+        member_id: &str,
         pool: &sqlx::SqlitePool,
     ) -> Result<SqliteQueryResult, sqlx::Error> {
-        sqlx::query!(r#"DELETE FROM club_members WHERE uuid = ?"#, member.uuid)
+        sqlx::query!(r#"DELETE FROM club_members WHERE uuid = ?"#, member_id)
             .execute(pool)
             .await
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::MemberState;
+
+    /// Todos los estados, en el orden en que se declaran.
+    const STATES: [MemberState; 4] = [
+        MemberState::Active,
+        MemberState::Unactive,
+        MemberState::Graduated,
+        MemberState::NoLongerAMember,
+    ];
+
+    /// Quedarse en el mismo estado siempre está permitido, incluso en los estados finales.
+    #[test]
+    fn same_state_is_always_allowed() {
+        for state in STATES {
+            assert!(state.can_transition_to(state), "{state:?} -> {state:?}");
+        }
+    }
+
+    /// Un integrante activo o inactivo puede pasar a cualquier estado.
+    #[test]
+    fn current_members_can_go_anywhere() {
+        for current in [MemberState::Active, MemberState::Unactive] {
+            for next in STATES {
+                assert!(current.can_transition_to(next), "{current:?} -> {next:?}");
+            }
+        }
+    }
+
+    /// Un ex miembro puede volver al club, pero no graduarse sin haber vuelto antes.
+    #[test]
+    fn former_members_can_only_come_back() {
+        let current = MemberState::NoLongerAMember;
+
+        assert!(current.can_transition_to(MemberState::Active));
+        assert!(current.can_transition_to(MemberState::Unactive));
+        assert!(!current.can_transition_to(MemberState::Graduated));
+    }
+
+    /// Graduarse es final.
+    #[test]
+    fn graduated_is_final() {
+        let current = MemberState::Graduated;
+
+        for next in STATES.into_iter().filter(|next| *next != current) {
+            assert!(!current.can_transition_to(next), "{current:?} -> {next:?}");
+        }
+    }
+}
@@ -0,0 +1,90 @@
+//! Modelo para un enlace de inicio de sesión.
+//!
+//! Los integrantes sin GitHub pueden iniciar sesión con un enlace que se les envía por correo.
+//! Cada enlace lleva un token de un solo uso y de corta duración; en la base de datos solo se
+//! guarda el hash de ese token, igual que con las llaves de las Apps.
+
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sqlx::sqlite::SqliteQueryResult;
+
+/// Modelo de un enlace de inicio de sesión.
+#[derive(Debug, Serialize, Deserialize, sqlx::FromRow)]
+pub struct MagicLinkModel {
+    /// Hash SHA-256 del token del enlace.
+    pub token_hash: String,
+    /// Integrante que puede usar el enlace.
+    pub club_member_uuid: String,
+    /// Fecha (UTC) desde la cual el enlace deja de funcionar.
+    pub expires_at: NaiveDateTime,
+    /// Fecha en que se usó el enlace, si ya se usó.
+    pub used_at: Option<NaiveDateTime>,
+    /// Fecha de creación de esta fila de la BDD.
+    pub created_at: NaiveDateTime,
+}
+
+impl MagicLinkModel {
+    /// Calcula el hash con el que se guarda un token.
+    pub fn hash_token(token: &str) -> String {
+        hex::encode(Sha256::digest(token.as_bytes()))
+    }
+
+    /// Guarda un nuevo enlace para un integrante.
+    ///
+    /// De paso, elimina los enlaces que ya expiraron, para que la tabla no crezca sin límite.
+    pub async fn create(
+        token_hash: &str,
+        member_id: &str,
+        expires_at: NaiveDateTime,
+        pool: &sqlx::SqlitePool,
+    ) -> Result<SqliteQueryResult, sqlx::Error> {
+        let now = chrono::Utc::now().naive_utc();
+
+        sqlx::query!("DELETE FROM magic_links WHERE expires_at <= ?", now)
+            .execute(pool)
+            .await?;
+
+        sqlx::query!(
+            r#"
+    INSERT INTO magic_links (token_hash, club_member_uuid, expires_at)
+    VALUES (?, ?, ?)"#,
+            token_hash,
+            member_id,
+            expires_at
+        )
+        .execute(pool)
+        .await
+    }
+
+    /// Busca un enlace según el hash de su token.
+    pub async fn find_by_hash(
+        token_hash: &str,
+        pool: &sqlx::SqlitePool,
+    ) -> Result<MagicLinkModel, sqlx::Error> {
+        sqlx::query_as!(
+            MagicLinkModel,
+            "SELECT * FROM magic_links WHERE token_hash = ?",
+            token_hash
+        )
+        .fetch_one(pool)
+        .await
+    }
+
+    /// Marca un enlace como usado.
+    ///
+    /// Entrega `false` si otra petición lo usó primero, de forma que un enlace nunca sirva dos
+    /// veces aunque llegue dos veces al mismo tiempo.
+    pub async fn mark_used(token_hash: &str, pool: &sqlx::SqlitePool) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query!(
+            r#"
+    UPDATE magic_links SET used_at = CURRENT_TIMESTAMP
+    WHERE token_hash = ? AND used_at IS NULL"#,
+            token_hash
+        )
+        .execute(pool)
+        .await?;
+
+        Ok(result.rows_affected() == 1)
+    }
+}
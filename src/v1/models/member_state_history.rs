@@ -0,0 +1,83 @@
+//! Modelo para el historial de estados de un integrante.
+//!
+//! Cada vez que cambia el estado de un integrante se guarda una fila con el estado anterior, el
+//! nuevo, y quién hizo el cambio: la App y, si la petición traía una sesión, el integrante en
+//! cuyo nombre actuaba.
+
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use sqlx::{sqlite::SqliteQueryResult, SqliteConnection};
+use utoipa::ToSchema;
+
+use crate::v1::models::{club_member::MemberState, ChangeAuthor};
+
+/// Modelo de un cambio de estado de un integrante.
+#[derive(Debug, Serialize, Deserialize, sqlx::FromRow, ToSchema)]
+pub struct MemberStateChangeModel {
+    /// ID correlativa del cambio.
+    pub id: i64,
+    /// Integrante cuyo estado cambió.
+    pub club_member_uuid: String,
+    /// Estado anterior del integrante.
+    pub from_state: String,
+    /// Estado nuevo del integrante.
+    pub to_state: String,
+    /// Si el cambio no estaba permitido y lo forzó un administrador.
+    pub overridden: bool,
+    /// App que hizo el cambio.
+    pub changed_by_app: String,
+    /// Integrante en cuyo nombre se hizo el cambio, si la petición traía una sesión.
+    pub changed_by_member: Option<String>,
+    /// Fecha del cambio.
+    pub created_at: NaiveDateTime,
+}
+
+impl MemberStateChangeModel {
+    /// Obtiene los cambios de estado de un integrante, del más antiguo al más reciente.
+    pub async fn get_by_member(
+        member_id: &str,
+        pool: &sqlx::SqlitePool,
+    ) -> Result<Vec<MemberStateChangeModel>, sqlx::Error> {
+        sqlx::query_as!(
+            MemberStateChangeModel,
+            r#"
+    SELECT id, club_member_uuid, from_state, to_state, overridden as "overridden: bool",
+        changed_by_app, changed_by_member, created_at
+    FROM member_state_history WHERE club_member_uuid = ? ORDER BY id"#,
+            member_id
+        )
+        .fetch_all(pool)
+        .await
+    }
+
+    /// Guarda un cambio de estado.
+    ///
+    /// Recibe una conexión en vez del pool, para guardarlo en la misma transacción que el
+    /// cambio.
+    pub async fn create(
+        member_id: &str,
+        from: MemberState,
+        to: MemberState,
+        author: &ChangeAuthor<'_>,
+        conn: &mut SqliteConnection,
+    ) -> Result<SqliteQueryResult, sqlx::Error> {
+        let overridden = !from.can_transition_to(to);
+        let from = String::from(from);
+        let to = String::from(to);
+
+        sqlx::query!(
+            r#"
+    INSERT INTO member_state_history
+        (club_member_uuid, from_state, to_state, overridden, changed_by_app, changed_by_member)
+    VALUES (?, ?, ?, ?, ?, ?)"#,
+            member_id,
+            from,
+            to,
+            overridden,
+            author.app,
+            author.member
+        )
+        .execute(conn)
+        .await
+    }
+}
@@ -8,4 +8,16 @@
 
 pub mod auth;
 pub mod club_member;
+pub mod magic_link;
+pub mod member_state_history;
 pub mod project;
+pub mod project_state_history;
+pub mod totp;
+
+/// Quién hace un cambio que queda registrado en un historial.
+pub struct ChangeAuthor<'a> {
+    /// UUID de la App que hace el cambio.
+    pub app: &'a str,
+    /// UUID del integrante en cuyo nombre actúa la App, si la petición trae una sesión.
+    pub member: Option<&'a str>,
+}
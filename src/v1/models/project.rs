@@ -3,16 +3,24 @@
 //! Este modelo corresponde a la abstracción de la tabla homónima, y que se usa para trabajar con
 //! el estado de los proyectos del club ExDev.
 
-use std::str::FromStr;
+use std::{
+    collections::{BTreeSet, HashMap},
+    str::FromStr,
+};
 
 use chrono::NaiveDateTime;
 use serde::{Deserialize, Serialize};
-use sqlx::{sqlite::SqliteQueryResult, QueryBuilder, Row, Sqlite};
+use sqlx::{sqlite::SqliteQueryResult, FromRow, QueryBuilder, Row, Sqlite};
+use utoipa::ToSchema;
 use uuid::Uuid;
 
-use crate::v1::schemas::{
-    club_member::ClubMemberResponse,
-    project::{CreateProjectSchema, UpdateProjectSchema},
+use crate::v1::{
+    models::{project_state_history::ProjectStateChangeModel, ChangeAuthor},
+    schemas::{
+        club_member::ClubMemberResponse,
+        pagination::{like_pattern, PageRequest, PageResult},
+        project::{CreateProjectSchema, ProjectDocument, ProjectListQuery},
+    },
 };
 
 use super::club_member::ClubMemberModel;
@@ -20,13 +28,13 @@ use super::club_member::ClubMemberModel;
 /// Estructura de un proyecto.
 ///
 /// Contiene un vector de integrantes que es mapeado en una relación n:m.
-#[derive(Serialize, Deserialize, sqlx::FromRow)]
+#[derive(Serialize, Deserialize, sqlx::FromRow, ToSchema)]
 pub struct ProjectModel {
     /// Identificador único del proyecto
-    uuid: String,
+    pub uuid: String,
 
     /// Nombre del proyecto.
-    name: String,
+    pub name: String,
 
     /// Descripción del proyecto.
     description: Option<String>,
@@ -39,15 +47,29 @@ pub struct ProjectModel {
     #[serde(skip_serializing_if = "Vec::is_empty")]
     involved: Vec<ClubMemberResponse>,
 
+    /// Cambios de estado del proyecto, del más antiguo al más reciente. Solo se cargan al
+    /// obtener un único proyecto.
+    #[sqlx(skip)]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    state_history: Vec<ProjectStateChangeModel>,
+
     /// Fecha de creación
     pub created_at: NaiveDateTime,
 
     /// Fecha de la Última modificación de este fila.
     pub updated_at: NaiveDateTime,
+
+    /// Si el proyecto fue archivado. Los proyectos archivados no aparecen en los listados, a
+    /// menos que se pidan.
+    pub archived: bool,
+
+    /// Fecha en que se eliminó el proyecto, si fue eliminado.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deleted_at: Option<NaiveDateTime>,
 }
 
 /// Los distintos estados en los que se puede encontrar un proyecto.
-#[derive(Debug, Deserialize, Serialize, sqlx::Type)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize, sqlx::Type, ToSchema)]
 pub enum ProjectState {
     /// El proyecto aún no inicia.
     NotStarted,
@@ -68,6 +90,20 @@ pub enum ProjectState {
     Cancelled,
 }
 
+impl ProjectState {
+    /// Nombre del estado tal como se guarda en la base de datos.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ProjectState::NotStarted => "NotStarted",
+            ProjectState::InProgress => "InProgress",
+            ProjectState::Idle => "Idle",
+            ProjectState::LookingForIdeas => "LookingForIdeas",
+            ProjectState::Finished => "Finished",
+            ProjectState::Cancelled => "Cancelled",
+        }
+    }
+}
+
 impl TryFrom<String> for ProjectState {
     type Error = anyhow::Error;
 
@@ -94,7 +130,126 @@ impl FromStr for ProjectState {
     }
 }
 
+/// Cambios de estado permitidos para los proyectos.
+///
+/// Quedarse en el mismo estado siempre está permitido. Por defecto, `Finished` y `Cancelled` son
+/// finales, y los demás estados pueden pasar entre ellos según el sentido común:
+///
+/// | Estado            | Puede pasar a                                      |
+/// |-------------------|----------------------------------------------------|
+/// | `NotStarted`      | `InProgress`, `LookingForIdeas`, `Cancelled`       |
+/// | `InProgress`      | `Idle`, `LookingForIdeas`, `Finished`, `Cancelled` |
+/// | `Idle`            | `InProgress`, `LookingForIdeas`, `Cancelled`       |
+/// | `LookingForIdeas` | `NotStarted`, `InProgress`, `Idle`, `Cancelled`    |
+///
+/// El grafo se puede reemplazar con la variable `PROJECT_TRANSITIONS`, que lista cada estado con
+/// sus destinos: `NotStarted:InProgress,Cancelled;InProgress:Finished`. Los estados que no
+/// aparecen en ella quedan como finales.
+#[derive(Debug, Clone)]
+pub struct ProjectWorkflow {
+    /// Estados a los que se puede pasar desde cada estado.
+    transitions: HashMap<ProjectState, Vec<ProjectState>>,
+}
+
+impl Default for ProjectWorkflow {
+    /// El grafo de cambios de estado por defecto.
+    fn default() -> ProjectWorkflow {
+        use ProjectState as ps;
+
+        ProjectWorkflow {
+            transitions: HashMap::from([
+                (
+                    ps::NotStarted,
+                    vec![ps::InProgress, ps::LookingForIdeas, ps::Cancelled],
+                ),
+                (
+                    ps::InProgress,
+                    vec![ps::Idle, ps::LookingForIdeas, ps::Finished, ps::Cancelled],
+                ),
+                (
+                    ps::Idle,
+                    vec![ps::InProgress, ps::LookingForIdeas, ps::Cancelled],
+                ),
+                (
+                    ps::LookingForIdeas,
+                    vec![ps::NotStarted, ps::InProgress, ps::Idle, ps::Cancelled],
+                ),
+            ]),
+        }
+    }
+}
+
+impl FromStr for ProjectWorkflow {
+    type Err = anyhow::Error;
+
+    /// Lee un grafo con el formato de `PROJECT_TRANSITIONS`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut transitions = HashMap::new();
+
+        for entry in s
+            .split(';')
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+        {
+            let (from, targets) = entry
+                .split_once(':')
+                .ok_or(anyhow::anyhow!("Falta ':' en el cambio de estado {entry}"))?;
+            let targets = targets
+                .split(',')
+                .map(str::trim)
+                .filter(|target| !target.is_empty())
+                .map(ProjectState::from_str)
+                .collect::<Result<Vec<_>, _>>()?;
+
+            transitions.insert(from.trim().parse()?, targets);
+        }
+
+        Ok(ProjectWorkflow { transitions })
+    }
+}
+
+impl ProjectWorkflow {
+    /// Crea el grafo a partir de `PROJECT_TRANSITIONS`, o el por defecto si no está definida.
+    pub fn from_env() -> anyhow::Result<ProjectWorkflow> {
+        match std::env::var("PROJECT_TRANSITIONS") {
+            Ok(transitions) => transitions.parse(),
+            Err(_) => Ok(ProjectWorkflow::default()),
+        }
+    }
+
+    /// Indica si un proyecto puede pasar del estado `from` al estado `to`.
+    pub fn allows(&self, from: ProjectState, to: ProjectState) -> bool {
+        from == to
+            || self
+                .transitions
+                .get(&from)
+                .is_some_and(|targets| targets.contains(&to))
+    }
+}
+
+impl From<&ProjectModel> for ProjectDocument {
+    /// Obtiene el documento con los datos actuales de un proyecto.
+    fn from(project: &ProjectModel) -> ProjectDocument {
+        ProjectDocument {
+            name: project.name.to_owned(),
+            description: project.description.to_owned(),
+            state: project.state,
+            archived: project.archived,
+            involved: project
+                .involved
+                .iter()
+                .filter_map(|member| member.uuid().parse().ok())
+                .collect(),
+        }
+    }
+}
+
 impl ProjectModel {
+    /// Estado actual del proyecto.
+    pub fn state(&self) -> ProjectState {
+        self.state
+    }
+
     /// Encuentra un proyecto según su UUID
     ///
     /// Además, encuentra y transforma todos los usuarios involucrados en ese proyecto hacia
@@ -106,16 +261,17 @@ impl ProjectModel {
         let id = id.to_string().to_owned();
 
         // Busco el proyecto.
-        let mut project: ProjectModel =
-            sqlx::query_as(r#"SELECT * FROM projects WHERE projects.uuid = $1"#)
-                .bind(id.clone())
-                .fetch_one(pool)
-                .await?;
+        let mut project: ProjectModel = sqlx::query_as(
+            r#"SELECT * FROM projects WHERE projects.uuid = $1 AND projects.deleted_at IS NULL"#,
+        )
+        .bind(id.clone())
+        .fetch_one(pool)
+        .await?;
 
         let involved: Vec<ClubMemberModel> = sqlx::query_as(
             r#"SELECT club_members.* FROM club_members
             JOIN project_involvement ON project_involvement.club_member_uuid = club_members.uuid
-            WHERE project_involvement.project_uuid = $1"#,
+            WHERE project_involvement.project_uuid = $1 AND club_members.deleted_at IS NULL"#,
         )
         .bind(id)
         .fetch_all(pool)
@@ -123,150 +279,510 @@ impl ProjectModel {
 
         // Busco a los involucrados.
         project.involved = ClubMemberResponse::from_vector(&involved);
+        project.state_history =
+            ProjectStateChangeModel::get_by_project(&project.uuid, pool).await?;
 
         Ok(project)
     }
 
-    /// Obtiene todos los proyectos de la base de datos.
+    /// Obtiene todos los proyectos de la base de datos, excepto los eliminados.
     ///
     /// Esta función no encuentra a lo involucrados en los proyectos.
     pub async fn get_all(pool: &sqlx::SqlitePool) -> Result<Vec<ProjectModel>, sqlx::Error> {
-        sqlx::query_as(r#"SELECT * FROM projects"#)
+        sqlx::query_as(r#"SELECT * FROM projects WHERE deleted_at IS NULL"#)
             .fetch_all(pool)
             .await
     }
 
+    /// Busca proyectos según los filtros de un listado, y entrega una página de ellos.
+    ///
+    /// Los involucrados solo se cargan si el listado los pide con `include=involved`, y en ese
+    /// caso se cargan los de toda la página en una sola consulta.
+    pub async fn search(
+        filters: &ProjectListQuery,
+        page: &PageRequest,
+        pool: &sqlx::SqlitePool,
+    ) -> Result<PageResult<ProjectModel>, sqlx::Error> {
+        let mut count =
+            QueryBuilder::new("SELECT COUNT(*) FROM projects WHERE projects.deleted_at IS NULL");
+        ProjectModel::push_filters(&mut count, filters);
+        let total: i64 = count.build_query_scalar().fetch_one(pool).await?;
+
+        let mut query =
+            QueryBuilder::new("SELECT * FROM projects WHERE projects.deleted_at IS NULL");
+        ProjectModel::push_filters(&mut query, filters);
+        page.push_sql(&mut query, "projects", filters.sort.column());
+        let mut projects: Vec<ProjectModel> = query.build_query_as().fetch_all(pool).await?;
+
+        if filters.includes_involved() {
+            ProjectModel::load_involved(&mut projects, pool).await?;
+        }
+
+        Ok(page.finish(projects, total))
+    }
+
+    /// Agrega las condiciones de los filtros de un listado a una consulta.
+    fn push_filters(query: &mut QueryBuilder<Sqlite>, filters: &ProjectListQuery) {
+        if !filters.include_archived {
+            query.push(" AND projects.archived = FALSE");
+        }
+
+        if let Some(state) = filters.state {
+            query.push(" AND projects.state = ").push_bind(state);
+        }
+
+        if let Some(member) = filters.member {
+            query
+                .push(
+                    " AND EXISTS (SELECT 1 FROM project_involvement \
+                    WHERE project_involvement.project_uuid = projects.uuid \
+                    AND project_involvement.club_member_uuid = ",
+                )
+                .push_bind(member.to_string())
+                .push(")");
+        }
+
+        if let Some(from) = filters.created_from {
+            query
+                .push(" AND date(projects.created_at) >= ")
+                .push_bind(from.to_string());
+        }
+
+        if let Some(to) = filters.created_to {
+            query
+                .push(" AND date(projects.created_at) <= ")
+                .push_bind(to.to_string());
+        }
+
+        if let Some(search) = filters.search.as_deref().filter(|s| !s.is_empty()) {
+            let pattern = like_pattern(search);
+            query
+                .push(" AND (projects.name LIKE ")
+                .push_bind(pattern.clone())
+                .push(" ESCAPE '\\' OR projects.description LIKE ")
+                .push_bind(pattern)
+                .push(" ESCAPE '\\')");
+        }
+    }
+
+    /// Carga a los involucrados de varios proyectos con una sola consulta.
+    async fn load_involved(
+        projects: &mut [ProjectModel],
+        pool: &sqlx::SqlitePool,
+    ) -> Result<(), sqlx::Error> {
+        if projects.is_empty() {
+            return Ok(());
+        }
+
+        let mut query: QueryBuilder<Sqlite> = QueryBuilder::new(
+            "SELECT project_involvement.project_uuid, club_members.* FROM club_members \
+            JOIN project_involvement ON project_involvement.club_member_uuid = club_members.uuid \
+            WHERE club_members.deleted_at IS NULL AND project_involvement.project_uuid IN (",
+        );
+
+        let mut separated = query.separated(", ");
+        for project in projects.iter() {
+            separated.push_bind(project.uuid.clone());
+        }
+        separated.push_unseparated(")");
+
+        let rows = query.build().fetch_all(pool).await?;
+
+        for row in rows {
+            let project_id: String = row.try_get("project_uuid")?;
+            let member = ClubMemberModel::from_row(&row)?;
+
+            if let Some(project) = projects.iter_mut().find(|p| p.uuid == project_id) {
+                project.involved.push(member.into());
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Obtiene todos los proyectos en los que participa un integrante, incluyendo los archivados.
+    ///
+    /// Al igual que `get_all`, esta función no encuentra a los involucrados en los proyectos.
+    pub async fn get_by_member(
+        member_id: &str,
+        pool: &sqlx::SqlitePool,
+    ) -> Result<Vec<ProjectModel>, sqlx::Error> {
+        sqlx::query_as(
+            r#"SELECT projects.* FROM projects
+            JOIN project_involvement ON project_involvement.project_uuid = projects.uuid
+            WHERE project_involvement.club_member_uuid = $1 AND projects.deleted_at IS NULL"#,
+        )
+        .bind(member_id)
+        .fetch_all(pool)
+        .await
+    }
+
     /// Crea un nuevo proyecto y lo retorna
     ///
-    /// Además se encarga de insertar a todos los involucrados en el proyecto.
+    /// Además se encarga de insertar a todos los involucrados en el proyecto. Todo se hace en una
+    /// sola transacción, así que si algún involucrado no existe, el proyecto tampoco se crea.
     pub async fn create(
         data: CreateProjectSchema,
         pool: &sqlx::SqlitePool,
     ) -> Result<ProjectModel, sqlx::Error> {
         let id = uuid::Uuid::new_v4();
+        let mut tx = pool.begin().await?;
+
         sqlx::query(r#"INSERT INTO projects(uuid, name, description) VALUES (?, ?, ?)"#)
             .bind(id.to_string())
             .bind(data.name)
             .bind(data.description)
-            .execute(pool)
+            .execute(&mut *tx)
             .await?;
 
         // Agrega a todos los involucrados relacionados al proyecto.
-        if let Some(member_ids) = data.involved {
+        if let Some(member_ids) = data.involved.filter(|ids| !ids.is_empty()) {
             let mut qb: QueryBuilder<Sqlite> = QueryBuilder::new(
-                "INSERT INTO project_involvement(project_uuid, club_member_uuid) ",
+                "INSERT OR IGNORE INTO project_involvement(project_uuid, club_member_uuid) ",
             );
 
             qb.push_values(member_ids, |mut b, value| {
                 b.push_bind(id.to_string()).push_bind(value.to_string());
             });
 
-            qb.build().execute(pool).await?;
+            qb.build().execute(&mut *tx).await?;
         }
 
+        tx.commit().await?;
+
         ProjectModel::find_by_id(id, pool).await
     }
 
     /// Actualiza un proyecto
     ///
-    /// Puede actualizar el estado del proyecto, y agregar o quitar involucrados según su UUID.
+    /// `changes` tiene todos los datos nuevos del proyecto, así que una descripción en `None`
+    /// borra la anterior. Lo mismo pasa con los involucrados: se agregan los que no estaban y se
+    /// quitan los que ya no están. Todos los cambios se hacen en una sola transacción.
+    ///
+    /// Si cambia el estado, el cambio queda en el historial del proyecto a nombre de `author`.
+    /// Esta función no revisa si el cambio de estado está permitido; eso le toca a quien la llama
+    /// con el `ProjectWorkflow` de la API a partir de `previous`. Por eso, devuelve `None` sin
+    /// cambiar nada si mientras tanto el proyecto cambió de estado o fue eliminado.
     pub async fn update(
-        id: uuid::Uuid,
-        data: UpdateProjectSchema,
+        previous: ProjectModel,
+        changes: ProjectDocument,
+        reason: Option<&str>,
+        author: &ChangeAuthor<'_>,
         pool: &sqlx::SqlitePool,
-    ) -> Result<ProjectModel, sqlx::Error> {
-        // Valores anteriores.
-        let previous = ProjectModel::find_by_id(id, pool).await?;
+    ) -> Result<Option<ProjectModel>, sqlx::Error> {
+        let id: Uuid = previous
+            .uuid
+            .parse()
+            .map_err(|err| sqlx::Error::Decode(Box::new(err)))?;
+        let new_state = changes.state;
+
+        let previous_ids: BTreeSet<String> = previous
+            .involved
+            .iter()
+            .map(|member| member.uuid().to_owned())
+            .collect();
+        let new_ids: BTreeSet<String> = changes.involved.iter().map(Uuid::to_string).collect();
 
-        // Actualizo el proyecto.
-        sqlx::query(
+        let mut tx = pool.begin().await?;
+
+        // Actualizo el proyecto, solo si sigue en el estado que se revisó.
+        let result = sqlx::query(
             r#" UPDATE projects
-                    SET name = ?, description = ?, state = ? WHERE uuid = ?"#,
+                    SET name = ?, description = ?, state = ?, archived = ?,
+                        updated_at = CURRENT_TIMESTAMP
+                    WHERE uuid = ? AND state = ? AND deleted_at IS NULL"#,
         )
-        .bind(data.name.unwrap_or(previous.name))
-        .bind(
-            data.description
-                .unwrap_or(previous.description.unwrap_or_default()),
-        )
-        .bind(data.state.unwrap_or(previous.state))
+        .bind(changes.name)
+        .bind(changes.description)
+        .bind(new_state)
+        .bind(changes.archived)
         .bind(id.to_string())
-        .execute(pool)
+        .bind(previous.state)
+        .execute(&mut *tx)
         .await?;
 
-        let schema = data.involved.unwrap_or_default();
-        // Si quieren agregar involucrados:
-        if let Some(mut add) = schema.add {
-            // Obtengo a los involucrados en el proyecto.
-            let previous_ids = sqlx::query(
-                "SELECT club_member_uuid FROM project_involvement WHERE project_uuid = ?",
+        if result.rows_affected() == 0 {
+            return Ok(None);
+        }
+
+        if new_state != previous.state {
+            ProjectStateChangeModel::create(
+                &previous.uuid,
+                previous.state,
+                new_state,
+                reason,
+                author,
+                &mut tx,
+            )
+            .await?;
+        }
+
+        // Agrego a los nuevos involucrados. `previous` no incluye a los integrantes eliminados,
+        // que siguen en `project_involvement`, así que alguno podría estar ya.
+        for member_id in new_ids.difference(&previous_ids) {
+            sqlx::query(
+                "INSERT OR IGNORE INTO project_involvement(project_uuid, club_member_uuid) \
+                VALUES (?, ?)",
             )
             .bind(id.to_string())
-            .fetch_all(pool)
-            .await?
-            .iter()
-            .map(|row| -> String { row.get("club_member_uuid") })
-            .collect::<String>();
+            .bind(member_id)
+            .execute(&mut *tx)
+            .await?;
+        }
 
-            // Quito los duplicados que quieren agregar.
-            add.sort_unstable();
-            add.dedup();
+        // Quito a los que ya no están.
+        for member_id in previous_ids.difference(&new_ids) {
+            sqlx::query(
+                "DELETE FROM project_involvement WHERE project_uuid = ? AND club_member_uuid = ?",
+            )
+            .bind(id.to_string())
+            .bind(member_id)
+            .execute(&mut *tx)
+            .await?;
+        }
 
-            // Quito los involucrados que ya están en el proyecto.
-            let add = add
-                .iter()
-                .filter(|add_id| !previous_ids.contains(&add_id.to_string()))
-                .collect::<Vec<&Uuid>>();
+        tx.commit().await?;
 
-            // Agrego a los resultantes.
-            let mut qb: QueryBuilder<Sqlite> = QueryBuilder::new(
-                "INSERT INTO project_involvement(project_uuid, club_member_uuid) ",
-            );
+        // Entrego el proyecto resultante.
+        ProjectModel::find_by_id(id, pool).await.map(Some)
+    }
 
-            qb.push_values(add, |mut b, value| {
-                b.push_bind(id.to_string()).push_bind(value.to_string());
-            });
+    /// Elimina un proyecto.
+    ///
+    /// La eliminación es 'suave': el proyecto deja de aparecer en la API, pero se mantiene junto
+    /// con sus involucrados hasta que pase el periodo de retención.
+    pub async fn delete(
+        id: uuid::Uuid,
+        pool: &sqlx::SqlitePool,
+    ) -> Result<SqliteQueryResult, sqlx::Error> {
+        sqlx::query(
+            "UPDATE projects SET deleted_at = CURRENT_TIMESTAMP WHERE uuid = ? AND deleted_at IS NULL",
+        )
+        .bind(id.to_string())
+        .execute(pool)
+        .await
+    }
 
-            qb.build().execute(pool).await?;
+    /// Restaura un proyecto eliminado después de `deleted_since`.
+    ///
+    /// Los proyectos eliminados antes de esa fecha ya no se pueden restaurar, y se tratan como
+    /// si no existieran.
+    pub async fn restore(
+        id: uuid::Uuid,
+        deleted_since: NaiveDateTime,
+        pool: &sqlx::SqlitePool,
+    ) -> Result<ProjectModel, sqlx::Error> {
+        let result =
+            sqlx::query("UPDATE projects SET deleted_at = NULL WHERE uuid = ? AND deleted_at > ?")
+                .bind(id.to_string())
+                .bind(deleted_since)
+                .execute(pool)
+                .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(sqlx::Error::RowNotFound);
         }
 
-        // Si quieren quitar involucrados
-        if let Some(mut remove) = schema.remove {
-            if !remove.is_empty() {
-                // Quito los duplicados.
-                remove.sort_unstable();
-                remove.dedup();
+        ProjectModel::find_by_id(id, pool).await
+    }
 
-                let mut qb: QueryBuilder<Sqlite> = QueryBuilder::new(
-                    "DELETE FROM project_involvement WHERE club_member_uuid IN (",
-                );
+    /// Elimina definitivamente los proyectos eliminados antes de `deleted_before`.
+    ///
+    /// De acuerdo a la configuración de la BDD, esto involucra todas las filas de project
+    /// involvement donde estén relacionados esos proyectos.
+    pub async fn purge_deleted(
+        deleted_before: NaiveDateTime,
+        pool: &sqlx::SqlitePool,
+    ) -> Result<SqliteQueryResult, sqlx::Error> {
+        sqlx::query("DELETE FROM projects WHERE deleted_at <= ?")
+            .bind(deleted_before)
+            .execute(pool)
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ProjectDocument, ProjectModel, ProjectState, ProjectWorkflow};
+    use crate::{
+        testing,
+        v1::{models::ChangeAuthor, schemas::project::CreateProjectSchema},
+    };
+
+    /// Todos los estados, en el orden en que se declaran.
+    const STATES: [ProjectState; 6] = [
+        ProjectState::NotStarted,
+        ProjectState::InProgress,
+        ProjectState::Idle,
+        ProjectState::LookingForIdeas,
+        ProjectState::Finished,
+        ProjectState::Cancelled,
+    ];
+
+    /// El grafo por defecto sigue la tabla de la documentación de `ProjectWorkflow`.
+    #[test]
+    fn default_workflow_follows_the_table() {
+        use ProjectState as ps;
+
+        let workflow = ProjectWorkflow::default();
+
+        assert!(workflow.allows(ps::NotStarted, ps::InProgress));
+        assert!(workflow.allows(ps::InProgress, ps::Finished));
+        assert!(workflow.allows(ps::Idle, ps::InProgress));
+        assert!(workflow.allows(ps::LookingForIdeas, ps::NotStarted));
+
+        assert!(!workflow.allows(ps::NotStarted, ps::Finished));
+        assert!(!workflow.allows(ps::NotStarted, ps::Idle));
+        assert!(!workflow.allows(ps::Idle, ps::Finished));
+        assert!(!workflow.allows(ps::InProgress, ps::NotStarted));
+    }
 
-                let mut separated = qb.separated(", ");
+    /// `Finished` y `Cancelled` son finales en el grafo por defecto, pero quedarse en el mismo
+    /// estado siempre está permitido.
+    #[test]
+    fn default_workflow_has_final_states() {
+        let workflow = ProjectWorkflow::default();
+
+        for from in [ProjectState::Finished, ProjectState::Cancelled] {
+            for to in STATES {
+                assert_eq!(workflow.allows(from, to), from == to, "{from:?} -> {to:?}");
+            }
+        }
+    }
 
-                for id in remove.iter() {
-                    separated.push_bind(id.to_string());
-                }
+    /// Un grafo leído de `PROJECT_TRANSITIONS` solo permite los cambios que lista, y los estados
+    /// que no aparecen quedan como finales.
+    #[test]
+    fn parsed_workflow_only_allows_listed_transitions() {
+        use ProjectState as ps;
+
+        let workflow: ProjectWorkflow = " NotStarted: InProgress , Cancelled ;InProgress:Finished;"
+            .parse()
+            .expect("El grafo debería ser válido");
+
+        assert!(workflow.allows(ps::NotStarted, ps::InProgress));
+        assert!(workflow.allows(ps::NotStarted, ps::Cancelled));
+        assert!(workflow.allows(ps::InProgress, ps::Finished));
+
+        assert!(!workflow.allows(ps::NotStarted, ps::LookingForIdeas));
+        assert!(!workflow.allows(ps::InProgress, ps::Idle));
+        for to in STATES {
+            assert_eq!(
+                workflow.allows(ps::Idle, to),
+                to == ps::Idle,
+                "Idle -> {to:?}"
+            );
+        }
+    }
 
-                separated.push_unseparated(") ");
+    /// Un grafo vacío deja todos los estados como finales.
+    #[test]
+    fn empty_workflow_only_allows_staying() {
+        let workflow: ProjectWorkflow = "".parse().expect("El grafo debería ser válido");
 
-                qb.build().execute(pool).await?;
+        for from in STATES {
+            for to in STATES {
+                assert_eq!(workflow.allows(from, to), from == to, "{from:?} -> {to:?}");
             }
         }
+    }
 
-        // Entrego el proyecto resultante.
-        ProjectModel::find_by_id(id, pool).await
+    /// Los grafos con estados desconocidos o sin `:` se rechazan.
+    #[test]
+    fn invalid_workflows_are_rejected() {
+        for spec in [
+            "NotStarted",
+            "NotStarted:Paused",
+            "Paused:InProgress",
+            "NotStarted:InProgress;Idle",
+        ] {
+            assert!(spec.parse::<ProjectWorkflow>().is_err(), "{spec}");
+        }
     }
 
-    /// Elimina un proyecto de la base de datos
-    ///
-    /// De acuerdo a la configuración de la BDD, esto debería involucrar todas las filas de project
-    /// involvement donde esté relacionado ese proyecto.
-    pub async fn delete(
-        id: uuid::Uuid,
-        pool: &sqlx::SqlitePool,
-    ) -> Result<SqliteQueryResult, sqlx::Error> {
-        sqlx::query("DELETE FROM projects WHERE $1")
-            .bind(id.to_string())
-            .execute(pool)
+    /// Si algún involucrado no existe, el proyecto no se crea, así que se puede volver a intentar
+    /// sin duplicarlo.
+    #[actix_web::test]
+    async fn create_with_unknown_member_writes_nothing() {
+        let pool = testing::pool().await;
+        let member_id = testing::create_member(&pool, "member").await;
+
+        let result = ProjectModel::create(
+            CreateProjectSchema {
+                name: String::from("Fantasma"),
+                description: None,
+                involved: Some(vec![member_id.parse().unwrap(), uuid::Uuid::new_v4()]),
+            },
+            &pool,
+        )
+        .await;
+
+        match result {
+            Err(sqlx::Error::Database(err)) => assert!(err.is_foreign_key_violation()),
+            Err(err) => panic!("Se esperaba un error de llave foránea, pero se obtuvo {err}"),
+            Ok(_) => panic!("El proyecto no debería crearse"),
+        }
+
+        let projects: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM projects")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        let involvement: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM project_involvement")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!((projects, involvement), (0, 0));
+    }
+
+    /// Un integrante eliminado sigue involucrado en sus proyectos; si se restaura y se vuelve a
+    /// agregar al proyecto, no queda dos veces.
+    #[actix_web::test]
+    async fn restored_member_is_not_involved_twice() {
+        let pool = testing::pool().await;
+        let member_id = testing::create_member(&pool, "member").await;
+        let project = ProjectModel::create(
+            CreateProjectSchema {
+                name: String::from("Proyecto"),
+                description: None,
+                involved: Some(vec![member_id.parse().unwrap(), member_id.parse().unwrap()]),
+            },
+            &pool,
+        )
+        .await
+        .unwrap();
+
+        sqlx::query("UPDATE club_members SET deleted_at = CURRENT_TIMESTAMP WHERE uuid = ?")
+            .bind(&member_id)
+            .execute(&pool)
+            .await
+            .unwrap();
+        let previous = ProjectModel::find_by_id(project.uuid.parse().unwrap(), &pool)
+            .await
+            .unwrap();
+        assert!(previous.involved.is_empty());
+
+        sqlx::query("UPDATE club_members SET deleted_at = NULL WHERE uuid = ?")
+            .bind(&member_id)
+            .execute(&pool)
+            .await
+            .unwrap();
+        let mut changes = ProjectDocument::from(&previous);
+        changes.involved.push(member_id.parse().unwrap());
+        let author = ChangeAuthor {
+            app: "app",
+            member: None,
+        };
+
+        let updated = ProjectModel::update(previous, changes, None, &author, &pool)
+            .await
+            .unwrap()
+            .expect("El proyecto debería actualizarse");
+
+        assert_eq!(updated.involved.len(), 1);
+        let rows: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM project_involvement")
+            .fetch_one(&pool)
             .await
+            .unwrap();
+        assert_eq!(rows, 1);
     }
 }
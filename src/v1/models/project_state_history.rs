@@ -0,0 +1,80 @@
+//! Modelo para el historial de estados de un proyecto.
+//!
+//! Cada vez que cambia el estado de un proyecto se guarda una fila con el estado anterior, el
+//! nuevo, el motivo (si se entregó) y quién hizo el cambio.
+
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use sqlx::{sqlite::SqliteQueryResult, SqliteConnection};
+use utoipa::ToSchema;
+
+use crate::v1::models::{project::ProjectState, ChangeAuthor};
+
+/// Modelo de un cambio de estado de un proyecto.
+#[derive(Debug, Serialize, Deserialize, sqlx::FromRow, ToSchema)]
+pub struct ProjectStateChangeModel {
+    /// ID correlativa del cambio.
+    pub id: i64,
+    /// Proyecto cuyo estado cambió.
+    #[serde(skip_serializing)]
+    pub project_uuid: String,
+    /// Estado anterior del proyecto.
+    pub from_state: String,
+    /// Estado nuevo del proyecto.
+    pub to_state: String,
+    /// Motivo del cambio, si se entregó.
+    pub reason: Option<String>,
+    /// App que hizo el cambio.
+    pub changed_by_app: String,
+    /// Integrante en cuyo nombre se hizo el cambio, si la petición traía una sesión.
+    pub changed_by_member: Option<String>,
+    /// Fecha del cambio.
+    pub created_at: NaiveDateTime,
+}
+
+impl ProjectStateChangeModel {
+    /// Obtiene los cambios de estado de un proyecto, del más antiguo al más reciente.
+    pub async fn get_by_project(
+        project_id: &str,
+        pool: &sqlx::SqlitePool,
+    ) -> Result<Vec<ProjectStateChangeModel>, sqlx::Error> {
+        sqlx::query_as!(
+            ProjectStateChangeModel,
+            "SELECT * FROM project_state_history WHERE project_uuid = ? ORDER BY id",
+            project_id
+        )
+        .fetch_all(pool)
+        .await
+    }
+
+    /// Guarda un cambio de estado.
+    ///
+    /// Recibe una conexión en vez del pool, para guardarlo en la misma transacción que el
+    /// cambio.
+    pub async fn create(
+        project_id: &str,
+        from: ProjectState,
+        to: ProjectState,
+        reason: Option<&str>,
+        author: &ChangeAuthor<'_>,
+        conn: &mut SqliteConnection,
+    ) -> Result<SqliteQueryResult, sqlx::Error> {
+        let from = from.as_str();
+        let to = to.as_str();
+
+        sqlx::query!(
+            r#"
+    INSERT INTO project_state_history
+        (project_uuid, from_state, to_state, reason, changed_by_app, changed_by_member)
+    VALUES (?, ?, ?, ?, ?, ?)"#,
+            project_id,
+            from,
+            to,
+            reason,
+            author.app,
+            author.member
+        )
+        .execute(conn)
+        .await
+    }
+}
@@ -0,0 +1,476 @@
+//! Modelo para la verificación en dos pasos (TOTP) de un integrante.
+//!
+//! Los integrantes de la directiva pueden activar códigos temporales según el RFC 6238, que
+//! generan en una aplicación como Google Authenticator. Una vez activados, se piden al iniciar
+//! sesión. Cada integrante recibe además códigos de recuperación de un solo uso, por si pierde su
+//! dispositivo; de esos solo se guarda el hash.
+//!
+//! Al iniciar sesión, solo sirve el último desafío entregado, y cada integrante tiene
+//! `MAX_FAILED_ATTEMPTS` intentos para responderlo. Al agotarlos, el desafío deja de servir y no se
+//! entregan otros durante `LOCKOUT_MINUTES` minutos. Los códigos para activar o desactivar la
+//! verificación cuentan en los mismos intentos, y también se rechazan durante el bloqueo.
+
+use chrono::NaiveDateTime;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sqlx::sqlite::SqliteQueryResult;
+use totp_rs::{Algorithm, Secret, TOTP};
+
+/// Nombre con el que aparece la API en la aplicación de autenticación.
+const ISSUER: &str = "ExDev";
+
+/// Segundos que dura cada código.
+const STEP: u64 = 30;
+
+/// Cantidad de códigos de recuperación que recibe un integrante.
+const RECOVERY_CODES: usize = 10;
+
+/// Intentos fallidos tras los cuales se bloquea el inicio de sesión con la verificación.
+pub const MAX_FAILED_ATTEMPTS: i64 = 5;
+
+/// Minutos que dura el bloqueo tras demasiados intentos fallidos.
+pub const LOCKOUT_MINUTES: i64 = 15;
+
+/// Modelo de la verificación en dos pasos de un integrante.
+#[derive(Debug, Serialize, Deserialize, sqlx::FromRow)]
+pub struct TotpModel {
+    /// Integrante dueño del secreto.
+    pub club_member_uuid: String,
+    /// Secreto compartido, codificado en base32.
+    pub secret: String,
+    /// Fecha en que se confirmó la activación. Mientras sea `None`, no se piden códigos.
+    pub enabled_at: Option<NaiveDateTime>,
+    /// Último intervalo en el que se usó un código, para que un código no sirva dos veces.
+    pub last_used_step: Option<i64>,
+    /// Identificador del último desafío entregado al iniciar sesión. Solo ese desafío sirve.
+    pub challenge_nonce: Option<String>,
+    /// Intentos fallidos desde el último inicio de sesión exitoso o bloqueo.
+    pub failed_attempts: i64,
+    /// Fecha (UTC) hasta la cual no se entregan desafíos, tras demasiados intentos fallidos.
+    pub locked_until: Option<NaiveDateTime>,
+    /// Fecha de creación de esta fila de la BDD.
+    pub created_at: NaiveDateTime,
+}
+
+impl TotpModel {
+    /// Genera un nuevo secreto aleatorio, codificado en base32.
+    pub fn generate_secret() -> String {
+        let mut secret = [0u8; 20];
+        rand::rngs::OsRng.fill_bytes(&mut secret);
+
+        Secret::Raw(secret.to_vec()).to_encoded().to_string()
+    }
+
+    /// Genera códigos de recuperación nuevos.
+    ///
+    /// Entrega los códigos, que se le muestran una sola vez al integrante, y sus hashes, que son
+    /// los que se guardan.
+    pub fn generate_recovery_codes() -> (Vec<String>, Vec<String>) {
+        let codes: Vec<String> = (0..RECOVERY_CODES)
+            .map(|_| {
+                let mut code = [0u8; 5];
+                rand::rngs::OsRng.fill_bytes(&mut code);
+                let code = hex::encode(code);
+                format!("{}-{}", &code[..5], &code[5..])
+            })
+            .collect();
+        let hashes = codes
+            .iter()
+            .map(|code| TotpModel::hash_recovery_code(code))
+            .collect();
+
+        (codes, hashes)
+    }
+
+    /// Calcula el hash con el que se guarda un código de recuperación.
+    ///
+    /// No distingue mayúsculas ni guiones, para que sea fácil de escribir.
+    pub fn hash_recovery_code(code: &str) -> String {
+        let code: String = code
+            .chars()
+            .filter(|c| *c != '-')
+            .map(|c| c.to_ascii_lowercase())
+            .collect();
+
+        hex::encode(Sha256::digest(code.as_bytes()))
+    }
+
+    /// Genera el generador de códigos a partir del secreto.
+    ///
+    /// `account` es el nombre con el que aparece el integrante en la aplicación de autenticación.
+    fn totp(&self, account: &str) -> anyhow::Result<TOTP> {
+        let secret = Secret::Encoded(self.secret.clone())
+            .to_bytes()
+            .map_err(|err| anyhow::anyhow!("{err:?}"))?;
+
+        Ok(TOTP::new(
+            Algorithm::SHA1,
+            6,
+            0,
+            STEP,
+            secret,
+            Some(ISSUER.to_owned()),
+            account.replace(':', ""),
+        )?)
+    }
+
+    /// URI `otpauth://` para agregar el secreto a una aplicación de autenticación.
+    pub fn otpauth_uri(&self, account: &str) -> anyhow::Result<String> {
+        Ok(self.totp(account)?.get_url())
+    }
+
+    /// Busca el intervalo al que corresponde un código.
+    ///
+    /// Se acepta el intervalo actual y sus vecinos, para tolerar relojes algo desfasados.
+    pub fn matching_step(&self, code: &str) -> Option<i64> {
+        let totp = self.totp("").ok()?;
+        let now = chrono::Utc::now().timestamp() as u64;
+
+        [now - STEP, now, now + STEP]
+            .into_iter()
+            .find(|time| totp.check(code, *time))
+            .map(|time| (time / STEP) as i64)
+    }
+
+    /// Verifica un código temporal o de recuperación, y lo marca como usado.
+    ///
+    /// Un código temporal no sirve si ya se usó otro del mismo intervalo o uno posterior; un
+    /// código de recuperación sirve una sola vez.
+    pub async fn verify(&self, code: &str, pool: &sqlx::SqlitePool) -> Result<bool, sqlx::Error> {
+        let code = code.trim();
+
+        if code.len() == 6 && code.chars().all(|c| c.is_ascii_digit()) {
+            return match self.matching_step(code) {
+                Some(step) => TotpModel::use_step(&self.club_member_uuid, step, pool).await,
+                None => Ok(false),
+            };
+        }
+
+        let code_hash = TotpModel::hash_recovery_code(code);
+        let result = sqlx::query!(
+            r#"
+    UPDATE member_recovery_codes SET used_at = CURRENT_TIMESTAMP
+    WHERE club_member_uuid = ? AND code_hash = ? AND used_at IS NULL"#,
+            self.club_member_uuid,
+            code_hash
+        )
+        .execute(pool)
+        .await?;
+
+        Ok(result.rows_affected() == 1)
+    }
+
+    /// Obtiene la verificación en dos pasos de un integrante.
+    pub async fn find(member_id: &str, pool: &sqlx::SqlitePool) -> Result<TotpModel, sqlx::Error> {
+        sqlx::query_as!(
+            TotpModel,
+            "SELECT * FROM member_totp WHERE club_member_uuid = ?",
+            member_id
+        )
+        .fetch_one(pool)
+        .await
+    }
+
+    /// Indica si un integrante tiene activada la verificación en dos pasos.
+    pub async fn is_enabled(member_id: &str, pool: &sqlx::SqlitePool) -> Result<bool, sqlx::Error> {
+        match TotpModel::find(member_id, pool).await {
+            Ok(totp) => Ok(totp.enabled_at.is_some()),
+            Err(sqlx::Error::RowNotFound) => Ok(false),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Guarda un nuevo secreto para un integrante, aún sin activar.
+    ///
+    /// Si el integrante ya había empezado a activarla, el secreto anterior se reemplaza. Si ya la
+    /// tiene activada, no se hace nada.
+    pub async fn start_enrolment(
+        member_id: &str,
+        secret: &str,
+        pool: &sqlx::SqlitePool,
+    ) -> Result<SqliteQueryResult, sqlx::Error> {
+        sqlx::query!(
+            r#"
+    INSERT INTO member_totp (club_member_uuid, secret) VALUES (?, ?)
+    ON CONFLICT(club_member_uuid) DO UPDATE
+    SET secret = excluded.secret, last_used_step = NULL, created_at = CURRENT_TIMESTAMP
+    WHERE enabled_at IS NULL"#,
+            member_id,
+            secret
+        )
+        .execute(pool)
+        .await
+    }
+
+    /// Activa la verificación en dos pasos, reemplaza los códigos de recuperación y reinicia los
+    /// intentos fallidos.
+    pub async fn enable(
+        member_id: &str,
+        step: i64,
+        recovery_hashes: &[String],
+        pool: &sqlx::SqlitePool,
+    ) -> Result<(), sqlx::Error> {
+        let mut tx = pool.begin().await?;
+
+        sqlx::query!(
+            r#"
+    UPDATE member_totp
+    SET enabled_at = CURRENT_TIMESTAMP, last_used_step = ?, failed_attempts = 0
+    WHERE club_member_uuid = ?"#,
+            step,
+            member_id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query!(
+            "DELETE FROM member_recovery_codes WHERE club_member_uuid = ?",
+            member_id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        for code_hash in recovery_hashes {
+            sqlx::query!(
+                "INSERT INTO member_recovery_codes (club_member_uuid, code_hash) VALUES (?, ?)",
+                member_id,
+                code_hash
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        tx.commit().await
+    }
+
+    /// Registra un nuevo desafío de inicio de sesión, que reemplaza a los anteriores.
+    ///
+    /// Devuelve `false` si el integrante está bloqueado por demasiados intentos fallidos.
+    pub async fn start_challenge(
+        member_id: &str,
+        nonce: &str,
+        pool: &sqlx::SqlitePool,
+    ) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query!(
+            r#"
+    UPDATE member_totp SET challenge_nonce = ?
+    WHERE club_member_uuid = ? AND (locked_until IS NULL OR locked_until <= datetime('now'))"#,
+            nonce,
+            member_id
+        )
+        .execute(pool)
+        .await?;
+
+        Ok(result.rows_affected() == 1)
+    }
+
+    /// Cuenta un intento de responder un desafío, antes de revisar el código.
+    ///
+    /// Devuelve `false` si el desafío ya no es el vigente o si se agotaron los intentos, de forma
+    /// que ni siquiera varias peticiones simultáneas puedan probar más códigos que el máximo.
+    pub async fn claim_attempt(
+        member_id: &str,
+        nonce: &str,
+        pool: &sqlx::SqlitePool,
+    ) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query!(
+            r#"
+    UPDATE member_totp SET failed_attempts = failed_attempts + 1
+    WHERE club_member_uuid = ? AND challenge_nonce = ? AND failed_attempts < ?"#,
+            member_id,
+            nonce,
+            MAX_FAILED_ATTEMPTS
+        )
+        .execute(pool)
+        .await?;
+
+        Ok(result.rows_affected() == 1)
+    }
+
+    /// Cuenta un intento de ingresar un código fuera del inicio de sesión, como al activar o
+    /// desactivar la verificación, antes de revisarlo.
+    ///
+    /// Usa los mismos contadores que los desafíos de inicio de sesión. Devuelve `false` si el
+    /// integrante está bloqueado o si agotó sus intentos.
+    pub async fn claim_code_attempt(
+        member_id: &str,
+        pool: &sqlx::SqlitePool,
+    ) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query!(
+            r#"
+    UPDATE member_totp SET failed_attempts = failed_attempts + 1
+    WHERE club_member_uuid = ? AND failed_attempts < ?
+    AND (locked_until IS NULL OR locked_until <= datetime('now'))"#,
+            member_id,
+            MAX_FAILED_ATTEMPTS
+        )
+        .execute(pool)
+        .await?;
+
+        Ok(result.rows_affected() == 1)
+    }
+
+    /// Termina un desafío respondido correctamente y reinicia los intentos fallidos.
+    pub async fn finish_challenge(
+        member_id: &str,
+        pool: &sqlx::SqlitePool,
+    ) -> Result<SqliteQueryResult, sqlx::Error> {
+        sqlx::query!(
+            r#"
+    UPDATE member_totp SET challenge_nonce = NULL, failed_attempts = 0
+    WHERE club_member_uuid = ?"#,
+            member_id
+        )
+        .execute(pool)
+        .await
+    }
+
+    /// Bloquea al integrante si agotó sus intentos, invalidando su desafío.
+    ///
+    /// Devuelve `true` si se bloqueó.
+    pub async fn lock_if_exhausted(
+        member_id: &str,
+        pool: &sqlx::SqlitePool,
+    ) -> Result<bool, sqlx::Error> {
+        let lockout = format!("+{LOCKOUT_MINUTES} minutes");
+        let result = sqlx::query!(
+            r#"
+    UPDATE member_totp
+    SET challenge_nonce = NULL, failed_attempts = 0, locked_until = datetime('now', ?)
+    WHERE club_member_uuid = ? AND failed_attempts >= ?"#,
+            lockout,
+            member_id,
+            MAX_FAILED_ATTEMPTS
+        )
+        .execute(pool)
+        .await?;
+
+        Ok(result.rows_affected() == 1)
+    }
+
+    /// Marca un intervalo como usado, si es posterior al último usado.
+    async fn use_step(
+        member_id: &str,
+        step: i64,
+        pool: &sqlx::SqlitePool,
+    ) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query!(
+            r#"
+    UPDATE member_totp SET last_used_step = ?
+    WHERE club_member_uuid = ? AND (last_used_step IS NULL OR last_used_step < ?)"#,
+            step,
+            member_id,
+            step
+        )
+        .execute(pool)
+        .await?;
+
+        Ok(result.rows_affected() == 1)
+    }
+
+    /// Desactiva la verificación en dos pasos, eliminando el secreto y los códigos de
+    /// recuperación.
+    pub async fn delete(member_id: &str, pool: &sqlx::SqlitePool) -> Result<(), sqlx::Error> {
+        let mut tx = pool.begin().await?;
+
+        sqlx::query!(
+            "DELETE FROM member_recovery_codes WHERE club_member_uuid = ?",
+            member_id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query!(
+            "DELETE FROM member_totp WHERE club_member_uuid = ?",
+            member_id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::SqlitePool;
+
+    use super::{TotpModel, STEP};
+    use crate::testing;
+
+    /// Crea una base de datos en memoria con un integrante que tiene la verificación activada.
+    async fn setup(recovery_hashes: &[String]) -> (SqlitePool, TotpModel) {
+        let pool = testing::pool().await;
+        let member_id = testing::create_member(&pool, "board").await;
+
+        TotpModel::start_enrolment(&member_id, &TotpModel::generate_secret(), &pool)
+            .await
+            .expect("El secreto debería guardarse");
+        TotpModel::enable(&member_id, 0, recovery_hashes, &pool)
+            .await
+            .expect("La verificación debería activarse");
+
+        let totp = TotpModel::find(&member_id, &pool)
+            .await
+            .expect("La verificación debería existir");
+
+        (pool, totp)
+    }
+
+    /// Código temporal del intervalo que contiene a `time`.
+    fn code_at(totp: &TotpModel, time: u64) -> String {
+        totp.totp("")
+            .expect("El secreto debería ser válido")
+            .generate(time)
+    }
+
+    /// Un código temporal sirve una sola vez.
+    #[actix_web::test]
+    async fn totp_code_cannot_be_replayed() {
+        let (pool, totp) = setup(&[]).await;
+        let code = code_at(&totp, chrono::Utc::now().timestamp() as u64);
+
+        assert!(totp.verify(&code, &pool).await.unwrap());
+        assert!(!totp.verify(&code, &pool).await.unwrap());
+    }
+
+    /// Tras usar un código, los de intervalos anteriores dejan de servir, aunque sigan dentro de
+    /// la tolerancia de reloj.
+    #[actix_web::test]
+    async fn older_totp_codes_are_rejected() {
+        let (pool, totp) = setup(&[]).await;
+        let now = chrono::Utc::now().timestamp() as u64;
+
+        assert!(totp.verify(&code_at(&totp, now), &pool).await.unwrap());
+        assert!(!totp
+            .verify(&code_at(&totp, now - STEP), &pool)
+            .await
+            .unwrap());
+    }
+
+    /// Un código que no corresponde a ningún intervalo cercano no sirve, ni cuenta como usado.
+    #[actix_web::test]
+    async fn wrong_totp_code_is_rejected() {
+        let (pool, totp) = setup(&[]).await;
+        let now = chrono::Utc::now().timestamp() as u64;
+
+        assert!(!totp
+            .verify(&code_at(&totp, now - 10 * STEP), &pool)
+            .await
+            .unwrap());
+        assert!(totp.verify(&code_at(&totp, now), &pool).await.unwrap());
+    }
+
+    /// Un código de recuperación sirve una sola vez, sin importar mayúsculas ni guiones.
+    #[actix_web::test]
+    async fn recovery_code_cannot_be_replayed() {
+        let (codes, hashes) = TotpModel::generate_recovery_codes();
+        let (pool, totp) = setup(&hashes).await;
+
+        let code = codes[0].to_uppercase().replace('-', "");
+        assert!(totp.verify(&code, &pool).await.unwrap());
+        assert!(!totp.verify(&codes[0], &pool).await.unwrap());
+        assert!(totp.verify(&codes[1], &pool).await.unwrap());
+    }
+}
@@ -0,0 +1,176 @@
+//! Especificación OpenAPI de la API.
+//!
+//! La especificación se genera a partir de los handlers de `res` (cada uno tiene su
+//! `#[utoipa::path]`) y de los esquemas que reciben y entregan. Cada grupo de rutas de `res` tiene
+//! su propia documentación (`MemberApi`, `ProjectApi`, ...), que aquí se anida bajo el mismo
+//! prefijo con el que se registran sus rutas.
+//!
+//! La especificación se sirve en `/v1/openapi.json`, y una página de Swagger UI para explorarla
+//! en `/docs/`. Ninguna de las dos necesita una llave de la API.
+
+use utoipa::{
+    openapi::security::{ApiKey, ApiKeyValue, HttpAuthScheme, HttpBuilder, SecurityScheme},
+    Modify, OpenApi,
+};
+use utoipa_swagger_ui::SwaggerUi;
+
+use crate::v1::{middleware::member_session::SESSION_HEADER, res};
+
+/// Especificación OpenAPI de todas las rutas de `/v1`.
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "API ExDev",
+        description = "API multipropósitos del club ExDev. Todas las rutas necesitan una llave de \
+            la API en el header `Authorization`, y las que actúan en nombre de un integrante \
+            además su token de sesión en `X-Session-Token`."
+    ),
+    nest(
+        (path = "/v1/auth", api = res::AuthApi),
+        (path = "/v1/members", api = res::MemberApi),
+        (path = "/v1/me", api = res::MeApi),
+        (path = "/v1/projects", api = res::ProjectApi),
+    ),
+    modifiers(&SecurityAddon),
+    security(("api_key" = []), ("api_key" = [], "session" = [])),
+)]
+pub struct ApiDoc;
+
+/// Agrega a la especificación las formas de autenticarse.
+struct SecurityAddon;
+
+impl Modify for SecurityAddon {
+    /// Registra la llave de la API (`api_key`) y el token de sesión de un integrante (`session`).
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+
+        components.add_security_scheme(
+            "api_key",
+            SecurityScheme::Http(
+                HttpBuilder::new()
+                    .scheme(HttpAuthScheme::Bearer)
+                    .description(Some("Llave de la App, con la forma `ExDevUtem_...`"))
+                    .build(),
+            ),
+        );
+        components.add_security_scheme(
+            "session",
+            SecurityScheme::ApiKey(ApiKey::Header(ApiKeyValue::with_description(
+                SESSION_HEADER,
+                "Token de sesión de un integrante",
+            ))),
+        );
+    }
+}
+
+/// Servicio con la especificación (`/v1/openapi.json`) y la página de Swagger UI (`/docs/`).
+///
+/// Se debe registrar antes que `v1::routes()`, pues el scope `/v1` responde un 401 a todas las
+/// peticiones sin llave, incluso a las rutas que no conoce.
+pub fn docs() -> SwaggerUi {
+    SwaggerUi::new("/docs/{_:.*}").url("/v1/openapi.json", ApiDoc::openapi())
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{
+        http::{Method, StatusCode},
+        test, web, App, HttpResponse,
+    };
+    use utoipa::OpenApi;
+
+    use super::ApiDoc;
+    use crate::{
+        testing,
+        v1::{self, models::auth::Scope},
+    };
+
+    /// Estado con el que responde el servidor de prueba a las peticiones que no llegan a ningún
+    /// handler.
+    const UNROUTED: StatusCode = StatusCode::IM_A_TEAPOT;
+
+    /// Operaciones de la especificación, como `(método, ruta completa, operationId)`.
+    fn documented_routes() -> Vec<(Method, String, String)> {
+        ApiDoc::openapi()
+            .paths
+            .paths
+            .into_iter()
+            .flat_map(|(path, item)| {
+                [
+                    (Method::GET, item.get),
+                    (Method::POST, item.post),
+                    (Method::PUT, item.put),
+                    (Method::PATCH, item.patch),
+                    (Method::DELETE, item.delete),
+                ]
+                .into_iter()
+                .filter_map(move |(method, operation)| {
+                    let operation_id = operation?.operation_id?;
+                    Some((method, path.to_owned(), operation_id))
+                })
+            })
+            .collect()
+    }
+
+    /// Ruta concreta para una ruta de la especificación, con una UUID en cada parámetro.
+    fn concrete_path(path: &str) -> String {
+        path.split('/')
+            .map(|segment| {
+                if segment.starts_with('{') {
+                    "6f1c2a4e-8a43-4a43-9d0e-3f2b1a0c9d8e"
+                } else {
+                    segment
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("/")
+    }
+
+    /// Cada operación de la especificación debe llegar a un handler del servidor con el mismo
+    /// método y la misma ruta completa.
+    ///
+    /// Lo contrario no hace falta revisarlo: cada grupo de rutas se declara con `route_group!`,
+    /// que registra y documenta los mismos handlers.
+    #[actix_web::test]
+    async fn every_documented_route_is_served() {
+        let pool = testing::pool().await;
+        let (_, key) = testing::create_app(&pool, &Scope::ALL).await;
+        let service = test::init_service(
+            App::new()
+                .app_data(testing::state(&pool))
+                .service(v1::routes())
+                .default_service(web::to(|| async { HttpResponse::new(UNROUTED) })),
+        )
+        .await;
+
+        let documented = documented_routes();
+        assert!(!documented.is_empty());
+
+        // Una ruta que no existe no llega a ningún handler, así que la prueba sí distingue.
+        let probes = documented
+            .into_iter()
+            .map(|(method, path, handler)| (method, path, Some(handler)))
+            .chain([(Method::DELETE, String::from("/v1/me"), None)]);
+
+        let mut unserved = Vec::new();
+        for (method, path, handler) in probes {
+            let req = test::TestRequest::default()
+                .method(method.clone())
+                .uri(&concrete_path(&path))
+                .insert_header(("Authorization", format!("Bearer {key}")))
+                .to_request();
+            let served = test::call_service(&service, req).await.status() != UNROUTED;
+
+            match handler {
+                Some(handler) if !served => unserved.push(format!("{method} {path} ({handler})")),
+                None => assert!(!served, "{method} {path} no debería existir"),
+                _ => {}
+            }
+        }
+
+        assert!(
+            unserved.is_empty(),
+            "Rutas de la especificación OpenAPI que el servidor no sirve: {unserved:?}"
+        );
+    }
+}
@@ -0,0 +1,409 @@
+//! Políticas de acceso de la API.
+//!
+//! Una petición siempre la hace una App, y opcionalmente lo hace en nombre de un integrante que
+//! inició sesión. Este módulo decide si quien hace la petición puede realizar una acción:
+//!
+//! - Sin sesión, la App actúa por su cuenta y basta con que tenga el permiso (scope) de la acción.
+//! - Con sesión, la App necesita el mismo permiso que sin sesión, y además el rol del integrante
+//!   debe permitir la acción.
+//!
+//! Las reglas para los integrantes son las siguientes. El perfil son el nombre, cumpleaños, email
+//! y Github; editar a otro integrante incluye cambiar su estado.
+//!
+//! | Acción                                  | Rol mínimo |
+//! |-----------------------------------------|------------|
+//! | Ver integrantes y proyectos             | `guest`    |
+//! | Editar su propio perfil                 | `guest`    |
+//! | Crear y editar proyectos                | `member`   |
+//! | Crear integrantes y editar a otros      | `board`    |
+//! | Eliminar integrantes y proyectos        | `board`    |
+//! | Restaurar integrantes y proyectos       | `board`    |
+//! | Cambiar el rol de cualquier integrante  | `admin`    |
+//! | Forzar un cambio de estado no permitido | `admin`    |
+//! | Eliminar integrantes definitivamente    | `admin`    |
+//!
+//! Solo un administrador puede editar o eliminar a un integrante con un rol igual o superior al
+//! suyo, lo que incluye cambiar su propio estado o eliminarse a sí mismo.
+//!
+//! Sin sesión, eliminar definitivamente a un integrante o forzar un cambio de estado requiere el
+//! permiso `apps:admin`.
+
+use std::future::Future;
+use std::pin::Pin;
+
+use actix_web::{dev::Payload, FromRequest, HttpRequest};
+
+use crate::v1::{
+    middleware::{
+        api_key::AuthenticatedApp,
+        member_session::{MemberSession, SESSION_HEADER},
+    },
+    models::{
+        auth::Scope,
+        club_member::{ClubMemberModel, Role},
+        ChangeAuthor,
+    },
+    responders::errors::ApiError,
+    schemas::club_member::MemberDocument,
+};
+
+/// Acciones que se pueden realizar sobre los recursos del club.
+pub enum Action<'a> {
+    /// Ver integrantes.
+    ReadMembers,
+    /// Crear un integrante.
+    CreateMember,
+    /// Actualizar los datos de un integrante.
+    UpdateMember {
+        /// El integrante que se quiere actualizar.
+        target: &'a ClubMemberModel,
+        /// Los datos que quedarían tras el cambio.
+        changes: &'a MemberDocument,
+    },
+    /// Eliminar a un integrante.
+    DeleteMember {
+        /// El integrante que se quiere eliminar.
+        target: &'a ClubMemberModel,
+    },
+    /// Restaurar a un integrante eliminado.
+    RestoreMember,
+    /// Eliminar definitivamente a un integrante.
+    PurgeMember,
+    /// Cambiar el estado de un integrante aunque el cambio no esté permitido.
+    OverrideMemberState,
+    /// Ver proyectos.
+    ReadProjects,
+    /// Crear o actualizar un proyecto.
+    WriteProject,
+    /// Eliminar un proyecto.
+    DeleteProject,
+    /// Restaurar un proyecto eliminado.
+    RestoreProject,
+}
+
+impl Action<'_> {
+    /// Permiso que necesita una App para realizar la acción por su cuenta.
+    fn app_scope(&self) -> Scope {
+        match self {
+            Action::ReadMembers => Scope::MembersRead,
+            Action::CreateMember
+            | Action::UpdateMember { .. }
+            | Action::DeleteMember { .. }
+            | Action::RestoreMember => Scope::MembersWrite,
+            Action::PurgeMember | Action::OverrideMemberState => Scope::AppsAdmin,
+            Action::ReadProjects => Scope::ProjectsRead,
+            Action::WriteProject | Action::DeleteProject | Action::RestoreProject => {
+                Scope::ProjectsWrite
+            }
+        }
+    }
+
+    /// Rol mínimo que necesita un integrante para realizar la acción.
+    fn required_role(&self, member: &ClubMemberModel) -> Role {
+        match self {
+            Action::ReadMembers | Action::ReadProjects => Role::Guest,
+            Action::UpdateMember { target, changes } if changes.role != target.role() => {
+                Role::Admin
+            }
+            Action::UpdateMember { target, changes }
+                if is_profile_edit(target, changes, member) =>
+            {
+                Role::Guest
+            }
+            Action::UpdateMember { .. } => Role::Board,
+            Action::WriteProject => Role::Member,
+            Action::CreateMember
+            | Action::DeleteMember { .. }
+            | Action::RestoreMember
+            | Action::DeleteProject
+            | Action::RestoreProject => Role::Board,
+            Action::PurgeMember | Action::OverrideMemberState => Role::Admin,
+        }
+    }
+
+    /// Indica si la acción edita o elimina a un integrante con un rol igual o superior al de
+    /// `member`, incluyéndolo a él mismo.
+    ///
+    /// Un integrante que edita solo su propio perfil no cuenta.
+    fn targets_peer(&self, member: &ClubMemberModel) -> bool {
+        match self {
+            Action::UpdateMember { target, changes } => {
+                !is_profile_edit(target, changes, member) && target.role() >= member.role()
+            }
+            Action::DeleteMember { target } => target.role() >= member.role(),
+            _ => false,
+        }
+    }
+}
+
+/// Indica si `member` edita solo su propio perfil, sin cambiar su estado ni su rol.
+fn is_profile_edit(
+    target: &ClubMemberModel,
+    changes: &MemberDocument,
+    member: &ClubMemberModel,
+) -> bool {
+    target.uuid == member.uuid && changes.state == target.state() && changes.role == target.role()
+}
+
+/// Quien hace la petición: la App y, si existe, el integrante que inició sesión.
+pub struct Actor {
+    /// La App que hace la petición.
+    pub app: AuthenticatedApp,
+    /// El integrante en cuyo nombre actúa la App, si la petición trae una sesión.
+    pub member: Option<ClubMemberModel>,
+}
+
+impl Actor {
+    /// Verifica que quien hace la petición pueda realizar una acción, o genera un error 403.
+    pub fn authorize(&self, action: Action) -> Result<(), ApiError> {
+        let Some(member) = &self.member else {
+            return self.app.require(action.app_scope());
+        };
+
+        self.app.require(action.app_scope())?;
+
+        if member.role() < action.required_role(member) {
+            return Err(ApiError::Forbidden(String::from(
+                "El rol del integrante no permite realizar esta acción",
+            )));
+        }
+
+        if member.role() != Role::Admin && action.targets_peer(member) {
+            return Err(ApiError::Forbidden(String::from(
+                "Solo un administrador puede editar o eliminar a un integrante con un rol igual o superior",
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Quién hace la petición, para registrarlo en los historiales.
+    pub fn author(&self) -> ChangeAuthor<'_> {
+        ChangeAuthor {
+            app: &self.app.app.uuid,
+            member: self.member.as_ref().map(|member| member.uuid.as_str()),
+        }
+    }
+}
+
+impl FromRequest for Actor {
+    type Error = ApiError;
+    type Future = Pin<Box<dyn Future<Output = Result<Self, Self::Error>>>>;
+
+    fn from_request(req: &HttpRequest, payload: &mut Payload) -> Self::Future {
+        let app = AuthenticatedApp::from_request(req, payload);
+        // La sesión solo se verifica si la petición la trae.
+        let session = req
+            .headers()
+            .contains_key(SESSION_HEADER)
+            .then(|| MemberSession::from_request(req, payload));
+
+        Box::pin(async move {
+            let app = app.await?;
+            let member = match session {
+                Some(session) => Some(session.await?.member),
+                None => None,
+            };
+
+            Ok(Actor { app, member })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::SqlitePool;
+
+    use super::{Action, Actor};
+    use crate::{
+        testing,
+        v1::{
+            middleware::api_key::AuthenticatedApp,
+            models::{
+                auth::{AppModel, Scope},
+                club_member::{ClubMemberModel, MemberState, Role},
+            },
+            schemas::club_member::MemberDocument,
+        },
+    };
+
+    /// Crea un integrante con el rol indicado.
+    async fn member(pool: &SqlitePool, role: &str) -> ClubMemberModel {
+        let member_id = testing::create_member(pool, role).await;
+
+        ClubMemberModel::get_one(member_id, pool).await.unwrap()
+    }
+
+    /// Crea una App con los permisos indicados que actúa en nombre de `member`.
+    async fn actor(pool: &SqlitePool, scopes: &[Scope], member: Option<&ClubMemberModel>) -> Actor {
+        let (app_id, _) = testing::create_app(pool, scopes).await;
+        let app = AppModel::find_by_id(&app_id, pool).await.unwrap();
+
+        Actor {
+            app: AuthenticatedApp {
+                app,
+                scopes: scopes.to_vec(),
+            },
+            member: match member {
+                Some(member) => Some(ClubMemberModel::get_one(&member.uuid, pool).await.unwrap()),
+                None => None,
+            },
+        }
+    }
+
+    /// Documento de `target` con el estado y el rol indicados.
+    fn changes(target: &ClubMemberModel, state: MemberState, role: Role) -> MemberDocument {
+        MemberDocument {
+            state,
+            role,
+            ..MemberDocument::from(target)
+        }
+    }
+
+    /// Documento de `target` con otro nombre.
+    fn profile_changes(target: &ClubMemberModel) -> MemberDocument {
+        MemberDocument {
+            name: String::from("Otro nombre"),
+            ..MemberDocument::from(target)
+        }
+    }
+
+    /// Indica si `actor` puede dejar a `target` con los datos de `changes`.
+    fn can_update(actor: &Actor, target: &ClubMemberModel, changes: &MemberDocument) -> bool {
+        actor
+            .authorize(Action::UpdateMember { target, changes })
+            .is_ok()
+    }
+
+    /// Indica si `actor` puede eliminar a `target`.
+    fn can_delete(actor: &Actor, target: &ClubMemberModel) -> bool {
+        actor.authorize(Action::DeleteMember { target }).is_ok()
+    }
+
+    /// Sin sesión, basta con que la App tenga el permiso de la acción.
+    #[actix_web::test]
+    async fn app_without_session_only_needs_the_scope() {
+        let pool = testing::pool().await;
+        let admin = member(&pool, "admin").await;
+        let writer = actor(&pool, &[Scope::MembersWrite], None).await;
+        let reader = actor(&pool, &[Scope::MembersRead], None).await;
+
+        assert!(can_delete(&writer, &admin));
+        assert!(writer.authorize(Action::ReadMembers).is_err());
+        assert!(writer.authorize(Action::PurgeMember).is_err());
+        assert!(reader.authorize(Action::ReadMembers).is_ok());
+        assert!(!can_delete(&reader, &admin));
+    }
+
+    /// Con sesión, la App sigue necesitando el permiso, aunque el integrante sea administrador.
+    #[actix_web::test]
+    async fn session_still_needs_the_app_scope() {
+        let pool = testing::pool().await;
+        let admin = member(&pool, "admin").await;
+        let target = member(&pool, "member").await;
+        let actor = actor(&pool, &[Scope::MembersRead], Some(&admin)).await;
+
+        assert!(actor.authorize(Action::ReadMembers).is_ok());
+        assert!(!can_delete(&actor, &target));
+    }
+
+    /// La directiva puede eliminar a integrantes con un rol menor, pero no a otros de la
+    /// directiva, a un administrador ni a sí misma.
+    #[actix_web::test]
+    async fn board_cannot_delete_peers_or_themselves() {
+        let pool = testing::pool().await;
+        let board = member(&pool, "board").await;
+        let actor = actor(&pool, &[Scope::MembersWrite], Some(&board)).await;
+
+        for (role, allowed) in [
+            ("guest", true),
+            ("member", true),
+            ("board", false),
+            ("admin", false),
+        ] {
+            let target = member(&pool, role).await;
+            assert_eq!(
+                can_delete(&actor, &target),
+                allowed,
+                "board elimina a {role}"
+            );
+        }
+
+        assert!(!can_delete(&actor, &board));
+    }
+
+    /// Los integrantes sin cargo no pueden eliminar a nadie, y un administrador puede eliminar a
+    /// cualquiera.
+    #[actix_web::test]
+    async fn only_board_and_admins_can_delete() {
+        let pool = testing::pool().await;
+        let guest = member(&pool, "guest").await;
+        let plain = member(&pool, "member").await;
+        let admin = member(&pool, "admin").await;
+        let other_admin = member(&pool, "admin").await;
+
+        let as_member = actor(&pool, &[Scope::MembersWrite], Some(&plain)).await;
+        let as_admin = actor(&pool, &[Scope::MembersWrite], Some(&admin)).await;
+
+        assert!(!can_delete(&as_member, &guest));
+        for target in [&guest, &plain, &other_admin, &admin] {
+            assert!(can_delete(&as_admin, target));
+        }
+    }
+
+    /// Cada integrante puede editar su propio perfil, pero no su estado ni su rol, ni los datos de
+    /// otros.
+    #[actix_web::test]
+    async fn members_can_only_edit_their_own_profile() {
+        let pool = testing::pool().await;
+        let plain = member(&pool, "member").await;
+        let other = member(&pool, "guest").await;
+        let actor = actor(&pool, &[Scope::MembersWrite], Some(&plain)).await;
+
+        assert!(can_update(&actor, &plain, &profile_changes(&plain)));
+        assert!(!can_update(
+            &actor,
+            &plain,
+            &changes(&plain, MemberState::Unactive, Role::Member)
+        ));
+        assert!(!can_update(
+            &actor,
+            &plain,
+            &changes(&plain, MemberState::Active, Role::Board)
+        ));
+        assert!(!can_update(&actor, &other, &profile_changes(&other)));
+    }
+
+    /// La directiva puede editar a integrantes con un rol menor, pero no a otros de la directiva
+    /// ni su propio estado, y no puede cambiar roles.
+    #[actix_web::test]
+    async fn board_cannot_edit_peers() {
+        let pool = testing::pool().await;
+        let board = member(&pool, "board").await;
+        let plain = member(&pool, "member").await;
+        let other_board = member(&pool, "board").await;
+        let actor = actor(&pool, &[Scope::MembersWrite], Some(&board)).await;
+
+        assert!(can_update(
+            &actor,
+            &plain,
+            &changes(&plain, MemberState::Unactive, Role::Member)
+        ));
+        assert!(can_update(&actor, &board, &profile_changes(&board)));
+        assert!(!can_update(
+            &actor,
+            &other_board,
+            &changes(&other_board, MemberState::Unactive, Role::Board)
+        ));
+        assert!(!can_update(
+            &actor,
+            &board,
+            &changes(&board, MemberState::Unactive, Role::Board)
+        ));
+        assert!(!can_update(
+            &actor,
+            &plain,
+            &changes(&plain, MemberState::Active, Role::Guest)
+        ));
+    }
+}
@@ -0,0 +1,131 @@
+//! Cliente OAuth2 de GitHub.
+//!
+//! Implementa el flujo "authorization code" de GitHub: el cliente envía al integrante a la URL de
+//! autorización, GitHub lo redirige de vuelta con un código, y ese código se cambia por un token
+//! con el que se pregunta quién es el usuario.
+//!
+//! Las URL de GitHub son configurables (`GITHUB_OAUTH_URL` y `GITHUB_API_URL`), de forma que
+//! durante el desarrollo se pueda usar un servidor falso en vez del GitHub real.
+
+use serde::Deserialize;
+
+/// Configuración y cliente HTTP para hablar con GitHub.
+#[derive(Clone)]
+pub struct GithubProvider {
+    /// ID de la OAuth App registrada en GitHub.
+    client_id: String,
+    /// Secreto de la OAuth App registrada en GitHub.
+    client_secret: String,
+    /// URL base de las páginas de OAuth, normalmente `https://github.com`.
+    oauth_url: String,
+    /// URL base de la API, normalmente `https://api.github.com`.
+    api_url: String,
+    /// Cliente HTTP compartido.
+    http: reqwest::Client,
+}
+
+/// Respuesta de GitHub al cambiar el código por un token.
+#[derive(Deserialize)]
+struct AccessTokenResponse {
+    access_token: Option<String>,
+    error_description: Option<String>,
+}
+
+/// Usuario de GitHub, solo con los campos que usa la API.
+#[derive(Deserialize)]
+pub struct GithubUser {
+    /// ID numérico de la cuenta, que no cambia aunque cambie el nombre de usuario.
+    pub id: i64,
+    /// Nombre de usuario de GitHub.
+    pub login: String,
+}
+
+impl GithubProvider {
+    /// Crea el proveedor a partir de las variables de ambiente.
+    ///
+    /// Si `GITHUB_CLIENT_ID` o `GITHUB_CLIENT_SECRET` no están definidas (o están vacías), el
+    /// inicio de sesión con GitHub queda deshabilitado y se entrega `None`.
+    pub fn from_env() -> Option<GithubProvider> {
+        let client_id = std::env::var("GITHUB_CLIENT_ID")
+            .ok()
+            .filter(|id| !id.is_empty())?;
+        let client_secret = std::env::var("GITHUB_CLIENT_SECRET")
+            .ok()
+            .filter(|secret| !secret.is_empty())?;
+
+        let oauth_url = std::env::var("GITHUB_OAUTH_URL")
+            .unwrap_or(String::from("https://github.com"))
+            .trim_end_matches('/')
+            .to_owned();
+        let api_url = std::env::var("GITHUB_API_URL")
+            .unwrap_or(String::from("https://api.github.com"))
+            .trim_end_matches('/')
+            .to_owned();
+
+        let http = reqwest::Client::builder()
+            .user_agent("exdev-api")
+            .build()
+            .expect("No se pudo crear el cliente HTTP para GitHub");
+
+        Some(GithubProvider {
+            client_id,
+            client_secret,
+            oauth_url,
+            api_url,
+            http,
+        })
+    }
+
+    /// URL a la que se debe enviar al integrante para que autorice a la API.
+    pub fn authorize_url(&self, redirect_uri: &str, state: &str) -> anyhow::Result<String> {
+        let url = reqwest::Url::parse_with_params(
+            &format!("{}/login/oauth/authorize", self.oauth_url),
+            &[
+                ("client_id", self.client_id.as_str()),
+                ("redirect_uri", redirect_uri),
+                ("state", state),
+                ("scope", "read:user"),
+            ],
+        )?;
+
+        Ok(url.to_string())
+    }
+
+    /// Cambia el código entregado por GitHub por el usuario que autorizó a la API.
+    pub async fn fetch_user(&self, code: &str, redirect_uri: &str) -> anyhow::Result<GithubUser> {
+        let token: AccessTokenResponse = self
+            .http
+            .post(format!("{}/login/oauth/access_token", self.oauth_url))
+            .header(reqwest::header::ACCEPT, "application/json")
+            .form(&[
+                ("client_id", self.client_id.as_str()),
+                ("client_secret", self.client_secret.as_str()),
+                ("code", code),
+                ("redirect_uri", redirect_uri),
+            ])
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        let access_token = token.access_token.ok_or_else(|| {
+            anyhow::anyhow!(token
+                .error_description
+                .unwrap_or(String::from("GitHub no entregó un token")))
+        })?;
+
+        let user = self
+            .http
+            .get(format!("{}/user", self.api_url))
+            .header(reqwest::header::ACCEPT, "application/vnd.github+json")
+            .bearer_auth(access_token)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        Ok(user)
+    }
+}
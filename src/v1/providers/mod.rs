@@ -0,0 +1,7 @@
+//! Proveedores externos de la API.
+//!
+//! Acá viven los clientes de los servicios que no son parte de la API, pero de los que esta
+//! depende para algunos procesos, como el inicio de sesión con GitHub o el envío de correos.
+
+pub mod github;
+pub mod mail;
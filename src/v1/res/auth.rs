@@ -1,106 +1,433 @@
 //! Handlers relacionados a la creación de Applicaciones clientes de la API.
 //!
-//! Solo existen dos rutas en este momento: una que crea estas aplicaciones y otra que regenera la
-//! llave en caso de necesitarse.
+//! Además de crear estas aplicaciones, actualizar sus datos y regenerar su llave en caso de
+//! necesitarse, existen rutas para que los administradores puedan listarlas, revisarlas,
+//! revocarlas y restaurarlas.
 
-use actix_web::{post, put, web, HttpResponse, Responder};
-use serde_json::json;
+use actix_web::{
+    get,
+    http::header::{HeaderName, LINK},
+    patch, post, put, web, HttpRequest,
+};
+use utoipa::{openapi::Deprecated, Modify};
 
 use crate::{
     v1::{
-        models::auth::AppModel,
-        schemas::auth::{CreateAppSchema, UpdateAppSchema},
+        middleware::api_key::{missing_key, AppsAdmin, AuthenticatedApp, Scoped},
+        models::auth::{AppModel, Scope},
+        responders::{
+            basic_response::BasicResponse,
+            errors::{ApiError, ErrorResponse},
+        },
+        schemas::{
+            auth::{
+                AppDocument, AppListQuery, AppResponse, CreateAppSchema, NewAppResponse,
+                NewKeyResponse, RegenerateAppSchema, UpdateAppSchema,
+            },
+            patch::merge_patch,
+            validation::Validate,
+        },
     },
     AppState,
 };
 
 /// Crea una nueva aplicación
+///
+/// Requiere el permiso `apps:admin`. La única excepción es la primera App de la API, que, si
+/// `ALLOW_BOOTSTRAP` lo permite, se registra sin llave y recibe todos los permisos para poder
+/// administrar a las demás.
+///
+/// Responde un estado 201 con la llave de la App, que no se vuelve a mostrar, y la URL de la App
+/// en el header `Location`.
+#[utoipa::path(
+    request_body = CreateAppSchema,
+    responses(
+        (status = 201, description = "App registrada. La llave no se vuelve a mostrar", body = BasicResponse<NewAppResponse>,
+            headers(("Location" = String, description = "URL de la nueva App"))),
+        (status = 401, description = "Sin llave, y ya existe una App o no se permite registrar la primera sin llave", body = ErrorResponse),
+        (status = 403, description = "Sin permiso `apps:admin`", body = ErrorResponse),
+        (status = 422, description = "Datos inválidos", body = ErrorResponse),
+    ),
+)]
 #[post("/register")]
-async fn register(body: web::Json<CreateAppSchema>, data: web::Data<AppState>) -> impl Responder {
-    let app_id = uuid::Uuid::new_v4().to_string();
+async fn register(
+    app: Option<AuthenticatedApp>,
+    body: web::Json<CreateAppSchema>,
+    req: HttpRequest,
+    data: web::Data<AppState>,
+) -> Result<BasicResponse<NewAppResponse>, ApiError> {
+    // El middleware solo deja pasar peticiones sin App durante el registro de la primera.
+    let mut scopes = match &app {
+        Some(app) => {
+            app.require(Scope::AppsAdmin)?;
+
+            body.scopes.to_owned().unwrap_or(Scope::DEFAULT.to_vec())
+        }
+        None => Scope::ALL.to_vec(),
+    };
+    scopes.sort_unstable();
+    scopes.dedup();
 
-    let name = body.name.to_owned();
-    let description = body.description.to_owned();
+    body.validate()?;
+
+    let app_id = uuid::Uuid::new_v4().to_string();
     let (pak, hash) = data.pak_controller.clone().generate_key_and_hash();
 
-    let query_result = sqlx::query(
-        r#"
-        INSERT INTO apps(uuid, name, description, api_token)
-        VALUES (?, ?, ?, ?);"#,
-    )
-    .bind(&app_id)
-    .bind(name)
-    .bind(description)
-    .bind(hash)
-    .execute(&data.pool)
-    .await
-    .map_err(|err: sqlx::Error| err.to_string());
-
-    if let Err(err) = query_result {
-        return HttpResponse::InternalServerError().json(
-            // WARN: Esto pasa el mensaje de error directo. Deberia haber un filtro a futuro que lo
-            // saque si no estamos en ambiente de desarrollo.
-            json!({
-                "status": 500,
-                "message": "Ha ocurrido un error interno",
-                "debug" : err
-            }),
-        );
+    if app.is_some() {
+        AppModel::create(&app_id, &body, &hash, &scopes, &data.pool).await?;
+    } else if !AppModel::create_first(&app_id, &body, &hash, &data.pool).await? {
+        // Otra petición registró la primera App mientras tanto.
+        return Err(missing_key());
     }
 
-    HttpResponse::Created().json(json!({
-        "status": 201,
-        "api_key": pak.to_string(),
-        "app_id": app_id,
-    }))
+    let location = req.url_for("app", [&app_id])?;
+
+    Ok(BasicResponse::created(
+        "Se ha registrado la aplicación. Guarda su llave, no se vuelve a mostrar",
+        NewAppResponse {
+            api_key: pak.to_string(),
+            app_id,
+            scopes,
+            expires_at: body.expires_at,
+        },
+        location,
+    ))
 }
 
-/// Regenera la llave de la aplicación.
+/// Actualiza el nombre, la descripción y el límite de peticiones de la aplicación.
+#[utoipa::path(
+    params(("id" = uuid::Uuid, Path, description = "UUID de la App")),
+    request_body = UpdateAppSchema,
+    responses(
+        (status = 200, description = "App actualizada", body = BasicResponse<Object>),
+        (status = 403, description = "Sin permiso `apps:admin`", body = ErrorResponse),
+        (status = 404, description = "La App no existe", body = ErrorResponse),
+        (status = 422, description = "Datos inválidos", body = ErrorResponse),
+    ),
+)]
+#[put("/update/{id}")]
+async fn update(
+    _app: Scoped<AppsAdmin>,
+    body: web::Json<UpdateAppSchema>,
+    data: web::Data<AppState>,
+    path: web::Path<uuid::Uuid>,
+) -> Result<BasicResponse<()>, ApiError> {
+    let app_id = path.into_inner().to_string();
+
+    update_app(&app_id, &body, &data).await?;
+
+    Ok(BasicResponse::new("App actualizada correctamente", None))
+}
+
+/// Actualiza el nombre, la descripción y el límite de peticiones de la aplicación.
+///
+/// Obsoleta: es la ruta con la que antes se editaban las Apps, y se mantiene para los clientes que
+/// aún la usan. Hace lo mismo que `PUT /update/{id}`, y responde además los headers `Deprecation`
+/// y `Link` con la ruta que la reemplaza. Para cambiar la llave se usa `POST /regenerate/{id}`.
+#[utoipa::path(
+    params(("id" = uuid::Uuid, Path, description = "UUID de la App")),
+    request_body = UpdateAppSchema,
+    responses(
+        (status = 200, description = "App actualizada", body = BasicResponse<Object>,
+            headers(
+                ("Deprecation" = String, description = "Siempre `true`"),
+                ("Link" = String, description = "Ruta que reemplaza a esta (`successor-version`)"),
+            )),
+        (status = 403, description = "Sin permiso `apps:admin`", body = ErrorResponse),
+        (status = 404, description = "La App no existe", body = ErrorResponse),
+        (status = 422, description = "Datos inválidos", body = ErrorResponse),
+    ),
+)]
 #[put("/regenerate/{id}")]
-async fn regenerate(
+async fn update_legacy(
+    _app: Scoped<AppsAdmin>,
     body: web::Json<UpdateAppSchema>,
     data: web::Data<AppState>,
     path: web::Path<uuid::Uuid>,
-) -> impl Responder {
+) -> Result<BasicResponse<()>, ApiError> {
     let app_id = path.into_inner().to_string();
 
-    let query_result = sqlx::query_as!(AppModel, "SELECT * FROM apps WHERE uuid = ?", app_id)
-        .fetch_one(&data.pool)
-        .await;
+    update_app(&app_id, &body, &data).await?;
 
-    // Abortamos si no existe la app.
-    let app = match query_result {
-        Ok(app) => app,
-        Err(e) => {
-            return HttpResponse::NotFound().json(json!({
-                "status": 404,
-                "message": "No se encontro la app buscada",
-                "debug": e.to_string()
-            }));
+    Ok(BasicResponse::new("App actualizada correctamente", None)
+        .with_header(HeaderName::from_static("deprecation"), "true")
+        .with_header(
+            LINK,
+            format!("</v1/auth/update/{app_id}>; rel=\"successor-version\""),
+        ))
+}
+
+/// Marca como obsoletas en la documentación las rutas que se mantienen solo por compatibilidad.
+pub struct DeprecatedRoutes;
+
+impl Modify for DeprecatedRoutes {
+    /// Marca `PUT /regenerate/{id}`, reemplazada por `PUT /update/{id}`.
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        if let Some(put) = openapi
+            .paths
+            .paths
+            .get_mut("/regenerate/{id}")
+            .and_then(|item| item.put.as_mut())
+        {
+            put.deprecated = Some(Deprecated::True);
         }
-    };
+    }
+}
+
+/// Valida y guarda los cambios de `PUT /update/{id}` sobre una App.
+async fn update_app(app_id: &str, body: &UpdateAppSchema, data: &AppState) -> Result<(), ApiError> {
+    // Abortamos si no existe la app.
+    let app = AppModel::find_by_id(app_id, &data.pool).await?;
+
+    let changes = AppDocument::from(&app).apply(body);
+    changes.validate()?;
+
+    AppModel::update(app_id, changes, &data.pool).await?;
+
+    Ok(())
+}
+
+/// Actualiza parcialmente una aplicación con un JSON Merge Patch.
+///
+/// A diferencia de `PUT /update/{id}`, un campo en `null` borra el dato; por ejemplo,
+/// `{"rate_limit": null}` hace que la App vuelva a usar el límite por defecto. Devuelve los datos
+/// ya actualizados.
+#[utoipa::path(
+    params(("id" = uuid::Uuid, Path, description = "UUID de la App")),
+    request_body(
+        description = "JSON Merge Patch (RFC 7396) sobre los datos de la App",
+        content = AppDocument,
+        content_type = "application/merge-patch+json",
+    ),
+    responses(
+        (status = 200, description = "App actualizada", body = BasicResponse<AppResponse>),
+        (status = 400, description = "Parche mal formado", body = ErrorResponse),
+        (status = 403, description = "Sin permiso `apps:admin`", body = ErrorResponse),
+        (status = 404, description = "La App no existe", body = ErrorResponse),
+        (status = 415, description = "Tipo de parche no soportado", body = ErrorResponse),
+        (status = 422, description = "El resultado no es válido", body = ErrorResponse),
+    ),
+)]
+#[patch("/apps/{id}")]
+async fn patch_app(
+    _app: Scoped<AppsAdmin>,
+    path: web::Path<uuid::Uuid>,
+    body: web::Json<serde_json::Value>,
+    req: HttpRequest,
+    data: web::Data<AppState>,
+) -> Result<BasicResponse<AppResponse>, actix_web::Error> {
+    let app_id = path.into_inner().to_string();
+
+    let app = AppModel::find_by_id(&app_id, &data.pool)
+        .await
+        .map_err(ApiError::from)?;
+
+    let changes = merge_patch(&AppDocument::from(&app), &req, &body)?;
+    changes.validate()?;
+
+    let app = AppModel::update(&app_id, changes, &data.pool)
+        .await
+        .map_err(ApiError::from)?;
+    let scopes = AppModel::get_scopes(&app_id, &data.pool)
+        .await
+        .map_err(ApiError::from)?;
+
+    Ok(BasicResponse::new(
+        "App actualizada correctamente",
+        Some(AppResponse::new(&app, scopes)),
+    ))
+}
+
+/// Regenera la llave de la aplicación.
+///
+/// La nueva llave se entrega solo en esta respuesta, igual que al registrar la App. La llave
+/// anterior sigue funcionando durante el periodo de gracia (en segundos) indicado en el cuerpo de
+/// la petición, o el configurado en `API_KEY_GRACE_PERIOD` si no se indica ninguno. Esto permite
+/// cambiar la llave de los clientes ya desplegados sin que dejen de funcionar.
+///
+/// Una App siempre puede regenerar su propia llave. Para regenerar la de otra App se necesita el
+/// permiso `apps:admin`.
+#[utoipa::path(
+    params(("id" = uuid::Uuid, Path, description = "UUID de la App")),
+    request_body(content = Option<RegenerateAppSchema>),
+    responses(
+        (status = 200, description = "Llave regenerada. La llave no se vuelve a mostrar", body = BasicResponse<NewKeyResponse>),
+        (status = 403, description = "Sin permiso `apps:admin` para regenerar la llave de otra App", body = ErrorResponse),
+        (status = 404, description = "La App no existe", body = ErrorResponse),
+        (status = 422, description = "Datos inválidos", body = ErrorResponse),
+    ),
+)]
+#[post("/regenerate/{id}")]
+async fn regenerate(
+    caller: AuthenticatedApp,
+    body: Option<web::Json<RegenerateAppSchema>>,
+    data: web::Data<AppState>,
+    path: web::Path<uuid::Uuid>,
+) -> Result<BasicResponse<NewKeyResponse>, ApiError> {
+    let app_id = path.into_inner().to_string();
+
+    if caller.app.uuid != app_id {
+        caller.require(Scope::AppsAdmin)?;
+    }
 
-    let name = body.name.to_owned().unwrap_or(app.name);
-    let desc = body.description.to_owned().or(app.description);
-
-    let query_result = sqlx::query!(
-        r#"UPDATE apps SET name = ?, description = ? WHERE uuid = ?"#,
-        name,
-        desc,
-        app_id
-    )
-    .execute(&data.pool)
-    .await;
-
-    match query_result {
-        Ok(_) => HttpResponse::Ok().json(json!({
-            "status": 200,
-            "message": "App actualizada correctamente"
-        })),
-        Err(e) => HttpResponse::InternalServerError().json(json!({
-        "status": 500,
-         "message": "No se pudo actualizar el registro",
-         "debug": e.to_string()
-        })),
+    if let Some(body) = &body {
+        body.validate()?;
     }
+
+    let app = AppModel::find_by_id(&app_id, &data.pool).await?;
+
+    let grace_period = body
+        .and_then(|body| body.grace_period)
+        .unwrap_or(data.api_key_grace_period)
+        .max(0);
+    let grace_until = (grace_period > 0)
+        .then(|| chrono::Utc::now().naive_utc() + chrono::Duration::seconds(grace_period));
+
+    let (pak, hash) = data.pak_controller.clone().generate_key_and_hash();
+
+    AppModel::rotate_key(&app, &hash, grace_until, &data.pool).await?;
+
+    Ok(BasicResponse::new(
+        "Se ha regenerado la llave. Guárdala, no se vuelve a mostrar",
+        Some(NewKeyResponse {
+            api_key: pak.to_string(),
+            app_id,
+            previous_key_expires_at: grace_until,
+        }),
+    ))
+}
+
+/// Obtiene una lista de las aplicaciones.
+///
+/// Por defecto no se incluyen las aplicaciones revocadas; para verlas se debe agregar
+/// `?revoked=true` a la URL.
+///
+/// Para encontrar integraciones abandonadas, `?unused_for_days=N` lista solo las aplicaciones que
+/// no han usado su llave en los últimos N días.
+#[utoipa::path(
+    params(AppListQuery),
+    responses(
+        (status = 200, description = "Lista de Apps", body = BasicResponse<Vec<AppResponse>>),
+        (status = 403, description = "Sin permiso `apps:admin`", body = ErrorResponse),
+        (status = 422, description = "Parámetros inválidos", body = ErrorResponse),
+    ),
+)]
+#[get("/apps")]
+async fn get_apps(
+    _app: Scoped<AppsAdmin>,
+    query: web::Query<AppListQuery>,
+    data: web::Data<AppState>,
+) -> Result<BasicResponse<Vec<AppResponse>>, actix_web::Error> {
+    query.validate()?;
+
+    let unused_since = query
+        .unused_for_days
+        .map(|days| chrono::Utc::now().naive_utc() - chrono::Duration::days(days));
+
+    let apps = AppModel::get_all(query.revoked, unused_since, &data.pool)
+        .await
+        .map_err(ApiError::from)?;
+    let mut scopes = AppModel::get_all_scopes(&data.pool)
+        .await
+        .map_err(ApiError::from)?;
+
+    let apps = apps
+        .iter()
+        .map(|app| AppResponse::new(app, scopes.remove(&app.uuid).unwrap_or_default()))
+        .collect();
+
+    Ok(BasicResponse::new("Lista de aplicaciones", Some(apps)))
+}
+
+/// Obtiene una única aplicación según su UUID, incluso si fue revocada.
+#[utoipa::path(
+    params(("id" = uuid::Uuid, Path, description = "UUID de la App")),
+    responses(
+        (status = 200, description = "Datos de la App", body = BasicResponse<AppResponse>),
+        (status = 403, description = "Sin permiso `apps:admin`", body = ErrorResponse),
+        (status = 404, description = "La App no existe", body = ErrorResponse),
+    ),
+)]
+#[get("/apps/{id}", name = "app")]
+async fn get_single_app(
+    _app: Scoped<AppsAdmin>,
+    path: web::Path<uuid::Uuid>,
+    data: web::Data<AppState>,
+) -> Result<BasicResponse<AppResponse>, ApiError> {
+    let app_id = path.into_inner().to_string();
+
+    let app = AppModel::find_by_id(&app_id, &data.pool).await?;
+    let scopes = AppModel::get_scopes(&app_id, &data.pool).await?;
+
+    Ok(BasicResponse::new(
+        "Se ha encontrado la siguiente aplicación",
+        Some(AppResponse::new(&app, scopes)),
+    ))
+}
+
+/// Revoca una aplicación.
+///
+/// Desde este momento su llave deja de ser aceptada, pero la aplicación no se elimina de la base de
+/// datos y puede ser restaurada.
+///
+/// No se puede revocar la última App activa con el permiso `apps:admin`, incluso si es la misma
+/// App que hace la petición, para que siempre quede alguna que pueda administrar a las demás.
+#[utoipa::path(
+    params(("id" = uuid::Uuid, Path, description = "UUID de la App")),
+    responses(
+        (status = 200, description = "App revocada", body = BasicResponse<AppResponse>),
+        (status = 403, description = "Sin permiso `apps:admin`", body = ErrorResponse),
+        (status = 404, description = "La App no existe", body = ErrorResponse),
+        (status = 409, description = "Es la última App activa con `apps:admin`", body = ErrorResponse),
+    ),
+)]
+#[post("/revoke/{id}")]
+async fn revoke(
+    _app: Scoped<AppsAdmin>,
+    path: web::Path<uuid::Uuid>,
+    data: web::Data<AppState>,
+) -> Result<BasicResponse<AppResponse>, ApiError> {
+    let app_id = path.into_inner().to_string();
+
+    let app = AppModel::find_by_id(&app_id, &data.pool).await?;
+
+    if app.deleted_at.is_none() && !AppModel::revoke(&app_id, &data.pool).await? {
+        return Err(ApiError::Conflict(String::from(
+            "No se puede revocar la última App activa con el permiso apps:admin",
+        )));
+    }
+
+    let app = AppModel::find_by_id(&app_id, &data.pool).await?;
+    let scopes = AppModel::get_scopes(&app_id, &data.pool).await?;
+
+    Ok(BasicResponse::new(
+        "Se ha revocado la aplicación",
+        Some(AppResponse::new(&app, scopes)),
+    ))
+}
+
+/// Restaura una aplicación revocada, de forma que su llave vuelva a ser aceptada.
+#[utoipa::path(
+    params(("id" = uuid::Uuid, Path, description = "UUID de la App")),
+    responses(
+        (status = 200, description = "App restaurada", body = BasicResponse<AppResponse>),
+        (status = 403, description = "Sin permiso `apps:admin`", body = ErrorResponse),
+        (status = 404, description = "La App no existe", body = ErrorResponse),
+    ),
+)]
+#[post("/restore/{id}")]
+async fn restore(
+    _app: Scoped<AppsAdmin>,
+    path: web::Path<uuid::Uuid>,
+    data: web::Data<AppState>,
+) -> Result<BasicResponse<AppResponse>, ApiError> {
+    let app_id = path.into_inner().to_string();
+
+    let app = AppModel::restore(&app_id, &data.pool).await?;
+    let scopes = AppModel::get_scopes(&app_id, &data.pool).await?;
+
+    Ok(BasicResponse::new(
+        "Se ha restaurado la aplicación",
+        Some(AppResponse::new(&app, scopes)),
+    ))
 }
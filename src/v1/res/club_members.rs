@@ -11,53 +11,97 @@
 //! Actualmente estas funciones son llevadas de forma literal, pero quizás un poco de discusión
 //! deba llevarse a cabo a futuro sobre estas.
 
-use actix_web::{delete, get, post, put, web};
+use actix_web::{delete, get, patch, post, put, web, HttpRequest};
 
 use crate::{
-    v1::{models::club_member::ClubMemberModel, responders::errors::DBError},
+    v1::policy::{Action, Actor},
+    v1::{
+        models::{
+            club_member::{ClubMemberModel, MemberState},
+            member_state_history::MemberStateChangeModel,
+        },
+        responders::errors::{ApiError, ErrorResponse},
+    },
     v1::{
         responders::basic_response::BasicResponse,
-        schemas::club_member::{ClubMemberResponse, CreateMemberSchema, UpdateMemberSchema},
+        schemas::club_member::{
+            ClubMemberResponse, CreateMemberSchema, MemberDocument, MemberListQuery,
+            UpdateMemberSchema,
+        },
+        schemas::patch::apply_patch,
+        schemas::validation::Validate,
     },
     AppState,
 };
 
-/// Obtiene una lista de todos los miembros
+/// Obtiene una lista de los miembros
 ///
-/// Actualmente esta función no hace ningún tipo de filtrado, y solo falla según falle la base de
-/// datos por alguna razón.
+/// La lista se puede filtrar, ordenar y paginar según los parámetros de `MemberListQuery`, por
+/// ejemplo `GET /v1/members?state=Active&search=juan&sort=created_at&order=desc&limit=20`.
 ///
 /// Los pasos que sigue son los siguientes:
-/// 1. Obtiene un vector con los modelos de todos los integrantes.
+/// 1. Obtiene una página con los modelos de los integrantes que cumplen los filtros.
 /// 2. Convierte ese vector de modelos a un vector de Respuestas (ClubMemberResponse)
-/// 3. Genera una respuesta básica con estos datos.
+/// 3. Genera una respuesta básica con estos datos y los de la paginación.
+#[utoipa::path(
+    params(MemberListQuery),
+    responses(
+        (status = 200, description = "Página de integrantes", body = BasicResponse<Vec<ClubMemberResponse>>),
+        (status = 422, description = "Parámetros inválidos", body = ErrorResponse),
+    ),
+)]
 #[get("")]
 async fn get_club_members(
+    actor: Actor,
+    query: web::Query<MemberListQuery>,
+    req: HttpRequest,
     data: web::Data<AppState>,
-) -> Result<BasicResponse<Vec<ClubMemberResponse>>, DBError> {
-    let members = ClubMemberModel::get_all(&data.pool).await?;
+) -> Result<BasicResponse<Vec<ClubMemberResponse>>, actix_web::Error> {
+    actor.authorize(Action::ReadMembers)?;
+
+    query.validate()?;
+    let page = query.page()?;
+    let result = ClubMemberModel::search(&query, &page, &data.pool)
+        .await
+        .map_err(ApiError::from)?;
 
-    let members = ClubMemberResponse::from_vector(&members);
+    let pagination = page.pagination(
+        &result,
+        |member| (query.sort.value_of(member), member.uuid.to_owned()),
+        &req,
+    );
+    let members = ClubMemberResponse::from_vector(&result.items);
 
-    Ok(BasicResponse::new("Lista de miembros", Some(members)))
+    Ok(BasicResponse::new("Lista de miembros", Some(members)).with_pagination(pagination))
 }
 
 /// Obtiene un único miembro según su UUID
 ///
-/// En caso de no encontrar el miembro, genera un DBError con código 404. Lo que esperarías, vaya.
+/// En caso de no encontrar el miembro, genera un ApiError con código 404. Lo que esperarías, vaya.
 /// Si lo encuentra, envía una respuesta básica con los datos de ese miembro.
 ///
 /// Lo único entretenido que está pasando es que la transformación hacia un ClubMemberResponse se
 /// hace hinteando el tipo de la variable y utilizando la función into().
-#[get("/{id}")]
+#[utoipa::path(
+    params(("id" = uuid::Uuid, Path, description = "UUID del integrante")),
+    responses(
+        (status = 200, description = "Datos del integrante", body = BasicResponse<ClubMemberResponse>),
+        (status = 404, description = "El integrante no existe", body = ErrorResponse),
+    ),
+)]
+#[get("/{id}", name = "member")]
 async fn get_single_member(
+    actor: Actor,
     path: web::Path<uuid::Uuid>,
     data: web::Data<AppState>,
-) -> Result<BasicResponse<ClubMemberResponse>, DBError> {
+) -> Result<BasicResponse<ClubMemberResponse>, actix_web::Error> {
+    actor.authorize(Action::ReadMembers)?;
+
     let member_id = path.into_inner().to_string();
 
     let member: ClubMemberResponse = ClubMemberModel::get_one(&member_id, &data.pool)
-        .await?
+        .await
+        .map_err(ApiError::from)?
         .into();
 
     Ok(BasicResponse::new(
@@ -71,18 +115,48 @@ async fn get_single_member(
 /// El cuerpo de esta petición requiere un JSON con un campo "name" requerido. Puedes ver más
 /// detalles al respecto revisando CreateMemberSchema para entender qué valores son requeridos,
 /// posibles y demás.
+///
+/// Responde un estado 201 con los datos del nuevo integrante, y su URL en el header `Location`.
+///
+/// En nombre de un integrante, solo la directiva puede agregar a otros.
+#[utoipa::path(
+    request_body = CreateMemberSchema,
+    responses(
+        (status = 201, description = "Integrante creado", body = BasicResponse<ClubMemberResponse>,
+            headers(("Location" = String, description = "URL del nuevo integrante"))),
+        (status = 403, description = "Sin permiso para agregar integrantes", body = ErrorResponse),
+        (status = 409, description = "Ya existe un integrante con el mismo email o GitHub", body = ErrorResponse),
+        (status = 422, description = "Datos inválidos", body = ErrorResponse),
+    ),
+)]
 #[post("/create")]
 async fn add_club_member(
+    actor: Actor,
     body: web::Json<CreateMemberSchema>,
+    req: HttpRequest,
     data: web::Data<AppState>,
-) -> Result<BasicResponse<()>, DBError> {
+) -> Result<BasicResponse<ClubMemberResponse>, actix_web::Error> {
+    actor.authorize(Action::CreateMember)?;
+    body.validate()?;
+
     let member_id = uuid::Uuid::new_v4().to_string();
 
-    ClubMemberModel::create(&member_id, body.into_inner(), &data.pool).await?;
+    ClubMemberModel::create(&member_id, body.into_inner(), &data.pool)
+        .await
+        .map_err(ApiError::from)?;
 
-    Ok(BasicResponse::new(
+    let member: ClubMemberResponse = ClubMemberModel::get_one(&member_id, &data.pool)
+        .await
+        .map_err(ApiError::from)?
+        .into();
+    let location = req
+        .url_for("member", [&member_id])
+        .map_err(ApiError::from)?;
+
+    Ok(BasicResponse::created(
         "Se ha agregado exitosamente un nuevo miembro",
-        None,
+        member,
+        location,
     ))
 }
 
@@ -92,17 +166,54 @@ async fn add_club_member(
 /// 1. Obtener los datos del integrante a partir de su UUID.
 /// 2. Intentar actualizar estos datos utilizando el cuerpo de la petición.
 /// 3. Devuelve una respuesta básica.
+///
+/// En nombre de un integrante, cada uno puede editar su propio perfil, pero solo la directiva
+/// puede editar a otros (incluyendo su estado) y solo un administrador puede cambiar roles o
+/// editar a alguien con un rol igual o superior al suyo.
+///
+/// Los cambios de estado que `MemberState` no permite generan un error 409, a menos que los
+/// haga un administrador. Todo cambio de estado queda en el historial del integrante.
+#[utoipa::path(
+    params(("id" = uuid::Uuid, Path, description = "UUID del integrante")),
+    request_body = UpdateMemberSchema,
+    responses(
+        (status = 200, description = "Integrante actualizado", body = BasicResponse<Object>),
+        (status = 403, description = "Sin permiso para editar al integrante", body = ErrorResponse),
+        (status = 404, description = "El integrante no existe", body = ErrorResponse),
+        (status = 409, description = "Cambio de estado no permitido, el integrante cambió mientras tanto, o ya existe un integrante con el mismo email o GitHub", body = ErrorResponse),
+        (status = 422, description = "Datos inválidos", body = ErrorResponse),
+    ),
+)]
 #[put("/update/{id}")]
 async fn update_club_member(
+    actor: Actor,
     path: web::Path<uuid::Uuid>,
     body: web::Json<UpdateMemberSchema>,
     data: web::Data<AppState>,
-) -> Result<BasicResponse<()>, DBError> {
+) -> Result<BasicResponse<()>, actix_web::Error> {
+    actor.authorize(Action::ReadMembers)?;
+
     let member_id = path.into_inner().to_string();
 
-    let target_member = ClubMemberModel::get_one(&member_id, &data.pool).await?;
+    let target_member = ClubMemberModel::get_one(&member_id, &data.pool)
+        .await
+        .map_err(ApiError::from)?;
+
+    let changes = MemberDocument::from(&target_member).apply(body.into_inner());
 
-    ClubMemberModel::update(target_member, body.into_inner(), &data.pool).await?;
+    actor.authorize(Action::UpdateMember {
+        target: &target_member,
+        changes: &changes,
+    })?;
+    changes.validate()?;
+    authorize_state_change(&actor, &target_member, changes.state)?;
+
+    if !ClubMemberModel::update(target_member, changes, &actor.author(), &data.pool)
+        .await
+        .map_err(ApiError::from)?
+    {
+        return Err(member_changed().into());
+    }
 
     // NOTE: Debería esto devolver los datos nuevos del integrante?
     Ok(BasicResponse::new(
@@ -111,28 +222,259 @@ async fn update_club_member(
     ))
 }
 
+/// Actualiza parcialmente a un integrante con un JSON Merge Patch o un JSON Patch.
+///
+/// A diferencia de `PUT /update/{id}`, un campo en `null` (o una operación `remove`) borra el
+/// dato, por ejemplo `{"birthday": null}`. Los permisos y los cambios de estado siguen las mismas reglas que en
+/// `PUT`. Devuelve los datos ya actualizados.
+#[utoipa::path(
+    params(("id" = uuid::Uuid, Path, description = "UUID del integrante")),
+    request_body(
+        description = "JSON Merge Patch (RFC 7396) o JSON Patch (RFC 6902) sobre los datos del integrante",
+        content(
+            (MemberDocument = "application/merge-patch+json"),
+            (Vec<Object> = "application/json-patch+json"),
+        ),
+    ),
+    responses(
+        (status = 200, description = "Integrante actualizado", body = BasicResponse<ClubMemberResponse>),
+        (status = 400, description = "Parche mal formado", body = ErrorResponse),
+        (status = 403, description = "Sin permiso para editar al integrante", body = ErrorResponse),
+        (status = 404, description = "El integrante no existe", body = ErrorResponse),
+        (status = 409, description = "Cambio de estado no permitido, `test` fallido, el integrante cambió mientras tanto, o ya existe un integrante con el mismo email o GitHub", body = ErrorResponse),
+        (status = 415, description = "Tipo de parche no soportado", body = ErrorResponse),
+        (status = 422, description = "El resultado no es válido", body = ErrorResponse),
+    ),
+)]
+#[patch("/{id}")]
+async fn patch_club_member(
+    actor: Actor,
+    path: web::Path<uuid::Uuid>,
+    body: web::Json<serde_json::Value>,
+    req: HttpRequest,
+    data: web::Data<AppState>,
+) -> Result<BasicResponse<ClubMemberResponse>, actix_web::Error> {
+    actor.authorize(Action::ReadMembers)?;
+
+    let member_id = path.into_inner().to_string();
+
+    let target_member = ClubMemberModel::get_one(&member_id, &data.pool)
+        .await
+        .map_err(ApiError::from)?;
+    let current = MemberDocument::from(&target_member);
+
+    // Primero se revisa que se pueda editar al integrante, y después el cambio que pide el
+    // parche, como el rol o el estado.
+    actor.authorize(Action::UpdateMember {
+        target: &target_member,
+        changes: &current,
+    })?;
+
+    let changes = apply_patch(&current, &req, &body)?;
+
+    actor.authorize(Action::UpdateMember {
+        target: &target_member,
+        changes: &changes,
+    })?;
+    changes.validate()?;
+    authorize_state_change(&actor, &target_member, changes.state)?;
+
+    if !ClubMemberModel::update(target_member, changes, &actor.author(), &data.pool)
+        .await
+        .map_err(ApiError::from)?
+    {
+        return Err(member_changed().into());
+    }
+
+    let member: ClubMemberResponse = ClubMemberModel::get_one(&member_id, &data.pool)
+        .await
+        .map_err(ApiError::from)?
+        .into();
+
+    Ok(BasicResponse::new(
+        "Se ha actualizado la informacion del miembro.",
+        Some(member),
+    ))
+}
+
+/// Error al actualizar a un integrante que cambió de estado o de rol mientras tanto.
+pub fn member_changed() -> ApiError {
+    ApiError::Conflict(String::from(
+        "El integrante cambió mientras se actualizaba. Vuelve a cargarlo e intenta de nuevo",
+    ))
+}
+
+/// Verifica que un integrante pueda pasar a `new_state`, o genera un error 409.
+///
+/// Los cambios que `MemberState` no permite solo los puede forzar un administrador.
+fn authorize_state_change(
+    actor: &Actor,
+    target: &ClubMemberModel,
+    new_state: MemberState,
+) -> Result<(), ApiError> {
+    let current_state = target.state();
+
+    if current_state.can_transition_to(new_state)
+        || actor.authorize(Action::OverrideMemberState).is_ok()
+    {
+        return Ok(());
+    }
+
+    Err(ApiError::Conflict(format!(
+        "No se puede pasar del estado {} a {}",
+        String::from(current_state),
+        String::from(new_state)
+    )))
+}
+
+/// Obtiene el historial de cambios de estado de un integrante, del más antiguo al más reciente.
+#[utoipa::path(
+    params(("id" = uuid::Uuid, Path, description = "UUID del integrante")),
+    responses(
+        (status = 200, description = "Historial de estados", body = BasicResponse<Vec<MemberStateChangeModel>>),
+        (status = 404, description = "El integrante no existe", body = ErrorResponse),
+    ),
+)]
+#[get("/{id}/history")]
+async fn get_member_history(
+    actor: Actor,
+    path: web::Path<uuid::Uuid>,
+    data: web::Data<AppState>,
+) -> Result<BasicResponse<Vec<MemberStateChangeModel>>, actix_web::Error> {
+    actor.authorize(Action::ReadMembers)?;
+
+    let member_id = path.into_inner().to_string();
+
+    let member = ClubMemberModel::get_one(&member_id, &data.pool)
+        .await
+        .map_err(ApiError::from)?;
+
+    let history = MemberStateChangeModel::get_by_member(&member.uuid, &data.pool)
+        .await
+        .map_err(ApiError::from)?;
+
+    Ok(BasicResponse::new(
+        "Historial de estados del integrante",
+        Some(history),
+    ))
+}
+
 /// Elimina a un integrante de la bdd.
 ///
-/// Actualmente esta eliminación es total. Quita la fila de la Base de datos. Quizás deberíamos
-/// considerar hacer un 'soft delete' de alguna forma en vez de eliminarlo directamente.
+/// Esta eliminación es un 'soft delete': el integrante deja de aparecer en la API, pero se
+/// mantiene su historial y se puede restaurar con `POST /{id}/restore`.
 ///
 /// En cualquier caso, los pasos son los de siempre:
 /// 1. Obtener el integrante a eliminar.
 /// 2. Intentar eliminarlo.
-/// 3. Devolver una respuesta básica.
+/// 3. Devolver una respuesta vacía, con estado 204.
+///
+/// En nombre de un integrante, solo la directiva puede eliminar integrantes, y solo un
+/// administrador puede eliminar a alguien con un rol igual o superior al suyo, o a sí mismo.
+#[utoipa::path(
+    params(("id" = uuid::Uuid, Path, description = "UUID del integrante")),
+    responses(
+        (status = 204, description = "Integrante eliminado"),
+        (status = 403, description = "Sin permiso para eliminar a este integrante", body = ErrorResponse),
+        (status = 404, description = "El integrante no existe", body = ErrorResponse),
+    ),
+)]
 #[delete("/delete/{id}")]
 pub async fn delete_member(
+    actor: Actor,
+    path: web::Path<uuid::Uuid>,
+    data: web::Data<AppState>,
+) -> Result<BasicResponse<()>, actix_web::Error> {
+    actor.authorize(Action::ReadMembers)?;
+
+    let member_id = path.into_inner().to_string();
+
+    let member = ClubMemberModel::get_one(&member_id, &data.pool)
+        .await
+        .map_err(ApiError::from)?;
+
+    // El rol del integrante a eliminar decide si quien hace la petición puede hacerlo.
+    actor.authorize(Action::DeleteMember { target: &member })?;
+
+    ClubMemberModel::delete(&member, &data.pool)
+        .await
+        .map_err(ApiError::from)?;
+
+    Ok(BasicResponse::no_content())
+}
+
+/// Restaura a un integrante eliminado.
+///
+/// Devuelve los datos del integrante restaurado. En nombre de un integrante, solo la directiva
+/// puede restaurar integrantes.
+#[utoipa::path(
+    params(("id" = uuid::Uuid, Path, description = "UUID del integrante eliminado")),
+    responses(
+        (status = 200, description = "Integrante restaurado", body = BasicResponse<ClubMemberResponse>),
+        (status = 403, description = "Sin permiso para restaurar integrantes", body = ErrorResponse),
+        (status = 404, description = "No existe un integrante eliminado con esa UUID", body = ErrorResponse),
+        (status = 409, description = "Otro integrante ya usa su email o GitHub", body = ErrorResponse),
+    ),
+)]
+#[post("/{id}/restore")]
+pub async fn restore_member(
+    actor: Actor,
     path: web::Path<uuid::Uuid>,
     data: web::Data<AppState>,
-) -> Result<BasicResponse<()>, DBError> {
+) -> Result<BasicResponse<ClubMemberResponse>, actix_web::Error> {
+    actor.authorize(Action::RestoreMember)?;
+
     let member_id = path.into_inner().to_string();
 
-    let member = ClubMemberModel::get_one(&member_id, &data.pool).await?;
+    let member = ClubMemberModel::get_deleted(&member_id, &data.pool)
+        .await
+        .map_err(ApiError::from)?;
 
-    ClubMemberModel::delete(&member, &data.pool).await?;
+    ClubMemberModel::restore(&member, &data.pool)
+        .await
+        .map_err(ApiError::from)?;
+
+    let member: ClubMemberResponse = ClubMemberModel::get_one(&member_id, &data.pool)
+        .await
+        .map_err(ApiError::from)?
+        .into();
 
     Ok(BasicResponse::new(
-        "Se ha eliminado el registro con exito.",
-        None,
+        "Se ha restaurado al integrante.",
+        Some(member),
     ))
 }
+
+/// Elimina definitivamente a un integrante de la bdd, esté eliminado o no.
+///
+/// Esta eliminación quita la fila de la base de datos junto con su participación en los
+/// proyectos, y no se puede deshacer. Solo un administrador (o una App con el permiso
+/// `apps:admin`) puede hacerla. Responde un estado 204, sin cuerpo.
+#[utoipa::path(
+    params(("id" = uuid::Uuid, Path, description = "UUID del integrante")),
+    responses(
+        (status = 204, description = "Integrante eliminado definitivamente"),
+        (status = 403, description = "Sin permiso de administrador", body = ErrorResponse),
+        (status = 404, description = "El integrante no existe", body = ErrorResponse),
+    ),
+)]
+#[delete("/{id}/purge")]
+pub async fn purge_member(
+    actor: Actor,
+    path: web::Path<uuid::Uuid>,
+    data: web::Data<AppState>,
+) -> Result<BasicResponse<()>, actix_web::Error> {
+    actor.authorize(Action::PurgeMember)?;
+
+    let member_id = path.into_inner().to_string();
+
+    let result = ClubMemberModel::purge(&member_id, &data.pool)
+        .await
+        .map_err(ApiError::from)?;
+
+    if result.rows_affected() == 0 {
+        return Err(ApiError::from(sqlx::Error::RowNotFound).into());
+    }
+
+    Ok(BasicResponse::no_content())
+}
@@ -0,0 +1,491 @@
+//! Handlers del integrante que inició sesión.
+//!
+//! Estas rutas actúan siempre sobre el integrante dueño de la sesión (header `X-Session-Token`),
+//! de forma que cada uno pueda mantener sus datos al día sin pasar por la directiva. La App que
+//! hace la petición necesita `members:read` para ver los datos y `members:write` para cambiarlos,
+//! incluyendo la verificación en dos pasos.
+//!
+//! Acá también se maneja la verificación en dos pasos, que pueden activar los integrantes de la
+//! directiva:
+//! 1. `POST /v1/me/2fa/enrol` entrega un secreto y su URI `otpauth://`.
+//! 2. `POST /v1/me/2fa/verify` con un código de la aplicación activa la verificación y entrega
+//!    los códigos de recuperación.
+//! 3. `POST /v1/me/2fa/disable` con un código (o uno de recuperación) la desactiva.
+
+use actix_web::{get, patch, post, put, web, HttpRequest};
+
+use crate::{
+    v1::{
+        middleware::{
+            api_key::{MembersRead, MembersWrite, ProjectsRead, Scoped},
+            member_session::MemberSession,
+        },
+        models::{
+            club_member::{ClubMemberModel, Role},
+            project::ProjectModel,
+            totp::TotpModel,
+            ChangeAuthor,
+        },
+        responders::{
+            basic_response::BasicResponse,
+            errors::{ApiError, ErrorResponse},
+        },
+        schemas::{
+            club_member::{ClubMemberResponse, MemberDocument, ProfileDocument, UpdateMeSchema},
+            patch::apply_patch,
+            session::{RecoveryCodesResponse, TotpCodeSchema, TotpEnrolmentResponse},
+            validation::Validate,
+        },
+    },
+    AppState,
+};
+
+use super::{
+    club_members::member_changed,
+    sessions::{invalid_code, totp_locked},
+};
+
+/// Error al activar la verificación en dos pasos cuando ya está activada.
+fn totp_already_enabled() -> ApiError {
+    ApiError::Conflict(String::from(
+        "La verificación en dos pasos ya está activada",
+    ))
+}
+
+/// Error al confirmar o desactivar la verificación en dos pasos cuando no está activada.
+fn totp_not_enrolled() -> ApiError {
+    ApiError::Conflict(String::from(
+        "La verificación en dos pasos no está activada",
+    ))
+}
+
+/// Error de un código incorrecto al activar o desactivar la verificación en dos pasos.
+///
+/// Si con este código el integrante agotó sus intentos, queda bloqueado.
+async fn rejected_code(member_id: &str, pool: &sqlx::SqlitePool) -> ApiError {
+    match TotpModel::lock_if_exhausted(member_id, pool).await {
+        Ok(true) => totp_locked(),
+        Ok(false) => invalid_code(),
+        Err(err) => err.into(),
+    }
+}
+
+/// Obtiene los datos del integrante que inició sesión.
+#[utoipa::path(
+    security(("api_key" = [], "session" = [])),
+    responses(
+        (status = 200, description = "Datos del integrante", body = BasicResponse<ClubMemberResponse>),
+        (status = 401, description = "Sin una sesión válida", body = ErrorResponse),
+    ),
+)]
+#[get("")]
+async fn get_me(
+    _app: Scoped<MembersRead>,
+    session: MemberSession,
+) -> BasicResponse<ClubMemberResponse> {
+    BasicResponse::new("Tus datos como integrante", Some(session.member.into()))
+}
+
+/// Actualiza los datos personales del integrante que inició sesión.
+///
+/// Solo se pueden cambiar el nombre, cumpleaños, email y Github; el estado y el rol siguen
+/// pasando por la directiva. Devuelve los datos ya actualizados.
+#[utoipa::path(
+    security(("api_key" = [], "session" = [])),
+    request_body = UpdateMeSchema,
+    responses(
+        (status = 200, description = "Datos actualizados", body = BasicResponse<ClubMemberResponse>),
+        (status = 401, description = "Sin una sesión válida", body = ErrorResponse),
+        (status = 409, description = "Ya existe un integrante con el mismo email o GitHub", body = ErrorResponse),
+        (status = 422, description = "Datos inválidos", body = ErrorResponse),
+    ),
+)]
+#[put("")]
+async fn update_me(
+    app: Scoped<MembersWrite>,
+    session: MemberSession,
+    body: web::Json<UpdateMeSchema>,
+    data: web::Data<AppState>,
+) -> Result<BasicResponse<ClubMemberResponse>, actix_web::Error> {
+    let changes = MemberDocument::from(&session.member).apply(body.into_inner().into());
+
+    save_profile(&app, session.member, changes, &data).await
+}
+
+/// Actualiza parcialmente los datos personales del integrante con un JSON Merge Patch o un JSON
+/// Patch.
+///
+/// Un campo en `null` borra el dato, así que un integrante puede retirar su cumpleaños, email o
+/// Github con, por ejemplo, `{"birthday": null}`. Al igual que en `PUT`, no se puede cambiar el
+/// estado ni el rol. Devuelve los datos ya actualizados.
+#[utoipa::path(
+    security(("api_key" = [], "session" = [])),
+    request_body(
+        description = "JSON Merge Patch (RFC 7396) o JSON Patch (RFC 6902) sobre el perfil",
+        content(
+            (ProfileDocument = "application/merge-patch+json"),
+            (Vec<Object> = "application/json-patch+json"),
+        ),
+    ),
+    responses(
+        (status = 200, description = "Datos actualizados", body = BasicResponse<ClubMemberResponse>),
+        (status = 400, description = "Parche mal formado", body = ErrorResponse),
+        (status = 401, description = "Sin una sesión válida", body = ErrorResponse),
+        (status = 409, description = "Un `test` del parche no se cumplió, o ya existe un integrante con el mismo email o GitHub", body = ErrorResponse),
+        (status = 415, description = "Tipo de parche no soportado", body = ErrorResponse),
+        (status = 422, description = "El resultado no es válido", body = ErrorResponse),
+    ),
+)]
+#[patch("")]
+async fn patch_me(
+    app: Scoped<MembersWrite>,
+    session: MemberSession,
+    body: web::Json<serde_json::Value>,
+    req: HttpRequest,
+    data: web::Data<AppState>,
+) -> Result<BasicResponse<ClubMemberResponse>, actix_web::Error> {
+    let profile = apply_patch(&ProfileDocument::from(&session.member), &req, &body)?;
+    let changes = MemberDocument::from(&session.member).with_profile(profile);
+
+    save_profile(&app, session.member, changes, &data).await
+}
+
+/// Valida y guarda los datos nuevos del integrante que inició sesión, y los devuelve.
+async fn save_profile(
+    app: &Scoped<MembersWrite>,
+    member: ClubMemberModel,
+    changes: MemberDocument,
+    data: &AppState,
+) -> Result<BasicResponse<ClubMemberResponse>, actix_web::Error> {
+    changes.validate()?;
+
+    let member_id = member.uuid.clone();
+    let author = ChangeAuthor {
+        app: &app.0.app.uuid,
+        member: Some(&member_id),
+    };
+
+    if !ClubMemberModel::update(member, changes, &author, &data.pool)
+        .await
+        .map_err(ApiError::from)?
+    {
+        return Err(member_changed().into());
+    }
+
+    let member: ClubMemberResponse = ClubMemberModel::get_one(&member_id, &data.pool)
+        .await
+        .map_err(ApiError::from)?
+        .into();
+
+    Ok(BasicResponse::new(
+        "Se han actualizado tus datos",
+        Some(member),
+    ))
+}
+
+/// Obtiene los proyectos en los que participa el integrante que inició sesión.
+#[utoipa::path(
+    security(("api_key" = [], "session" = [])),
+    responses(
+        (status = 200, description = "Proyectos del integrante", body = BasicResponse<Vec<ProjectModel>>),
+        (status = 401, description = "Sin una sesión válida", body = ErrorResponse),
+    ),
+)]
+#[get("/projects")]
+async fn get_my_projects(
+    _app: Scoped<ProjectsRead>,
+    session: MemberSession,
+    data: web::Data<AppState>,
+) -> Result<BasicResponse<Vec<ProjectModel>>, ApiError> {
+    Ok(BasicResponse::new(
+        "Tus proyectos",
+        Some(ProjectModel::get_by_member(&session.member.uuid, &data.pool).await?),
+    ))
+}
+
+/// Empieza a activar la verificación en dos pasos.
+///
+/// Solo la directiva puede activarla, pues son quienes pueden eliminar integrantes o revocar Apps.
+/// Entrega un secreto nuevo; la verificación no se pide hasta confirmarla con
+/// `POST /v1/me/2fa/verify`. Llamarla de nuevo antes de confirmar reemplaza el secreto.
+#[utoipa::path(
+    security(("api_key" = [], "session" = [])),
+    responses(
+        (status = 200, description = "Secreto para la aplicación de autenticación", body = BasicResponse<TotpEnrolmentResponse>),
+        (status = 401, description = "Sin una sesión válida", body = ErrorResponse),
+        (status = 403, description = "Solo la directiva puede activar la verificación", body = ErrorResponse),
+        (status = 409, description = "La verificación ya está activada", body = ErrorResponse),
+    ),
+)]
+#[post("/2fa/enrol")]
+async fn enrol_totp(
+    _app: Scoped<MembersWrite>,
+    session: MemberSession,
+    data: web::Data<AppState>,
+) -> Result<BasicResponse<TotpEnrolmentResponse>, ApiError> {
+    let member = session.member;
+
+    if member.role() < Role::Board {
+        return Err(ApiError::Forbidden(String::from(
+            "Solo la directiva puede activar la verificación en dos pasos",
+        )));
+    }
+
+    if TotpModel::is_enabled(&member.uuid, &data.pool).await? {
+        return Err(totp_already_enabled());
+    }
+
+    let secret = TotpModel::generate_secret();
+    TotpModel::start_enrolment(&member.uuid, &secret, &data.pool).await?;
+
+    let totp = TotpModel::find(&member.uuid, &data.pool).await?;
+    let account = member.email.or(member.github).unwrap_or(member.name);
+    let otpauth_uri = totp
+        .otpauth_uri(&account)
+        .map_err(|err| ApiError::Internal(err.to_string()))?;
+
+    Ok(BasicResponse::new(
+        "Agrega el secreto a tu aplicación de autenticación",
+        Some(TotpEnrolmentResponse {
+            secret: totp.secret,
+            otpauth_uri,
+        }),
+    ))
+}
+
+/// Confirma la activación de la verificación en dos pasos.
+///
+/// Recibe un código generado con el secreto entregado por `POST /v1/me/2fa/enrol`, y entrega los
+/// códigos de recuperación, que no se vuelven a mostrar.
+#[utoipa::path(
+    security(("api_key" = [], "session" = [])),
+    request_body = TotpCodeSchema,
+    responses(
+        (status = 200, description = "Verificación activada, con sus códigos de recuperación", body = BasicResponse<RecoveryCodesResponse>),
+        (status = 401, description = "Sin una sesión válida, o el código no es válido", body = ErrorResponse),
+        (status = 403, description = "Demasiados códigos incorrectos; la verificación está bloqueada", body = ErrorResponse),
+        (status = 409, description = "La verificación no se empezó a activar, o ya está activada", body = ErrorResponse),
+    ),
+)]
+#[post("/2fa/verify")]
+async fn verify_totp(
+    _app: Scoped<MembersWrite>,
+    session: MemberSession,
+    body: web::Json<TotpCodeSchema>,
+    data: web::Data<AppState>,
+) -> Result<BasicResponse<RecoveryCodesResponse>, ApiError> {
+    let totp = match TotpModel::find(&session.member.uuid, &data.pool).await {
+        Ok(totp) => totp,
+        Err(sqlx::Error::RowNotFound) => return Err(totp_not_enrolled()),
+        Err(err) => return Err(err.into()),
+    };
+
+    if totp.enabled_at.is_some() {
+        return Err(totp_already_enabled());
+    }
+
+    // El intento se cuenta antes de revisar el código, igual que al iniciar sesión.
+    if !TotpModel::claim_code_attempt(&totp.club_member_uuid, &data.pool).await? {
+        return Err(totp_locked());
+    }
+
+    let Some(step) = totp.matching_step(body.code.trim()) else {
+        return Err(rejected_code(&totp.club_member_uuid, &data.pool).await);
+    };
+
+    let (recovery_codes, hashes) = TotpModel::generate_recovery_codes();
+    TotpModel::enable(&totp.club_member_uuid, step, &hashes, &data.pool).await?;
+
+    Ok(BasicResponse::new(
+        "Se ha activado la verificación en dos pasos. Guarda tus códigos de recuperación",
+        Some(RecoveryCodesResponse { recovery_codes }),
+    ))
+}
+
+/// Desactiva la verificación en dos pasos.
+///
+/// Pide un código de la aplicación de autenticación o uno de recuperación, para que una sesión
+/// robada no baste para desactivarla. Los códigos incorrectos cuentan en los mismos intentos que
+/// al iniciar sesión, así que tampoco sirve probar códigos hasta acertar.
+#[utoipa::path(
+    security(("api_key" = [], "session" = [])),
+    request_body = TotpCodeSchema,
+    responses(
+        (status = 200, description = "Verificación desactivada", body = BasicResponse<Object>),
+        (status = 401, description = "Sin una sesión válida, o el código no es válido", body = ErrorResponse),
+        (status = 403, description = "Demasiados códigos incorrectos; la verificación está bloqueada", body = ErrorResponse),
+        (status = 409, description = "La verificación no está activada", body = ErrorResponse),
+    ),
+)]
+#[post("/2fa/disable")]
+async fn disable_totp(
+    _app: Scoped<MembersWrite>,
+    session: MemberSession,
+    body: web::Json<TotpCodeSchema>,
+    data: web::Data<AppState>,
+) -> Result<BasicResponse<()>, ApiError> {
+    let totp = match TotpModel::find(&session.member.uuid, &data.pool).await {
+        Ok(totp) if totp.enabled_at.is_some() => totp,
+        Ok(_) | Err(sqlx::Error::RowNotFound) => return Err(totp_not_enrolled()),
+        Err(err) => return Err(err.into()),
+    };
+
+    if !TotpModel::claim_code_attempt(&totp.club_member_uuid, &data.pool).await? {
+        return Err(totp_locked());
+    }
+
+    if !totp.verify(&body.code, &data.pool).await? {
+        return Err(rejected_code(&totp.club_member_uuid, &data.pool).await);
+    }
+
+    TotpModel::delete(&totp.club_member_uuid, &data.pool).await?;
+
+    Ok(BasicResponse::new(
+        "Se ha desactivado la verificación en dos pasos",
+        None,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{http::StatusCode, test, App};
+    use serde_json::json;
+    use totp_rs::{Algorithm, Secret, TOTP};
+
+    use crate::{
+        testing,
+        v1::{
+            self,
+            middleware::member_session::SESSION_HEADER,
+            models::{
+                auth::Scope,
+                totp::{TotpModel, MAX_FAILED_ATTEMPTS},
+            },
+        },
+    };
+
+    /// Código actual de la aplicación de autenticación.
+    fn current_code(totp: &TotpModel) -> String {
+        let secret = Secret::Encoded(totp.secret.clone()).to_bytes().unwrap();
+
+        TOTP::new(Algorithm::SHA1, 6, 0, 30, secret, None, String::new())
+            .unwrap()
+            .generate_current()
+            .unwrap()
+    }
+
+    /// Un código de 6 dígitos que no sirve en este momento.
+    fn wrong_code(totp: &TotpModel) -> String {
+        (0..)
+            .map(|n| format!("{n:06}"))
+            .find(|code| totp.matching_step(code).is_none())
+            .unwrap()
+    }
+
+    /// Petición a `POST /v1/me/2fa/{action}` con el código indicado.
+    fn code_request(action: &str, key: &str, session: &str, code: &str) -> test::TestRequest {
+        test::TestRequest::post()
+            .uri(&format!("/v1/me/2fa/{action}"))
+            .insert_header(("Authorization", format!("Bearer {key}")))
+            .insert_header((SESSION_HEADER, session))
+            .set_json(json!({ "code": code }))
+    }
+
+    /// Tras `MAX_FAILED_ATTEMPTS` códigos incorrectos, la activación se bloquea y ni siquiera un
+    /// código correcto sirve.
+    #[actix_web::test]
+    async fn verify_locks_after_too_many_wrong_codes() {
+        let pool = testing::pool().await;
+        let data = testing::state(&pool);
+        let (_, key) = testing::create_app(&pool, &[Scope::MembersWrite]).await;
+        let member_id = testing::create_member(&pool, "board").await;
+        let (session, _) = data.token_signer.sign_session(&member_id);
+        let service =
+            test::init_service(App::new().app_data(data.clone()).service(v1::routes())).await;
+
+        TotpModel::start_enrolment(&member_id, &TotpModel::generate_secret(), &pool)
+            .await
+            .unwrap();
+        let totp = TotpModel::find(&member_id, &pool).await.unwrap();
+
+        for _ in 1..MAX_FAILED_ATTEMPTS {
+            let req = code_request("verify", &key, &session, &wrong_code(&totp)).to_request();
+            let status = test::call_service(&service, req).await.status();
+            assert_eq!(status, StatusCode::UNAUTHORIZED);
+        }
+
+        let req = code_request("verify", &key, &session, &wrong_code(&totp)).to_request();
+        let status = test::call_service(&service, req).await.status();
+        assert_eq!(status, StatusCode::FORBIDDEN);
+
+        let req = code_request("verify", &key, &session, &current_code(&totp)).to_request();
+        let status = test::call_service(&service, req).await.status();
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert!(!TotpModel::is_enabled(&member_id, &pool).await.unwrap());
+    }
+
+    /// Tras `MAX_FAILED_ATTEMPTS` códigos incorrectos, la desactivación se bloquea, así que una
+    /// sesión robada no puede probar códigos hasta acertar.
+    #[actix_web::test]
+    async fn disable_locks_after_too_many_wrong_codes() {
+        let pool = testing::pool().await;
+        let data = testing::state(&pool);
+        let (_, key) = testing::create_app(&pool, &[Scope::MembersWrite]).await;
+        let member_id = testing::create_member(&pool, "board").await;
+        let (session, _) = data.token_signer.sign_session(&member_id);
+        let service =
+            test::init_service(App::new().app_data(data.clone()).service(v1::routes())).await;
+
+        TotpModel::start_enrolment(&member_id, &TotpModel::generate_secret(), &pool)
+            .await
+            .unwrap();
+        TotpModel::enable(&member_id, 0, &[], &pool).await.unwrap();
+        let totp = TotpModel::find(&member_id, &pool).await.unwrap();
+
+        for _ in 1..MAX_FAILED_ATTEMPTS {
+            let req = code_request("disable", &key, &session, &wrong_code(&totp)).to_request();
+            let status = test::call_service(&service, req).await.status();
+            assert_eq!(status, StatusCode::UNAUTHORIZED);
+        }
+
+        let req = code_request("disable", &key, &session, &wrong_code(&totp)).to_request();
+        let status = test::call_service(&service, req).await.status();
+        assert_eq!(status, StatusCode::FORBIDDEN);
+
+        let req = code_request("disable", &key, &session, &current_code(&totp)).to_request();
+        let status = test::call_service(&service, req).await.status();
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert!(TotpModel::is_enabled(&member_id, &pool).await.unwrap());
+    }
+
+    /// Un código correcto antes de agotar los intentos sirve, y reinicia los intentos fallidos.
+    #[actix_web::test]
+    async fn correct_code_resets_failed_attempts() {
+        let pool = testing::pool().await;
+        let data = testing::state(&pool);
+        let (_, key) = testing::create_app(&pool, &[Scope::MembersWrite]).await;
+        let member_id = testing::create_member(&pool, "board").await;
+        let (session, _) = data.token_signer.sign_session(&member_id);
+        let service =
+            test::init_service(App::new().app_data(data.clone()).service(v1::routes())).await;
+
+        TotpModel::start_enrolment(&member_id, &TotpModel::generate_secret(), &pool)
+            .await
+            .unwrap();
+        let totp = TotpModel::find(&member_id, &pool).await.unwrap();
+
+        for _ in 1..MAX_FAILED_ATTEMPTS {
+            let req = code_request("verify", &key, &session, &wrong_code(&totp)).to_request();
+            let status = test::call_service(&service, req).await.status();
+            assert_eq!(status, StatusCode::UNAUTHORIZED);
+        }
+
+        let req = code_request("verify", &key, &session, &current_code(&totp)).to_request();
+        let status = test::call_service(&service, req).await.status();
+        assert_eq!(status, StatusCode::OK);
+
+        let totp = TotpModel::find(&member_id, &pool).await.unwrap();
+        assert!(totp.enabled_at.is_some());
+        assert_eq!(totp.failed_attempts, 0);
+    }
+}
@@ -2,49 +2,127 @@
 //!
 //! Aquí encontrarás el cuerpo y carne de la API, junto con el mapeo de ruta a función dentro de
 //! esta.
+//!
+//! Cada grupo de rutas se declara una sola vez con `route_group!`, que genera tanto la función que
+//! registra sus handlers como su documentación OpenAPI. Así, no se puede servir una ruta sin
+//! documentarla, ni documentar una que no se sirve.
+
+use utoipa::OpenApi;
+
+use auth::DeprecatedRoutes;
 
 pub mod auth;
 pub mod club_members;
+pub mod me;
 pub mod projects;
+pub mod sessions;
 
-/// Agrega las rutas relacionada a la autorización de Apps.
+/// Declara un grupo de rutas a partir de una sola lista de handlers.
 ///
-/// Las rutas son agregadas bajo el campo de `/auth`, por lo que todas las funciones puedes ser
-/// encontradas bajo `/v1/auth`.
-pub fn add_auth_routes() -> actix_web::Scope {
-    use auth as a;
-
-    actix_web::web::scope("/auth")
-        .service(a::register)
-        .service(a::regenerate)
+/// Genera la función que registra los handlers bajo el scope indicado, en el mismo orden de la
+/// lista, y la documentación OpenAPI del grupo con esos mismos handlers. Con `with { ... }` se
+/// pueden agregar otras opciones de `#[openapi(...)]`, como `modifiers`.
+macro_rules! route_group {
+    (
+        $(#[$fn_meta:meta])*
+        fn $name:ident($scope:literal);
+        $(#[$api_meta:meta])*
+        struct $api:ident $(with { $($openapi:tt)+ })?;
+        [$($module:ident::$handler:ident),+ $(,)?]
+    ) => {
+        $(#[$fn_meta])*
+        pub fn $name() -> actix_web::Scope {
+            actix_web::web::scope($scope)$(.service($module::$handler))+
+        }
+
+        $(#[$api_meta])*
+        #[derive(OpenApi)]
+        #[openapi(paths($($module::$handler),+) $(, $($openapi)+)?)]
+        pub struct $api;
+    };
 }
 
-/// Agrega las rutas relacionada a los integrantes del club.
-///
-/// Las rutas son agregadas bajo el campo de `/members`, por lo que todas las funciones puedes ser
-/// encontradas bajo `/v1/members`.
-pub fn add_member_routes() -> actix_web::Scope {
-    use club_members as cm;
-
-    actix_web::web::scope("/members")
-        .service(cm::get_club_members)
-        .service(cm::get_single_member)
-        .service(cm::add_club_member)
-        .service(cm::update_club_member)
-        .service(cm::delete_member)
+route_group! {
+    /// Agrega las rutas relacionada a la autorización de Apps.
+    ///
+    /// Las rutas son agregadas bajo el campo de `/auth`, por lo que todas las funciones puedes ser
+    /// encontradas bajo `/v1/auth`. Acá también están las rutas de inicio de sesión de integrantes.
+    fn add_auth_routes("/auth");
+    /// Documentación OpenAPI de las rutas de `add_auth_routes`.
+    struct AuthApi with { modifiers(&DeprecatedRoutes) };
+    [
+        sessions::github_authorize,
+        sessions::github_callback,
+        sessions::request_magic_link,
+        sessions::magic_link_login,
+        sessions::totp_login,
+        auth::register,
+        auth::update,
+        auth::update_legacy,
+        auth::patch_app,
+        auth::regenerate,
+        auth::get_apps,
+        auth::get_single_app,
+        auth::revoke,
+        auth::restore,
+    ]
 }
 
-/// Agrega las rutas relacionadas a los proyectos del club.
-///
-/// Las rutas son agregadas bajo el campo de `/projects`, por lo que todas las funciones pueden ser
-/// encontradas bajo `/v1/projects`.
-pub fn add_project_routes() -> actix_web::Scope {
-    use projects as p;
-
-    actix_web::web::scope("/projects")
-        .service(p::get_single_member)
-        .service(p::get_projects)
-        .service(p::create_project)
-        .service(p::update_project)
-        .service(p::delete_project)
+route_group! {
+    /// Agrega las rutas relacionada a los integrantes del club.
+    ///
+    /// Las rutas son agregadas bajo el campo de `/members`, por lo que todas las funciones puedes
+    /// ser encontradas bajo `/v1/members`.
+    fn add_member_routes("/members");
+    /// Documentación OpenAPI de las rutas de `add_member_routes`.
+    struct MemberApi;
+    [
+        club_members::get_club_members,
+        club_members::get_single_member,
+        club_members::add_club_member,
+        club_members::update_club_member,
+        club_members::patch_club_member,
+        club_members::delete_member,
+        club_members::get_member_history,
+        club_members::restore_member,
+        club_members::purge_member,
+    ]
+}
+
+route_group! {
+    /// Agrega las rutas del integrante que inició sesión.
+    ///
+    /// Las rutas son agregadas bajo el campo de `/me`, por lo que todas las funciones pueden ser
+    /// encontradas bajo `/v1/me`.
+    fn add_me_routes("/me");
+    /// Documentación OpenAPI de las rutas de `add_me_routes`.
+    struct MeApi;
+    [
+        me::get_me,
+        me::update_me,
+        me::patch_me,
+        me::get_my_projects,
+        me::enrol_totp,
+        me::verify_totp,
+        me::disable_totp,
+    ]
+}
+
+route_group! {
+    /// Agrega las rutas relacionadas a los proyectos del club.
+    ///
+    /// Las rutas son agregadas bajo el campo de `/projects`, por lo que todas las funciones pueden
+    /// ser encontradas bajo `/v1/projects`.
+    fn add_project_routes("/projects");
+    /// Documentación OpenAPI de las rutas de `add_project_routes`.
+    struct ProjectApi;
+    [
+        projects::get_single_project,
+        projects::get_projects,
+        projects::create_project,
+        projects::update_project,
+        projects::patch_project,
+        projects::delete_project,
+        projects::restore_project,
+    ]
 }
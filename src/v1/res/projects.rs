@@ -11,72 +11,185 @@
 //! - Actualizar
 //! - Eliminar.
 
-use actix_web::web::{Data, Json, Path};
-use actix_web::{delete, get, post, put};
+use actix_web::web::{Data, Json, Path, Query};
+use actix_web::{delete, get, patch, post, put, HttpRequest};
 
-use crate::v1::schemas::project::{CreateProjectSchema, UpdateProjectSchema};
+use crate::v1::policy::{Action, Actor};
+use crate::v1::schemas::{
+    patch::apply_patch,
+    project::{CreateProjectSchema, ProjectDocument, ProjectListQuery, UpdateProjectSchema},
+    validation::Validate,
+};
 use crate::{
     v1::{
-        models::project::ProjectModel,
-        responders::{basic_response::BasicResponse, errors::DBError},
+        models::project::{ProjectModel, ProjectState},
+        responders::{
+            basic_response::BasicResponse,
+            errors::{ApiError, ErrorResponse},
+        },
     },
     AppState,
 };
 
 /// Obtiene un único proyecto según su UUID
 ///
-/// En caso de no encontrar el proyecto, genera un DBError con código 404. Lo que esperarías, vaya.
+/// En caso de no encontrar el proyecto, genera un ApiError con código 404. Lo que esperarías, vaya.
 /// Si lo encuentra, envía una respuesta básica con los datos de ese proyecto.
-#[get("/{id}")]
-async fn get_single_member(
+#[utoipa::path(
+    params(("id" = uuid::Uuid, Path, description = "UUID del proyecto")),
+    responses(
+        (status = 200, description = "Datos del proyecto, con sus involucrados y su historial", body = BasicResponse<ProjectModel>),
+        (status = 404, description = "El proyecto no existe", body = ErrorResponse),
+    ),
+)]
+#[get("/{id}", name = "project")]
+async fn get_single_project(
+    actor: Actor,
     path: Path<uuid::Uuid>,
     data: Data<AppState>,
-) -> Result<BasicResponse<ProjectModel>, DBError> {
+) -> Result<BasicResponse<ProjectModel>, actix_web::Error> {
+    actor.authorize(Action::ReadProjects)?;
+
     let project_id = path.into_inner();
 
     Ok(BasicResponse::new(
         "Se ha encontrado el siguiente proyecto del club",
-        Some(ProjectModel::find_by_id(project_id, &data.pool).await?),
+        Some(
+            ProjectModel::find_by_id(project_id, &data.pool)
+                .await
+                .map_err(ApiError::from)?,
+        ),
     ))
 }
 
-/// Obtiene todos los proyectos de la API.
+/// Obtiene los proyectos de la API.
 ///
-/// Esta lista no entrega una sublista de involucrados.
+/// La lista se puede filtrar, ordenar y paginar según los parámetros de `ProjectListQuery`, por
+/// ejemplo `GET /v1/projects?state=InProgress&created_from=2024-03-01&sort=created_at`.
+///
+/// Esta lista no entrega una sublista de involucrados, a menos que se pida con
+/// `include=involved`.
+#[utoipa::path(
+    params(ProjectListQuery),
+    responses(
+        (status = 200, description = "Página de proyectos", body = BasicResponse<Vec<ProjectModel>>),
+        (status = 422, description = "Parámetros inválidos", body = ErrorResponse),
+    ),
+)]
 #[get("")]
-async fn get_projects(data: Data<AppState>) -> Result<BasicResponse<Vec<ProjectModel>>, DBError> {
+async fn get_projects(
+    actor: Actor,
+    query: Query<ProjectListQuery>,
+    req: HttpRequest,
+    data: Data<AppState>,
+) -> Result<BasicResponse<Vec<ProjectModel>>, actix_web::Error> {
+    actor.authorize(Action::ReadProjects)?;
+
+    query.validate()?;
+    let page = query.page()?;
+    let result = ProjectModel::search(&query, &page, &data.pool)
+        .await
+        .map_err(ApiError::from)?;
+
+    let pagination = page.pagination(
+        &result,
+        |project| (query.sort.value_of(project), project.uuid.to_owned()),
+        &req,
+    );
+
     Ok(BasicResponse::new(
         "Se han conseguido los siguientes proyectos",
-        Some(ProjectModel::get_all(&data.pool).await?),
-    ))
+        Some(result.items),
+    )
+    .with_pagination(pagination))
 }
 
 /// Crea un nuevo proyecto en la API.
 ///
-/// Además, retorna el proyecto de la misma forma en que lo haría al hacer `GET /{id}`
+/// Además, retorna el proyecto de la misma forma en que lo haría al hacer `GET /{id}`, con un
+/// estado 201 y la URL del proyecto en el header `Location`.
+///
+/// En nombre de un integrante, cualquier integrante que no sea invitado puede crear proyectos.
+#[utoipa::path(
+    request_body = CreateProjectSchema,
+    responses(
+        (status = 201, description = "Proyecto creado", body = BasicResponse<ProjectModel>,
+            headers(("Location" = String, description = "URL del nuevo proyecto"))),
+        (status = 403, description = "Sin permiso para crear proyectos", body = ErrorResponse),
+        (status = 422, description = "Datos inválidos, o un involucrado que no existe", body = ErrorResponse),
+    ),
+)]
 #[post("/create")]
 async fn create_project(
+    actor: Actor,
     body: Json<CreateProjectSchema>,
+    req: HttpRequest,
     data: Data<AppState>,
-) -> Result<BasicResponse<ProjectModel>, DBError> {
-    let project = ProjectModel::create(body.into_inner(), &data.pool).await?;
+) -> Result<BasicResponse<ProjectModel>, actix_web::Error> {
+    actor.authorize(Action::WriteProject)?;
+    body.validate()?;
 
-    Ok(BasicResponse::new(
+    let project = ProjectModel::create(body.into_inner(), &data.pool)
+        .await
+        .map_err(ApiError::from)?;
+
+    let location = req
+        .url_for("project", [&project.uuid])
+        .map_err(ApiError::from)?;
+
+    Ok(BasicResponse::created(
         "Se ha creado un nuevo proyecto",
-        Some(project),
+        project,
+        location,
     ))
 }
 
 /// Actualiza la información del proyecto.
 ///
-/// Además, retorna los nuevos valores del proyecto.
+/// Además, retorna los nuevos valores del proyecto. Los cambios de estado que no permite el
+/// `ProjectWorkflow` de la API generan un error 409.
+///
+/// En nombre de un integrante, cualquier integrante que no sea invitado puede editar proyectos.
+#[utoipa::path(
+    params(("id" = uuid::Uuid, Path, description = "UUID del proyecto")),
+    request_body = UpdateProjectSchema,
+    responses(
+        (status = 200, description = "Proyecto actualizado", body = BasicResponse<ProjectModel>),
+        (status = 403, description = "Sin permiso para editar proyectos", body = ErrorResponse),
+        (status = 404, description = "El proyecto no existe", body = ErrorResponse),
+        (status = 409, description = "Cambio de estado no permitido, o el proyecto cambió de estado mientras tanto", body = ErrorResponse),
+        (status = 422, description = "Datos inválidos", body = ErrorResponse),
+    ),
+)]
 #[put("/update/{id}")]
 async fn update_project(
+    actor: Actor,
     path: Path<uuid::Uuid>,
     body: Json<UpdateProjectSchema>,
     data: Data<AppState>,
-) -> Result<BasicResponse<ProjectModel>, DBError> {
-    let project = ProjectModel::update(path.into_inner(), body.into_inner(), &data.pool).await?;
+) -> Result<BasicResponse<ProjectModel>, actix_web::Error> {
+    actor.authorize(Action::WriteProject)?;
+    body.validate()?;
+
+    let previous = ProjectModel::find_by_id(path.into_inner(), &data.pool)
+        .await
+        .map_err(ApiError::from)?;
+
+    let body = body.into_inner();
+    let changes = ProjectDocument::from(&previous).apply(&body);
+    changes.validate()?;
+    authorize_state_change(&data, &previous, changes.state)?;
+
+    let project = ProjectModel::update(
+        previous,
+        changes,
+        body.reason.as_deref(),
+        &actor.author(),
+        &data.pool,
+    )
+    .await
+    .map_err(ApiError::from)?
+    .ok_or_else(project_changed)?;
 
     Ok(BasicResponse::new(
         "Se ha actualizado el proyecto",
@@ -84,13 +197,150 @@ async fn update_project(
     ))
 }
 
+/// Actualiza parcialmente un proyecto con un JSON Merge Patch o un JSON Patch.
+///
+/// A diferencia de `PUT /update/{id}`, un campo en `null` borra el dato, por ejemplo
+/// `{"description": null}`. Los involucrados son la lista `involved` de UUID, así que con un
+/// JSON Patch se pueden agregar (`{"op": "add", "path": "/involved/-", "value": "<uuid>"}`) o
+/// quitar uno a uno. Los cambios de estado siguen las mismas reglas que en `PUT`, pero quedan en
+/// el historial sin motivo. Devuelve los datos ya actualizados.
+///
+/// En nombre de un integrante, cualquier integrante que no sea invitado puede editar proyectos.
+#[utoipa::path(
+    params(("id" = uuid::Uuid, Path, description = "UUID del proyecto")),
+    request_body(
+        description = "JSON Merge Patch (RFC 7396) o JSON Patch (RFC 6902) sobre los datos del proyecto",
+        content(
+            (ProjectDocument = "application/merge-patch+json"),
+            (Vec<Object> = "application/json-patch+json"),
+        ),
+    ),
+    responses(
+        (status = 200, description = "Proyecto actualizado", body = BasicResponse<ProjectModel>),
+        (status = 400, description = "Parche mal formado", body = ErrorResponse),
+        (status = 403, description = "Sin permiso para editar proyectos", body = ErrorResponse),
+        (status = 404, description = "El proyecto no existe", body = ErrorResponse),
+        (status = 409, description = "Cambio de estado no permitido, `test` fallido, o el proyecto cambió de estado mientras tanto", body = ErrorResponse),
+        (status = 415, description = "Tipo de parche no soportado", body = ErrorResponse),
+        (status = 422, description = "El resultado no es válido", body = ErrorResponse),
+    ),
+)]
+#[patch("/{id}")]
+async fn patch_project(
+    actor: Actor,
+    path: Path<uuid::Uuid>,
+    body: Json<serde_json::Value>,
+    req: HttpRequest,
+    data: Data<AppState>,
+) -> Result<BasicResponse<ProjectModel>, actix_web::Error> {
+    actor.authorize(Action::WriteProject)?;
+
+    let previous = ProjectModel::find_by_id(path.into_inner(), &data.pool)
+        .await
+        .map_err(ApiError::from)?;
+
+    let changes = apply_patch(&ProjectDocument::from(&previous), &req, &body)?;
+    changes.validate()?;
+    authorize_state_change(&data, &previous, changes.state)?;
+
+    let project = ProjectModel::update(previous, changes, None, &actor.author(), &data.pool)
+        .await
+        .map_err(ApiError::from)?
+        .ok_or_else(project_changed)?;
+
+    Ok(BasicResponse::new(
+        "Se ha actualizado el proyecto",
+        Some(project),
+    ))
+}
+
+/// Error al actualizar un proyecto que cambió de estado mientras tanto.
+fn project_changed() -> ApiError {
+    ApiError::Conflict(String::from(
+        "El proyecto cambió de estado mientras se actualizaba. Vuelve a cargarlo e intenta de nuevo",
+    ))
+}
+
+/// Verifica que el `ProjectWorkflow` de la API permita que un proyecto pase a `new_state`, o
+/// genera un error 409.
+fn authorize_state_change(
+    data: &AppState,
+    project: &ProjectModel,
+    new_state: ProjectState,
+) -> Result<(), ApiError> {
+    if data.project_workflow.allows(project.state(), new_state) {
+        return Ok(());
+    }
+
+    Err(ApiError::Conflict(format!(
+        "No se puede pasar del estado {} a {}",
+        project.state().as_str(),
+        new_state.as_str()
+    )))
+}
+
 /// Elimina un proyecto.
+///
+/// El proyecto se puede restaurar con `POST /{id}/restore` durante el periodo de retención
+/// (`PROJECT_RETENTION_DAYS`); después, `exdev-admin project purge` lo elimina definitivamente.
+/// Responde un estado 204, sin cuerpo.
+///
+/// En nombre de un integrante, solo la directiva puede eliminar proyectos.
+#[utoipa::path(
+    params(("id" = uuid::Uuid, Path, description = "UUID del proyecto")),
+    responses(
+        (status = 204, description = "Proyecto eliminado"),
+        (status = 403, description = "Sin permiso para eliminar proyectos", body = ErrorResponse),
+        (status = 404, description = "El proyecto no existe", body = ErrorResponse),
+    ),
+)]
 #[delete("/delete/{id}")]
 async fn delete_project(
+    actor: Actor,
     path: Path<uuid::Uuid>,
     data: Data<AppState>,
-) -> Result<BasicResponse<()>, DBError> {
-    ProjectModel::delete(path.into_inner(), &data.pool).await?;
+) -> Result<BasicResponse<()>, actix_web::Error> {
+    actor.authorize(Action::DeleteProject)?;
 
-    Ok(BasicResponse::new("Se ha eliminado el proyecto {id}", None))
+    let result = ProjectModel::delete(path.into_inner(), &data.pool)
+        .await
+        .map_err(ApiError::from)?;
+
+    if result.rows_affected() == 0 {
+        return Err(ApiError::from(sqlx::Error::RowNotFound).into());
+    }
+
+    Ok(BasicResponse::no_content())
+}
+
+/// Restaura un proyecto eliminado.
+///
+/// Solo se pueden restaurar los proyectos eliminados dentro del periodo de retención; los demás
+/// se tratan como si no existieran. En nombre de un integrante, solo la directiva puede
+/// restaurar proyectos.
+#[utoipa::path(
+    params(("id" = uuid::Uuid, Path, description = "UUID del proyecto eliminado")),
+    responses(
+        (status = 200, description = "Proyecto restaurado", body = BasicResponse<ProjectModel>),
+        (status = 403, description = "Sin permiso para restaurar proyectos", body = ErrorResponse),
+        (status = 404, description = "No existe un proyecto eliminado con esa UUID dentro del periodo de retención", body = ErrorResponse),
+    ),
+)]
+#[post("/{id}/restore")]
+async fn restore_project(
+    actor: Actor,
+    path: Path<uuid::Uuid>,
+    data: Data<AppState>,
+) -> Result<BasicResponse<ProjectModel>, actix_web::Error> {
+    actor.authorize(Action::RestoreProject)?;
+
+    let retention_start = chrono::Utc::now().naive_utc() - data.project_retention;
+    let project = ProjectModel::restore(path.into_inner(), retention_start, &data.pool)
+        .await
+        .map_err(ApiError::from)?;
+
+    Ok(BasicResponse::new(
+        "Se ha restaurado el proyecto",
+        Some(project),
+    ))
 }
@@ -0,0 +1,553 @@
+//! Handlers del inicio de sesión de integrantes del club.
+//!
+//! Estas rutas las llama una App cliente (con su propia llave) en nombre de un integrante. Al
+//! terminar, el integrante recibe un token de sesión que la App debe enviar en el header
+//! `X-Session-Token` para usar las rutas que actúan en su nombre.
+//!
+//! El inicio de sesión con GitHub sigue el flujo OAuth2 "authorization code":
+//! 1. La App pide `GET /v1/auth/github/authorize` y envía al integrante a la URL que recibe.
+//! 2. GitHub redirige al integrante a la App con un `code` y el `state`.
+//! 3. La App envía ambos a `POST /v1/auth/github/callback` y recibe la sesión.
+//!
+//! Los integrantes sin GitHub pueden iniciar sesión con un enlace enviado a su correo:
+//! 1. La App pide `POST /v1/auth/magic-link` con el email del integrante y su propia URL.
+//! 2. El integrante recibe un correo con un enlace a la App, que lleva un `token` de un solo uso.
+//! 3. La App envía ese token a `GET /v1/auth/magic-link/{token}` y recibe la sesión.
+//!
+//! Si el integrante activó la verificación en dos pasos, cualquiera de estos flujos termina con un
+//! error 401 `totp_required` que trae un `challenge`. La App pide el código al integrante y envía
+//! ambos a `POST /v1/auth/2fa` para recibir la sesión.
+
+use actix_web::web::{Data, Json, Path, Query};
+use actix_web::{get, post};
+use rand::RngCore;
+
+use crate::{
+    v1::{
+        middleware::{
+            api_key::AuthenticatedApp,
+            member_session::{expired_token, invalid_token},
+        },
+        models::{
+            club_member::ClubMemberModel,
+            magic_link::MagicLinkModel,
+            totp::{TotpModel, LOCKOUT_MINUTES},
+        },
+        responders::{
+            basic_response::BasicResponse,
+            errors::{ApiError, ErrorResponse},
+        },
+        schemas::{
+            session::{
+                AuthorizeResponse, GithubAuthorizeQuery, GithubCallbackSchema, MagicLinkSchema,
+                SessionResponse, TotpLoginSchema,
+            },
+            validation::Validate,
+        },
+    },
+    AppState,
+};
+
+/// Propósito del `state` firmado del flujo OAuth.
+const OAUTH_STATE_PURPOSE: &str = "oauth-state";
+
+/// Minutos que tiene el integrante para autorizar a la API en GitHub.
+const OAUTH_STATE_TTL_MINUTES: i64 = 10;
+
+/// Propósito del desafío de la verificación en dos pasos.
+const TOTP_CHALLENGE_PURPOSE: &str = "totp-challenge";
+
+/// Minutos que tiene el integrante para ingresar su código de verificación.
+const TOTP_CHALLENGE_TTL_MINUTES: i64 = 5;
+
+/// Mensaje al pedir un enlace de inicio de sesión, exista o no el integrante.
+const MAGIC_LINK_SENT: &str = "Si el email pertenece a un integrante, se le envió un enlace";
+
+/// Error de los métodos de inicio de sesión que no están configurados en este servidor.
+fn not_configured() -> ApiError {
+    ApiError::NotImplemented(String::from(
+        "Este método de inicio de sesión no está habilitado",
+    ))
+}
+
+/// Error de las URL de redirección que no son válidas o no están en `ALLOWED_REDIRECT_URIS`.
+fn invalid_redirect() -> ApiError {
+    ApiError::BadRequest(String::from(
+        "La URL de redirección no es válida o no está permitida",
+    ))
+}
+
+/// Error de un proveedor externo, como GitHub, que rechazó o no respondió la petición.
+fn provider_error(err: anyhow::Error) -> ApiError {
+    ApiError::BadGateway {
+        message: String::from("No se pudo verificar la identidad"),
+        detail: err.to_string(),
+    }
+}
+
+/// Error de los códigos de verificación (o de recuperación) que no son válidos.
+pub fn invalid_code() -> ApiError {
+    ApiError::Unauthorized(String::from("El código de verificación no es válido"))
+}
+
+/// Error al usar la verificación en dos pasos mientras está bloqueada.
+pub fn totp_locked() -> ApiError {
+    ApiError::Forbidden(format!(
+        "Demasiados códigos de verificación incorrectos. Intenta de nuevo en {LOCKOUT_MINUTES} minutos"
+    ))
+}
+
+/// Convierte el error de buscar algo a partir de un token: si no existe, el token no es válido.
+fn token_lookup_error(err: sqlx::Error) -> ApiError {
+    match err {
+        sqlx::Error::RowNotFound => invalid_token(),
+        err => err.into(),
+    }
+}
+
+/// Entrega una sesión a un integrante que ya demostró su identidad.
+///
+/// Si el integrante activó la verificación en dos pasos, en vez de la sesión entrega el error
+/// `totp_required` con un desafío firmado. Solo el último desafío entregado sirve, y deja de
+/// servir tras `MAX_FAILED_ATTEMPTS` códigos incorrectos.
+async fn start_session(
+    member: ClubMemberModel,
+    data: &AppState,
+) -> Result<BasicResponse<SessionResponse>, ApiError> {
+    if TotpModel::is_enabled(&member.uuid, &data.pool).await? {
+        let mut nonce = [0u8; 16];
+        rand::rngs::OsRng.fill_bytes(&mut nonce);
+        let nonce = hex::encode(nonce);
+
+        if !TotpModel::start_challenge(&member.uuid, &nonce, &data.pool).await? {
+            return Err(totp_locked());
+        }
+
+        let (challenge, _) = data.token_signer.sign(
+            TOTP_CHALLENGE_PURPOSE,
+            &format!("{}:{nonce}", member.uuid),
+            chrono::Duration::minutes(TOTP_CHALLENGE_TTL_MINUTES),
+        );
+
+        return Err(ApiError::TotpRequired(challenge));
+    }
+
+    let (token, expires_at) = data.token_signer.sign_session(&member.uuid);
+
+    Ok(BasicResponse::new(
+        "Se ha iniciado la sesión",
+        Some(SessionResponse {
+            token,
+            expires_at,
+            member: member.into(),
+        }),
+    ))
+}
+
+/// Inicia el inicio de sesión con GitHub.
+///
+/// `redirect_uri` debe estar en `ALLOWED_REDIRECT_URIS`. Entrega la URL de GitHub a la que se
+/// debe enviar al integrante, y un `state` firmado que solo sirve para la App que lo pidió y
+/// durante unos minutos. La API no guarda el `state`, así que por
+/// sí solo no protege contra ataques CSRF: la App debe guardarlo junto a la sesión del navegador
+/// del integrante y revisar que GitHub le devuelva el mismo antes de llamar a `/github/callback`.
+#[utoipa::path(
+    params(GithubAuthorizeQuery),
+    responses(
+        (status = 200, description = "URL de GitHub y `state` del flujo", body = BasicResponse<AuthorizeResponse>),
+        (status = 400, description = "La URL de redirección no es válida o no está permitida", body = ErrorResponse),
+        (status = 501, description = "El inicio de sesión con GitHub no está configurado", body = ErrorResponse),
+    ),
+)]
+#[get("/github/authorize")]
+async fn github_authorize(
+    app: AuthenticatedApp,
+    query: Query<GithubAuthorizeQuery>,
+    data: Data<AppState>,
+) -> Result<BasicResponse<AuthorizeResponse>, ApiError> {
+    let github = data.github.as_ref().ok_or_else(not_configured)?;
+    data.allowed_redirects
+        .check(&query.redirect_uri)
+        .ok_or_else(invalid_redirect)?;
+
+    let mut nonce = [0u8; 16];
+    rand::rngs::OsRng.fill_bytes(&mut nonce);
+
+    let (state, _) = data.token_signer.sign(
+        OAUTH_STATE_PURPOSE,
+        &format!("{}:{}", app.app.uuid, hex::encode(nonce)),
+        chrono::Duration::minutes(OAUTH_STATE_TTL_MINUTES),
+    );
+
+    let url = github
+        .authorize_url(&query.redirect_uri, &state)
+        .map_err(provider_error)?;
+
+    Ok(BasicResponse::new(
+        "Envía al integrante a la URL de GitHub",
+        Some(AuthorizeResponse { url, state }),
+    ))
+}
+
+/// Termina el inicio de sesión con GitHub.
+///
+/// Cambia el código entregado por GitHub por el usuario que autorizó a la API, busca al integrante
+/// del club con ese usuario de GitHub y le entrega una sesión.
+#[utoipa::path(
+    request_body = GithubCallbackSchema,
+    responses(
+        (status = 200, description = "Sesión del integrante", body = BasicResponse<SessionResponse>),
+        (status = 400, description = "La URL de redirección no es válida o no está permitida", body = ErrorResponse),
+        (status = 401, description = "El `state` no es válido o expiró, o se requiere la verificación en dos pasos (`totp_required`)", body = ErrorResponse),
+        (status = 403, description = "La cuenta de GitHub no pertenece a ningún integrante, o la verificación en dos pasos está bloqueada", body = ErrorResponse),
+        (status = 501, description = "El inicio de sesión con GitHub no está configurado", body = ErrorResponse),
+        (status = 502, description = "GitHub no pudo verificar la identidad", body = ErrorResponse),
+    ),
+)]
+#[post("/github/callback")]
+async fn github_callback(
+    app: AuthenticatedApp,
+    body: Json<GithubCallbackSchema>,
+    data: Data<AppState>,
+) -> Result<BasicResponse<SessionResponse>, ApiError> {
+    let github = data.github.as_ref().ok_or_else(not_configured)?;
+
+    let state = data.token_signer.verify(OAUTH_STATE_PURPOSE, &body.state)?;
+
+    // El `state` de otra App no sirve, aunque su firma sea válida.
+    if state.split_once(':').map(|(app_id, _)| app_id) != Some(app.app.uuid.as_str()) {
+        return Err(invalid_token());
+    }
+
+    data.allowed_redirects
+        .check(&body.redirect_uri)
+        .ok_or_else(invalid_redirect)?;
+
+    let user = github
+        .fetch_user(&body.code, &body.redirect_uri)
+        .await
+        .map_err(provider_error)?;
+
+    let member = match ClubMemberModel::find_by_github(user.id, &user.login, &data.pool).await {
+        Ok(member) => member,
+        Err(sqlx::Error::RowNotFound) => {
+            return Err(ApiError::Forbidden(String::from(
+                "No existe un integrante del club asociado a esta cuenta",
+            )))
+        }
+        Err(err) => return Err(err.into()),
+    };
+
+    start_session(member, &data).await
+}
+
+/// Envía un enlace de inicio de sesión al correo de un integrante.
+///
+/// La respuesta es la misma si el email no pertenece a ningún integrante, para no revelar quiénes
+/// son parte del club, e incluso si el correo no se pudo enviar. El enlace lleva a `redirect_uri`
+/// (que debe estar en `ALLOWED_REDIRECT_URIS`) con un parámetro `token` de un solo uso, que
+/// expira según `MAGIC_LINK_TTL`.
+#[utoipa::path(
+    request_body = MagicLinkSchema,
+    responses(
+        (status = 200, description = "Enlace enviado, si el email pertenece a un integrante", body = BasicResponse<Object>),
+        (status = 400, description = "La URL de redirección no es válida o no está permitida", body = ErrorResponse),
+        (status = 422, description = "Datos inválidos", body = ErrorResponse),
+    ),
+)]
+#[post("/magic-link")]
+async fn request_magic_link(
+    body: Json<MagicLinkSchema>,
+    data: Data<AppState>,
+) -> Result<BasicResponse<()>, ApiError> {
+    // Se valida todo antes de buscar al integrante, para que el error no dependa del email.
+    body.validate()?;
+    let mut link = data
+        .allowed_redirects
+        .check(&body.redirect_uri)
+        .ok_or_else(invalid_redirect)?;
+
+    let member = match ClubMemberModel::find_by_email(&body.email, &data.pool).await {
+        Ok(member) => member,
+        Err(sqlx::Error::RowNotFound) => return Ok(BasicResponse::new(MAGIC_LINK_SENT, None)),
+        Err(err) => return Err(err.into()),
+    };
+
+    let mut token = [0u8; 32];
+    rand::rngs::OsRng.fill_bytes(&mut token);
+    let token = hex::encode(token);
+
+    let expires_at = chrono::Utc::now().naive_utc() + data.magic_link_ttl;
+    MagicLinkModel::create(
+        &MagicLinkModel::hash_token(&token),
+        &member.uuid,
+        expires_at,
+        &data.pool,
+    )
+    .await?;
+
+    link.query_pairs_mut().append_pair("token", &token);
+
+    let message = format!(
+        "Hola, {}:\n\n\
+        Para iniciar sesión en el ExDev, abre el siguiente enlace:\n\n\
+        {link}\n\n\
+        El enlace funciona una sola vez y expira en {} minutos. Si no lo pediste, puedes ignorar \
+        este correo.\n",
+        member.name,
+        data.magic_link_ttl.num_minutes(),
+    );
+
+    // Un error al enviar el correo solo se registra: responder con un error revelaría que el
+    // email pertenece a un integrante.
+    if let Err(err) = data
+        .mailer
+        .send(&body.email, "Tu enlace para iniciar sesión", message)
+        .await
+    {
+        eprintln!("No se pudo enviar el enlace de inicio de sesión: {err}");
+    }
+
+    Ok(BasicResponse::new(MAGIC_LINK_SENT, None))
+}
+
+/// Cambia el token de un enlace de inicio de sesión por una sesión.
+///
+/// El token deja de servir apenas se usa, aunque la sesión no llegue a su destino.
+#[utoipa::path(
+    params(("token" = String, Path, description = "Token de un solo uso del enlace")),
+    responses(
+        (status = 200, description = "Sesión del integrante", body = BasicResponse<SessionResponse>),
+        (status = 401, description = "El token no es válido, expiró o se requiere la verificación en dos pasos", body = ErrorResponse),
+        (status = 403, description = "La verificación en dos pasos está bloqueada por demasiados códigos incorrectos", body = ErrorResponse),
+    ),
+)]
+#[get("/magic-link/{token}")]
+async fn magic_link_login(
+    path: Path<String>,
+    data: Data<AppState>,
+) -> Result<BasicResponse<SessionResponse>, ApiError> {
+    let token_hash = MagicLinkModel::hash_token(&path.into_inner());
+
+    let link = MagicLinkModel::find_by_hash(&token_hash, &data.pool)
+        .await
+        .map_err(token_lookup_error)?;
+
+    if link.used_at.is_some() {
+        return Err(invalid_token());
+    }
+
+    if link.expires_at <= chrono::Utc::now().naive_utc() {
+        return Err(expired_token());
+    }
+
+    // Si otra petición usó el enlace entre la búsqueda y ahora, esta no recibe sesión.
+    if !MagicLinkModel::mark_used(&token_hash, &data.pool).await? {
+        return Err(invalid_token());
+    }
+
+    let member = ClubMemberModel::get_one(link.club_member_uuid, &data.pool)
+        .await
+        .map_err(token_lookup_error)?;
+
+    start_session(member, &data).await
+}
+
+/// Termina un inicio de sesión que pidió la verificación en dos pasos.
+///
+/// Recibe el desafío entregado junto al error `totp_required` y un código de la aplicación de
+/// autenticación, o uno de recuperación. Tras `MAX_FAILED_ATTEMPTS` códigos incorrectos el desafío
+/// deja de servir y el integrante no puede iniciar sesión durante `LOCKOUT_MINUTES` minutos.
+#[utoipa::path(
+    request_body = TotpLoginSchema,
+    responses(
+        (status = 200, description = "Sesión del integrante", body = BasicResponse<SessionResponse>),
+        (status = 401, description = "El desafío o el código no son válidos", body = ErrorResponse),
+        (status = 403, description = "Demasiados códigos incorrectos; el inicio de sesión está bloqueado por unos minutos", body = ErrorResponse),
+    ),
+)]
+#[post("/2fa")]
+async fn totp_login(
+    body: Json<TotpLoginSchema>,
+    data: Data<AppState>,
+) -> Result<BasicResponse<SessionResponse>, ApiError> {
+    let subject = data
+        .token_signer
+        .verify(TOTP_CHALLENGE_PURPOSE, &body.challenge)?;
+    let (member_id, nonce) = subject.split_once(':').ok_or_else(invalid_token)?;
+
+    let totp = TotpModel::find(member_id, &data.pool)
+        .await
+        .map_err(token_lookup_error)?;
+
+    // El intento se cuenta antes de revisar el código, y un desafío reemplazado o agotado ya no
+    // sirve.
+    if !TotpModel::claim_attempt(member_id, nonce, &data.pool).await? {
+        return Err(invalid_token());
+    }
+
+    if totp.enabled_at.is_none() || !totp.verify(&body.code, &data.pool).await? {
+        if TotpModel::lock_if_exhausted(member_id, &data.pool).await? {
+            return Err(totp_locked());
+        }
+
+        return Err(invalid_code());
+    }
+
+    TotpModel::finish_challenge(member_id, &data.pool).await?;
+
+    let member = ClubMemberModel::get_one(member_id, &data.pool)
+        .await
+        .map_err(token_lookup_error)?;
+    let (token, expires_at) = data.token_signer.sign_session(&member.uuid);
+
+    Ok(BasicResponse::new(
+        "Se ha iniciado la sesión",
+        Some(SessionResponse {
+            token,
+            expires_at,
+            member: member.into(),
+        }),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{http::StatusCode, test, web, App};
+    use serde_json::{json, Value};
+
+    use crate::{
+        testing,
+        v1::{
+            self,
+            middleware::member_session::RedirectAllowlist,
+            models::{auth::Scope, magic_link::MagicLinkModel},
+        },
+    };
+
+    /// Única URL de redirección permitida en estas pruebas.
+    const REDIRECT_URI: &str = "https://exdev.cl/login";
+
+    /// Envía la petición a la API, con la llave indicada, y entrega el estado y el cuerpo de la
+    /// respuesta.
+    async fn send(
+        pool: &sqlx::SqlitePool,
+        key: &str,
+        req: test::TestRequest,
+    ) -> (StatusCode, Value) {
+        let mut state = testing::app_state(pool);
+        state.allowed_redirects = RedirectAllowlist::new(vec![REDIRECT_URI.parse().unwrap()]);
+        let service = test::init_service(
+            App::new()
+                .app_data(web::Data::new(state))
+                .service(v1::routes()),
+        )
+        .await;
+
+        let req = req
+            .insert_header(("Authorization", format!("Bearer {key}")))
+            .to_request();
+        let res = test::call_service(&service, req).await;
+        let status = res.status();
+
+        (status, test::read_body_json(res).await)
+    }
+
+    /// Guarda un enlace para el integrante, que expira en `ttl`, y entrega su token.
+    async fn create_link(
+        pool: &sqlx::SqlitePool,
+        member_id: &str,
+        ttl: chrono::Duration,
+    ) -> String {
+        let token = hex::encode(uuid::Uuid::new_v4().as_bytes());
+        let expires_at = chrono::Utc::now().naive_utc() + ttl;
+
+        MagicLinkModel::create(
+            &MagicLinkModel::hash_token(&token),
+            member_id,
+            expires_at,
+            pool,
+        )
+        .await
+        .unwrap();
+
+        token
+    }
+
+    /// Cantidad de enlaces guardados.
+    async fn link_count(pool: &sqlx::SqlitePool) -> i64 {
+        sqlx::query_scalar("SELECT COUNT(*) FROM magic_links")
+            .fetch_one(pool)
+            .await
+            .unwrap()
+    }
+
+    /// Un enlace entrega una sesión del integrante la primera vez, y después ya no sirve.
+    #[actix_web::test]
+    async fn magic_link_works_once() {
+        let pool = testing::pool().await;
+        let (_, key) = testing::create_app(&pool, &Scope::ALL).await;
+        let member_id = testing::create_member(&pool, "member").await;
+        let token = create_link(&pool, &member_id, chrono::Duration::minutes(15)).await;
+        let uri = format!("/v1/auth/magic-link/{token}");
+
+        let (status, body) = send(&pool, &key, test::TestRequest::get().uri(&uri)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["data"]["member"]["uuid"], member_id);
+        assert!(body["data"]["token"]
+            .as_str()
+            .is_some_and(|token| !token.is_empty()));
+
+        let (status, _) = send(&pool, &key, test::TestRequest::get().uri(&uri)).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+    }
+
+    /// Los enlaces expirados y los tokens que no son de ningún enlace no entregan sesión.
+    #[actix_web::test]
+    async fn expired_or_unknown_links_are_rejected() {
+        let pool = testing::pool().await;
+        let (_, key) = testing::create_app(&pool, &Scope::ALL).await;
+        let member_id = testing::create_member(&pool, "member").await;
+        let expired = create_link(&pool, &member_id, chrono::Duration::minutes(-1)).await;
+
+        for token in [expired, String::from("no-existe")] {
+            let req = test::TestRequest::get().uri(&format!("/v1/auth/magic-link/{token}"));
+            let (status, _) = send(&pool, &key, req).await;
+            assert_eq!(status, StatusCode::UNAUTHORIZED);
+        }
+    }
+
+    /// Solo se envían enlaces a URL permitidas, y la respuesta no revela si el email pertenece a
+    /// un integrante.
+    #[actix_web::test]
+    async fn magic_link_request_checks_redirect_and_hides_members() {
+        let pool = testing::pool().await;
+        let (_, key) = testing::create_app(&pool, &Scope::ALL).await;
+        let member_id = testing::create_member(&pool, "member").await;
+        sqlx::query("UPDATE club_members SET email = 'socio@exdev.cl' WHERE uuid = ?")
+            .bind(&member_id)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let request = |email: &str, redirect_uri: &str| {
+            test::TestRequest::post()
+                .uri("/v1/auth/magic-link")
+                .set_json(json!({ "email": email, "redirect_uri": redirect_uri }))
+        };
+
+        let req = request("socio@exdev.cl", "https://evil.example/login");
+        let (status, _) = send(&pool, &key, req).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(link_count(&pool).await, 0);
+
+        let (status, unknown) = send(&pool, &key, request("nadie@exdev.cl", REDIRECT_URI)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(link_count(&pool).await, 0);
+
+        let (status, known) = send(&pool, &key, request("socio@exdev.cl", REDIRECT_URI)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(known, unknown);
+        assert_eq!(link_count(&pool).await, 1);
+    }
+}
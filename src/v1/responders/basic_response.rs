@@ -1,12 +1,25 @@
 //! Respuesta simple de la API.
 //!
 //! La respuesta típica de la API es un estado de 200, un mensaje relacionado a la operación, y
-//! opcionalmente los datos que se hayan solicitado.
-use actix_web::{http::StatusCode, HttpRequest, HttpResponse, Responder};
+//! opcionalmente los datos que se hayan solicitado. Los listados paginados incluyen además los
+//! datos de su paginación.
+//!
+//! Algunas operaciones responden otro estado: las creaciones responden un 201 (Created) con la URL
+//! del nuevo recurso en el header `Location`, y las eliminaciones un 204 (No Content) sin cuerpo.
+use actix_web::{
+    http::{
+        header::{HeaderName, LOCATION},
+        StatusCode,
+    },
+    HttpRequest, HttpResponse, Responder,
+};
 use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::v1::schemas::pagination::Pagination;
 
 /// La respuesta típica de la API en forma de estructura.
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct BasicResponse<T: Serialize> {
     /// Estado HTTP. Necesariamente es un u16 debido a que actic_web::http::StatusCode no
     /// implementa Serialize o Deserialize de serde.
@@ -18,19 +31,60 @@ pub struct BasicResponse<T: Serialize> {
     /// Los datos solicitados por el cliente, en caso de que existan.
     #[serde(skip_serializing_if = "Option::is_none")]
     data: Option<T>,
+
+    /// Datos de paginación, si los datos son un listado paginado.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pagination: Option<Pagination>,
+
+    /// Headers extra de la respuesta, como el `Location` de un recurso recién creado.
+    #[serde(skip)]
+    headers: Vec<(HeaderName, String)>,
 }
 
 impl<T: Serialize> BasicResponse<T> {
     /// Crea una nueva respuesta típica
     pub fn new(message: impl Into<String>, data: Option<T>) -> BasicResponse<T> {
         BasicResponse {
-            // FIX: Quizás no siempre deba ser un 200. Por ejemplo, los handlers de las operaciones
-            // de creación quizás quieran devolver un estado 201 (Created).
             status: StatusCode::OK.into(),
             message: message.into(),
             data,
+            pagination: None,
+            headers: Vec::new(),
         }
     }
+
+    /// Crea la respuesta a la creación de un recurso.
+    ///
+    /// Tiene estado 201 (Created), incluye los datos del nuevo recurso, y su URL en el header
+    /// `Location`.
+    pub fn created(message: impl Into<String>, data: T, location: impl Into<String>) -> Self {
+        BasicResponse::new(message, Some(data))
+            .with_status(StatusCode::CREATED)
+            .with_header(LOCATION, location)
+    }
+
+    /// Crea una respuesta sin cuerpo, con estado 204 (No Content), como la de una eliminación.
+    pub fn no_content() -> BasicResponse<T> {
+        BasicResponse::new("", None).with_status(StatusCode::NO_CONTENT)
+    }
+
+    /// Cambia el estado HTTP de la respuesta.
+    pub fn with_status(mut self, status: StatusCode) -> BasicResponse<T> {
+        self.status = status.into();
+        self
+    }
+
+    /// Agrega un header a la respuesta.
+    pub fn with_header(mut self, name: HeaderName, value: impl Into<String>) -> BasicResponse<T> {
+        self.headers.push((name, value.into()));
+        self
+    }
+
+    /// Agrega los datos de paginación a la respuesta.
+    pub fn with_pagination(mut self, pagination: Pagination) -> BasicResponse<T> {
+        self.pagination = Some(pagination);
+        self
+    }
 }
 
 impl<T: Serialize> Responder for BasicResponse<T> {
@@ -38,8 +92,20 @@ impl<T: Serialize> Responder for BasicResponse<T> {
 
     /// Implementación para actix
     ///
-    /// Esto evita tener que explicitar la respuesta después de cada Handler.
+    /// Esto evita tener que explicitar la respuesta después de cada Handler. Las respuestas 204
+    /// (No Content) se envían sin cuerpo.
     fn respond_to(self, _req: &HttpRequest) -> HttpResponse<Self::Body> {
-        HttpResponse::Ok().json(self)
+        let status = StatusCode::from_u16(self.status).unwrap_or(StatusCode::OK);
+        let mut res = HttpResponse::build(status);
+
+        for (name, value) in &self.headers {
+            res.insert_header((name.clone(), value.as_str()));
+        }
+
+        if status == StatusCode::NO_CONTENT {
+            return res.finish();
+        }
+
+        res.json(self)
     }
 }
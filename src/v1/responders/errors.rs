@@ -1,64 +1,413 @@
 //! Respuestas de error de la API.
 //!
-//! Este módulo habilita el uso del operador `?` en la mayoría de handlers de la API. Es posible
-//! que a futuro se necesite crear otros tipos de respuestas de error conforme otras operaciones
-//! que sean implementadas puedan fallar.
+//! Este módulo habilita el uso del operador `?` en la mayoría de handlers de la API. Todos los
+//! errores, desde una llave inválida hasta un parche mal formado, son variantes de [`ApiError`] y
+//! responden el mismo cuerpo, [`ErrorResponse`].
 
 use actix_web::{
-    http::{header::ContentType, StatusCode},
+    error::UrlGenerationError,
+    http::{
+        header::{ContentType, RETRY_AFTER},
+        StatusCode,
+    },
     HttpResponse,
 };
-use serde_json::json;
+use chrono::NaiveDateTime;
+use serde::Serialize;
+use sqlx::error::ErrorKind;
+use std::sync::atomic::{AtomicBool, Ordering};
+use utoipa::ToSchema;
 
-/// Error de la base de datos.
+use crate::v1::middleware::rate_limit::RateLimitStatus;
+
+/// Si las respuestas de los errores internos incluyen su detalle (`debug`).
+///
+/// Se configura una sola vez al iniciar la API, con `ApiError::show_debug`.
+static SHOW_DEBUG: AtomicBool = AtomicBool::new(false);
+
+/// Error de la API.
 ///
-/// Se toman todos tal cual son recibidos.
+/// La mayoría de las variantes llevan el mensaje que se le muestra al cliente. Las que el cliente
+/// necesita distinguir además tienen un `code` (ver [`ApiError::code`]) y, si corresponde, sus
+/// propios datos, como el desafío de la verificación en dos pasos.
+///
+/// Los errores de la base de datos se convierten implícitamente (`?`): una fila no encontrada es
+/// un 404, una restricción `UNIQUE` violada es un 409, las demás restricciones son errores de
+/// validación, y todo lo demás es un error interno.
 #[derive(Debug)]
-pub struct DBError(sqlx::Error);
+pub enum ApiError {
+    /// La petición está mal formada (400), por ejemplo un cursor alterado.
+    BadRequest(String),
+    /// El recurso buscado no existe (404).
+    NotFound(String),
+    /// Los datos enviados no son válidos (422). Incluye los problemas de cada campo.
+    Validation(Vec<FieldError>),
+    /// La operación choca con el estado actual de los datos (409), por ejemplo un cambio de
+    /// estado no permitido.
+    Conflict(String),
+    /// La petición no trae credenciales válidas (401).
+    Unauthorized(String),
+    /// La llave de la API expiró en la fecha indicada (401, `expired_key`).
+    ExpiredKey(NaiveDateTime),
+    /// El integrante tiene activada la verificación en dos pasos (401, `totp_required`). Lleva el
+    /// desafío firmado que se debe presentar junto con el código.
+    TotpRequired(String),
+    /// Quien hace la petición no puede realizar la operación (403).
+    Forbidden(String),
+    /// El cuerpo no viene en un formato soportado (415).
+    UnsupportedMediaType(String),
+    /// Se superó el límite de peticiones por minuto (429, `rate_limited`).
+    TooManyRequests(RateLimitStatus),
+    /// Error interno (500). Incluye el detalle, que solo se le muestra al cliente si está
+    /// activado `API_DEBUG`.
+    Internal(String),
+    /// La funcionalidad no está configurada en este servidor (501).
+    NotImplemented(String),
+    /// Un servicio externo, como GitHub, falló (502). Incluye el mensaje para el cliente y el
+    /// detalle, que se trata igual que el de un error interno.
+    BadGateway {
+        /// Mensaje para el cliente.
+        message: String,
+        /// Detalle del error del servicio externo.
+        detail: String,
+    },
+}
 
-impl std::fmt::Display for DBError {
-    /// ¿Cómo imprimo el error en pantalla?
+impl ApiError {
+    /// Activa o desactiva el detalle de los errores internos en las respuestas.
     ///
-    /// Simplemente uso la implementación hecha por sqlx::Error.
+    /// El detalle puede incluir consultas y nombres de tablas, así que solo debería activarse
+    /// durante el desarrollo.
+    pub fn show_debug(enabled: bool) {
+        SHOW_DEBUG.store(enabled, Ordering::Relaxed);
+    }
+
+    /// Código del error, para los errores que el cliente necesita distinguir de otros con el
+    /// mismo estado HTTP.
+    pub fn code(&self) -> Option<&'static str> {
+        match self {
+            ApiError::ExpiredKey(_) => Some("expired_key"),
+            ApiError::TotpRequired(_) => Some("totp_required"),
+            ApiError::TooManyRequests(_) => Some("rate_limited"),
+            _ => None,
+        }
+    }
+
+    /// Detalle de los errores internos y de los servicios externos, que no se le muestra al
+    /// cliente salvo con `API_DEBUG`.
+    fn detail(&self) -> Option<&str> {
+        match self {
+            ApiError::Internal(detail) | ApiError::BadGateway { detail, .. } => Some(detail),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for ApiError {
+    /// Mensaje del error, tal como se le entrega al cliente.
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", self.0)
+        match self {
+            ApiError::BadRequest(message)
+            | ApiError::NotFound(message)
+            | ApiError::Conflict(message)
+            | ApiError::Unauthorized(message)
+            | ApiError::Forbidden(message)
+            | ApiError::UnsupportedMediaType(message)
+            | ApiError::NotImplemented(message)
+            | ApiError::BadGateway { message, .. } => write!(f, "{message}"),
+            ApiError::Validation(_) => write!(f, "Los datos enviados no son válidos"),
+            ApiError::ExpiredKey(_) => write!(f, "La llave de la API expiró"),
+            ApiError::TotpRequired(_) => {
+                write!(f, "Se requiere el código de verificación en dos pasos")
+            }
+            ApiError::TooManyRequests(status) => write!(
+                f,
+                "Se superó el límite de {} peticiones por minuto. Intenta de nuevo en {} segundos",
+                status.limit, status.reset
+            ),
+            ApiError::Internal(_) => write!(f, "Ocurrió un error interno"),
+        }
     }
 }
 
-impl From<sqlx::Error> for DBError {
-    /// Convertir desde sqlx::Error a DBError.
+impl From<sqlx::Error> for ApiError {
+    /// Convertir desde sqlx::Error a ApiError.
     ///
     /// Esto habilita las conversiones implícitas, y hace que te saltes llamados a la función
     /// `map_err`.
     fn from(value: sqlx::Error) -> Self {
-        Self(value)
+        let err = match value {
+            sqlx::Error::RowNotFound => {
+                return ApiError::NotFound(String::from("No se ha encontrado el recurso buscado"))
+            }
+            sqlx::Error::Database(err) => err,
+            err => return ApiError::Internal(err.to_string()),
+        };
+
+        // SQLite indica la columna en el mensaje, por ejemplo
+        // "UNIQUE constraint failed: club_members.email".
+        let column = err
+            .message()
+            .split_once(": ")
+            .and_then(|(_, columns)| columns.split(", ").next())
+            .and_then(|column| column.split_once('.'))
+            .map(|(_, column)| column.to_owned());
+
+        let field_error = |code, message: &str| {
+            ApiError::Validation(vec![FieldError {
+                field: column.clone().unwrap_or(String::from("body")),
+                code,
+                message: message.to_owned(),
+            }])
+        };
+
+        match err.kind() {
+            ErrorKind::UniqueViolation => ApiError::Conflict(match &column {
+                Some(column) => format!("Ya existe un recurso con el mismo {column}"),
+                None => String::from("Ya existe un recurso con los mismos datos"),
+            }),
+            ErrorKind::ForeignKeyViolation => field_error(
+                "invalid_reference",
+                "Hace referencia a un recurso que no existe",
+            ),
+            ErrorKind::NotNullViolation => field_error("required", "No puede estar vacío"),
+            ErrorKind::CheckViolation => field_error("invalid", "No es un valor válido"),
+            _ => ApiError::Internal(err.to_string()),
+        }
+    }
+}
+
+impl From<UrlGenerationError> for ApiError {
+    /// Convertir desde UrlGenerationError a ApiError.
+    ///
+    /// No poder armar la URL de una ruta de la API es un error de programación, así que se trata
+    /// como un error interno.
+    fn from(value: UrlGenerationError) -> Self {
+        ApiError::Internal(value.to_string())
     }
 }
 
-impl actix_web::error::ResponseError for DBError {
+impl actix_web::error::ResponseError for ApiError {
     /// Cuerpo de la respuesta del error.
     ///
-    /// En caso de que el error sea tipo RowNotFound, el mensaje es un poco distinto. Fuera de eso,
-    /// siempre se entrega el mismo error.
+    /// Los errores de validación incluyen la lista `errors` con los problemas de cada campo, los
+    /// que tienen código incluyen `code` y sus datos, y los internos incluyen `debug` solo si está
+    /// activado. El detalle de los errores internos y de los servicios externos siempre se
+    /// imprime en la consola.
     fn error_response(&self) -> actix_web::HttpResponse<actix_web::body::BoxBody> {
-        HttpResponse::build(self.status_code())
-            .insert_header(ContentType::json())
-            .json(match self.0 {
-                sqlx::Error::RowNotFound => {
-                    json!({"status": 404, "message": "No se ha encontrado el recurso buscado"})
-                }
-                _ => json!({"status": 500, "message": "Ocurrió un error en la bdd.", "debug": self.0.to_string()}),
-            })
+        let mut body = ErrorResponse {
+            status: self.status_code().as_u16(),
+            message: self.to_string(),
+            code: self.code(),
+            errors: None,
+            expired_at: None,
+            challenge: None,
+            debug: None,
+        };
+
+        match self {
+            ApiError::Validation(errors) => body.errors = Some(errors.to_owned()),
+            ApiError::ExpiredKey(expired_at) => body.expired_at = Some(*expired_at),
+            ApiError::TotpRequired(challenge) => body.challenge = Some(challenge.to_owned()),
+            _ => {}
+        }
+
+        if let Some(detail) = self.detail() {
+            eprintln!("Error interno: {detail}");
+
+            if SHOW_DEBUG.load(Ordering::Relaxed) {
+                body.debug = Some(detail.to_owned());
+            }
+        }
+
+        let mut res = HttpResponse::build(self.status_code());
+        res.insert_header(ContentType::json());
+
+        if let ApiError::TooManyRequests(status) = self {
+            res.insert_header((RETRY_AFTER, status.reset));
+        }
+
+        let mut res = res.json(body);
+
+        if let ApiError::TooManyRequests(status) = self {
+            status.apply_headers(res.headers_mut());
+        }
+
+        res
     }
 
     /// Código de error.
-    ///
-    /// Se va por defecto a una respuesta 500, pero existe la excepción de buscar datos que no
-    /// existen, por lo que se entrega un error 404.
     fn status_code(&self) -> actix_web::http::StatusCode {
-        match self.0 {
-            sqlx::Error::RowNotFound => StatusCode::NOT_FOUND,
-            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        match self {
+            ApiError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            ApiError::NotFound(_) => StatusCode::NOT_FOUND,
+            ApiError::Validation(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            ApiError::Conflict(_) => StatusCode::CONFLICT,
+            ApiError::Unauthorized(_) | ApiError::ExpiredKey(_) | ApiError::TotpRequired(_) => {
+                StatusCode::UNAUTHORIZED
+            }
+            ApiError::Forbidden(_) => StatusCode::FORBIDDEN,
+            ApiError::UnsupportedMediaType(_) => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            ApiError::TooManyRequests(_) => StatusCode::TOO_MANY_REQUESTS,
+            ApiError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            ApiError::NotImplemented(_) => StatusCode::NOT_IMPLEMENTED,
+            ApiError::BadGateway { .. } => StatusCode::BAD_GATEWAY,
         }
     }
 }
+
+/// Cuerpo de las respuestas de error.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ErrorResponse {
+    /// Estado HTTP del error.
+    pub status: u16,
+
+    /// Descripción del error.
+    pub message: String,
+
+    /// Código del error, para los errores que el cliente necesita distinguir (`expired_key`,
+    /// `totp_required`, `rate_limited`).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code: Option<&'static str>,
+
+    /// Problemas de cada campo, en los errores de validación.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub errors: Option<Vec<FieldError>>,
+
+    /// Fecha (UTC) en que expiró la llave, en los errores `expired_key`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expired_at: Option<NaiveDateTime>,
+
+    /// Desafío que se envía junto al código de verificación, en los errores `totp_required`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub challenge: Option<String>,
+
+    /// Detalle de un error interno, solo si se activó `API_DEBUG`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub debug: Option<String>,
+}
+
+/// Problema en un campo de los datos enviados por el cliente.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct FieldError {
+    /// Campo con el problema, tal como se llama en el JSON o en los parámetros de la URL.
+    pub field: String,
+    /// Código del problema, pensado para que lo revisen los clientes (`required`, `too_long`,
+    /// `invalid_email`, ...).
+    pub code: &'static str,
+    /// Descripción del problema, pensada para mostrarla a una persona.
+    pub message: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{body::to_bytes, http::StatusCode, ResponseError};
+    use serde_json::Value;
+    use sqlx::SqlitePool;
+
+    use super::ApiError;
+    use crate::testing;
+
+    /// Ejecuta una consulta que debería fallar, y entrega su error convertido.
+    async fn fail(pool: &SqlitePool, query: &str) -> ApiError {
+        sqlx::query(query)
+            .execute(pool)
+            .await
+            .expect_err("La consulta debería fallar")
+            .into()
+    }
+
+    /// Estado y cuerpo de la respuesta de un error.
+    async fn response(err: &ApiError) -> (StatusCode, Value) {
+        let res = err.error_response();
+        let status = res.status();
+        let body = to_bytes(res.into_body()).await.unwrap();
+
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    /// Una restricción `UNIQUE` violada es un conflicto que indica la columna.
+    #[actix_web::test]
+    async fn unique_violation_is_a_conflict() {
+        let pool = testing::pool().await;
+        sqlx::query("INSERT INTO club_members (uuid, name, email) VALUES ('a', 'A', 'a@b.cl')")
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let err = fail(
+            &pool,
+            "INSERT INTO club_members (uuid, name, email) VALUES ('b', 'B', 'A@b.cl')",
+        )
+        .await;
+        let (status, body) = response(&err).await;
+
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert_eq!(body["message"], "Ya existe un recurso con el mismo email");
+    }
+
+    /// Las demás restricciones son errores de validación en la columna que las viola.
+    #[actix_web::test]
+    async fn other_constraints_are_validation_errors() {
+        let pool = testing::pool().await;
+
+        for (query, field, code) in [
+            (
+                "INSERT INTO project_involvement (project_uuid, club_member_uuid) VALUES ('p', 'm')",
+                "body",
+                "invalid_reference",
+            ),
+            (
+                "INSERT INTO club_members (uuid, name) VALUES ('a', NULL)",
+                "name",
+                "required",
+            ),
+            (
+                "INSERT INTO club_members (uuid, name, state) VALUES ('a', 'A', 'Perdido')",
+                "body",
+                "invalid",
+            ),
+        ] {
+            let err = fail(&pool, query).await;
+            let (status, body) = response(&err).await;
+
+            assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY, "{query}");
+            assert_eq!(body["errors"][0]["field"], field, "{query}");
+            assert_eq!(body["errors"][0]["code"], code, "{query}");
+        }
+    }
+
+    /// Una fila no encontrada es un 404.
+    #[actix_web::test]
+    async fn row_not_found_is_not_found() {
+        let pool = testing::pool().await;
+        let err: ApiError = sqlx::query_scalar::<_, String>("SELECT name FROM club_members")
+            .fetch_one(&pool)
+            .await
+            .unwrap_err()
+            .into();
+
+        assert_eq!(response(&err).await.0, StatusCode::NOT_FOUND);
+    }
+
+    /// Los demás errores de la base de datos son internos, y su detalle solo se muestra si se
+    /// activó con `show_debug`.
+    #[actix_web::test]
+    async fn internal_detail_is_hidden_by_default() {
+        let pool = testing::pool().await;
+        let err = fail(&pool, "SELECT * FROM tabla_que_no_existe").await;
+
+        let (status, body) = response(&err).await;
+        assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(body["message"], "Ocurrió un error interno");
+        assert!(body.get("debug").is_none());
+
+        ApiError::show_debug(true);
+        let (_, body) = response(&err).await;
+        ApiError::show_debug(false);
+
+        assert!(body["debug"]
+            .as_str()
+            .is_some_and(|debug| debug.contains("tabla_que_no_existe")));
+    }
+}
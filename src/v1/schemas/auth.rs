@@ -3,10 +3,24 @@
 //! Estos esquemas se refieren a la creación y actualización de nuevas aplicaciones dentro de la
 //! API.
 
+use chrono::NaiveDateTime;
 use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::v1::{
+    models::auth::{AppModel, Scope},
+    responders::errors::ApiError,
+    schemas::validation::{Validate, Validator, MAX_NAME_LENGTH, MAX_TEXT_LENGTH},
+};
+
+/// Límite de peticiones por minuto más alto que se le puede dar a una aplicación.
+const MAX_RATE_LIMIT: i64 = 100_000;
+
+/// Periodo de gracia más largo (un año, en segundos) al regenerar una llave.
+const MAX_GRACE_PERIOD: i64 = 365 * 24 * 60 * 60;
 
 /// Estructura de creación de una nueva aplicación.
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
 pub struct CreateAppSchema {
     /// Nombre de la aplicación
     pub name: String,
@@ -14,12 +28,35 @@ pub struct CreateAppSchema {
     /// Descripción opcional de la aplicación.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub description: Option<String>,
+
+    /// Permisos que se le otorgan a la aplicación. Si no se entregan, la aplicación solo podrá
+    /// leer datos.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scopes: Option<Vec<Scope>>,
+
+    /// Fecha (UTC) desde la cual la llave deja de funcionar. Si no se entrega, la llave no expira.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<NaiveDateTime>,
+
+    /// Peticiones por minuto que puede hacer la aplicación. Si no se entrega, se usa el límite por
+    /// defecto de la API.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rate_limit: Option<i64>,
+}
+
+impl Validate for CreateAppSchema {
+    /// Revisa los datos de la nueva aplicación. La fecha de expiración, si viene, debe ser futura.
+    fn validate(&self) -> Result<(), ApiError> {
+        validate_app(&self.name, self.description.as_deref(), self.rate_limit)
+            .future("expires_at", self.expires_at)
+            .finish()
+    }
 }
 
 /// Estructura de actualización de una aplicación.
 ///
 /// Notar dos cosas: no se cambia la UUID de la aplicación, y todos los valores son opcionales.
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
 pub struct UpdateAppSchema {
     /// Nuevo nombre de la aplicación.
     pub name: Option<String>,
@@ -27,4 +64,198 @@ pub struct UpdateAppSchema {
     /// Nueva descripción de la aplicación.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub description: Option<String>,
+
+    /// Nuevo límite de peticiones por minuto de la aplicación.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rate_limit: Option<i64>,
+}
+
+/// Campos editables de una aplicación, sobre los que se aplican los `PATCH`.
+///
+/// A diferencia de `UpdateAppSchema`, representa todos los datos de la aplicación: un valor en
+/// `None` significa que la aplicación no lo tiene, por ejemplo que usa el límite de peticiones
+/// por defecto de la API.
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct AppDocument {
+    /// Nombre de la aplicación.
+    pub name: String,
+
+    /// Descripción de la aplicación.
+    pub description: Option<String>,
+
+    /// Límite de peticiones por minuto de la aplicación.
+    pub rate_limit: Option<i64>,
+}
+
+impl From<&AppModel> for AppDocument {
+    /// Obtiene el documento con los datos actuales de una aplicación.
+    fn from(app: &AppModel) -> AppDocument {
+        AppDocument {
+            name: app.name.to_owned(),
+            description: app.description.to_owned(),
+            rate_limit: app.rate_limit,
+        }
+    }
+}
+
+impl AppDocument {
+    /// Aplica los cambios de un `PUT`, manteniendo los valores que no vienen en él.
+    pub fn apply(self, changes: &UpdateAppSchema) -> AppDocument {
+        AppDocument {
+            name: changes.name.to_owned().unwrap_or(self.name),
+            description: changes.description.to_owned().or(self.description),
+            rate_limit: changes.rate_limit.or(self.rate_limit),
+        }
+    }
+}
+
+impl Validate for AppDocument {
+    /// Revisa los datos de la aplicación.
+    fn validate(&self) -> Result<(), ApiError> {
+        validate_app(&self.name, self.description.as_deref(), self.rate_limit).finish()
+    }
+}
+
+/// Revisa el nombre, la descripción y el límite de peticiones de una aplicación.
+fn validate_app(name: &str, description: Option<&str>, rate_limit: Option<i64>) -> Validator {
+    Validator::new()
+        .required("name", Some(name))
+        .max_length("name", Some(name), MAX_NAME_LENGTH)
+        .max_length("description", description, MAX_TEXT_LENGTH)
+        .range("rate_limit", rate_limit, 1, MAX_RATE_LIMIT)
+}
+
+/// Estructura para regenerar la llave de una aplicación.
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct RegenerateAppSchema {
+    /// Segundos durante los cuales la llave anterior sigue siendo válida.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub grace_period: Option<i64>,
+}
+
+impl Validate for RegenerateAppSchema {
+    /// Revisa que el periodo de gracia no sea negativo ni exagerado.
+    fn validate(&self) -> Result<(), ApiError> {
+        Validator::new()
+            .range("grace_period", self.grace_period, 0, MAX_GRACE_PERIOD)
+            .finish()
+    }
+}
+
+/// Filtros para listar las aplicaciones.
+#[derive(Serialize, Deserialize, Debug, Default, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct AppListQuery {
+    /// Si es `true`, también se listan las aplicaciones revocadas.
+    #[serde(default)]
+    pub revoked: bool,
+
+    /// Si se entrega, solo se listan las aplicaciones que no han usado su llave en esta cantidad
+    /// de días.
+    pub unused_for_days: Option<i64>,
+}
+
+impl Validate for AppListQuery {
+    /// Revisa que la cantidad de días sin uso no sea negativa ni exagerada.
+    fn validate(&self) -> Result<(), ApiError> {
+        Validator::new()
+            .range("unused_for_days", self.unused_for_days, 0, 36_500)
+            .finish()
+    }
+}
+
+/// Estructura para enviar una aplicación como respuesta.
+///
+/// Nunca incluye el hash de la llave de la aplicación.
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct AppResponse {
+    /// ID único de la aplicación.
+    uuid: String,
+
+    /// Nombre de la aplicación.
+    name: String,
+
+    /// Descripción de la aplicación.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    description: Option<String>,
+
+    /// Permisos de la aplicación.
+    scopes: Vec<Scope>,
+
+    /// Fecha de creación de la aplicación.
+    created_at: NaiveDateTime,
+
+    /// Fecha de la última modificación de la aplicación.
+    updated_at: NaiveDateTime,
+
+    /// Fecha en que la aplicación fue revocada, si es que lo fue.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    revoked_at: Option<NaiveDateTime>,
+
+    /// Fecha desde la cual la llave deja de funcionar.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    expires_at: Option<NaiveDateTime>,
+
+    /// Última vez que se usó la llave, con hasta un minuto de atraso.
+    last_used_at: Option<NaiveDateTime>,
+
+    /// IP desde la cual se usó la llave por última vez.
+    last_used_ip: Option<String>,
+
+    /// Peticiones por minuto que puede hacer la aplicación, si tiene un límite propio.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    rate_limit: Option<i64>,
+}
+
+impl AppResponse {
+    /// Crea una nueva estructura de respuesta a partir de un modelo de la bdd y sus permisos.
+    pub fn new(app: &AppModel, scopes: Vec<Scope>) -> AppResponse {
+        AppResponse {
+            uuid: app.uuid.to_owned(),
+            name: app.name.to_owned(),
+            description: app.description.to_owned(),
+            scopes,
+            created_at: app.created_at,
+            updated_at: app.updated_at,
+            revoked_at: app.deleted_at,
+            expires_at: app.expires_at,
+            last_used_at: app.last_used_at,
+            last_used_ip: app.last_used_ip.to_owned(),
+            rate_limit: app.rate_limit,
+        }
+    }
+}
+
+/// Llave de una aplicación recién registrada.
+///
+/// Es la única vez que se muestra la llave.
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct NewAppResponse {
+    /// Llave de la aplicación, que se debe enviar en el header `Authorization`.
+    pub api_key: String,
+
+    /// ID único de la aplicación.
+    pub app_id: String,
+
+    /// Permisos de la aplicación.
+    pub scopes: Vec<Scope>,
+
+    /// Fecha desde la cual la llave deja de funcionar, si tiene una.
+    pub expires_at: Option<NaiveDateTime>,
+}
+
+/// Nueva llave de una aplicación.
+///
+/// Es la única vez que se muestra la llave.
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct NewKeyResponse {
+    /// Nueva llave de la aplicación.
+    pub api_key: String,
+
+    /// ID único de la aplicación.
+    pub app_id: String,
+
+    /// Fecha hasta la cual la llave anterior sigue funcionando, si tiene un periodo de gracia.
+    pub previous_key_expires_at: Option<NaiveDateTime>,
 }
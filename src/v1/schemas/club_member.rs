@@ -5,14 +5,22 @@
 //! de forma de no enviar nada que sea 'peligroso', o qué sé yo.
 
 use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
 
-use crate::v1::models::club_member::ClubMemberModel;
+use crate::v1::{
+    models::club_member::{ClubMemberModel, MemberState, Role},
+    responders::errors::ApiError,
+    schemas::{
+        pagination::{PageRequest, SortOrder},
+        validation::{Validate, Validator, MAX_NAME_LENGTH, MAX_SEARCH_LENGTH},
+    },
+};
 
 /// Estructura para la creación de un nuevo integrante.
 ///
 /// El único campo requerido es el del nombre, pues todos los demás tienen valores por defecto y/u
 /// opcionales.
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
 pub struct CreateMemberSchema {
     /// Nombre del integrante a agregar.
     pub name: String,
@@ -30,14 +38,25 @@ pub struct CreateMemberSchema {
     pub github: Option<String>,
 }
 
+impl Validate for CreateMemberSchema {
+    /// Revisa los datos personales del nuevo integrante.
+    fn validate(&self) -> Result<(), ApiError> {
+        validate_profile(
+            &self.name,
+            self.birthday.as_deref(),
+            self.email.as_deref(),
+            self.github.as_deref(),
+        )
+    }
+}
+
 /// Estructura para actualizar los datos de un nuevo integrante.
 ///
 /// Todos sus valores son opcionales, y en caso de no estar presentes se opta por el valor anterior
-/// que estuviera presente en la fila de ese integrante.
-#[derive(Serialize, Deserialize, Debug)]
+/// que estuviera presente en la fila de ese integrante. Para borrar un valor, como el cumpleaños,
+/// se debe usar `PATCH` con un `MemberDocument`.
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
 pub struct UpdateMemberSchema {
-    // WARN: ¿Qué pasa si quiero eliminar algún valor que haya sido guardado en la bdd?.
-    // Por ejemplo, algún integrante quiere quitar su cumpleaños de la bdd.
     /// Nombre del integrante.
     pub name: Option<String>,
     /// Cumpleaños del integrante.
@@ -46,15 +65,246 @@ pub struct UpdateMemberSchema {
     pub email: Option<String>,
     /// Github del integrante.
     pub github: Option<String>,
+    /// Estado del integrante. Solo se permiten los cambios de estado descritos en `MemberState`.
+    pub state: Option<MemberState>,
+    /// Rol del integrante. Solo un administrador puede cambiarlo.
+    pub role: Option<Role>,
+}
+
+/// Estructura para que un integrante actualice su propio perfil.
+///
+/// Solo incluye los datos personales del integrante: su estado y su rol los maneja la directiva.
+/// Al igual que en `UpdateMemberSchema`, los valores ausentes se mantienen.
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct UpdateMeSchema {
+    /// Nombre del integrante.
+    pub name: Option<String>,
+    /// Cumpleaños del integrante.
+    pub birthday: Option<String>,
+    /// Email del integrante.
+    pub email: Option<String>,
+    /// Github del integrante.
+    pub github: Option<String>,
+}
+
+impl From<UpdateMeSchema> for UpdateMemberSchema {
+    /// Convierte los cambios al perfil propio en una actualización que no toca el estado ni el rol.
+    fn from(value: UpdateMeSchema) -> UpdateMemberSchema {
+        UpdateMemberSchema {
+            name: value.name,
+            birthday: value.birthday,
+            email: value.email,
+            github: value.github,
+            state: None,
+            role: None,
+        }
+    }
+}
+
+/// Campos editables de un integrante, sobre los que se aplican los `PATCH`.
+///
+/// A diferencia de `UpdateMemberSchema`, representa todos los datos del integrante: un valor en
+/// `None` significa que el integrante no tiene ese dato.
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct MemberDocument {
+    /// Nombre del integrante.
+    pub name: String,
+    /// Cumpleaños del integrante.
+    pub birthday: Option<String>,
+    /// Email del integrante.
+    pub email: Option<String>,
+    /// Github del integrante.
+    pub github: Option<String>,
     /// Estado del integrante.
-    pub state: Option<String>,
+    pub state: MemberState,
+    /// Rol del integrante.
+    pub role: Role,
+}
+
+impl From<&ClubMemberModel> for MemberDocument {
+    /// Obtiene el documento con los datos actuales de un integrante.
+    fn from(member: &ClubMemberModel) -> MemberDocument {
+        MemberDocument {
+            name: member.name.to_owned(),
+            birthday: member.birthday.to_owned(),
+            email: member.email.to_owned(),
+            github: member.github.to_owned(),
+            state: member.state(),
+            role: member.role(),
+        }
+    }
+}
+
+impl MemberDocument {
+    /// Aplica los cambios de un `PUT`, manteniendo los valores que no vienen en él.
+    pub fn apply(self, changes: UpdateMemberSchema) -> MemberDocument {
+        MemberDocument {
+            name: changes.name.unwrap_or(self.name),
+            birthday: changes.birthday.or(self.birthday),
+            email: changes.email.or(self.email),
+            github: changes.github.or(self.github),
+            state: changes.state.unwrap_or(self.state),
+            role: changes.role.unwrap_or(self.role),
+        }
+    }
+
+    /// Reemplaza los datos personales por los de un perfil.
+    pub fn with_profile(self, profile: ProfileDocument) -> MemberDocument {
+        MemberDocument {
+            name: profile.name,
+            birthday: profile.birthday,
+            email: profile.email,
+            github: profile.github,
+            ..self
+        }
+    }
+}
+
+impl Validate for MemberDocument {
+    /// Revisa los datos personales del integrante. El estado y el rol ya vienen validados por sus
+    /// tipos.
+    fn validate(&self) -> Result<(), ApiError> {
+        validate_profile(
+            &self.name,
+            self.birthday.as_deref(),
+            self.email.as_deref(),
+            self.github.as_deref(),
+        )
+    }
+}
+
+/// Revisa los datos personales de un integrante.
+///
+/// El cumpleaños debe ser una fecha `AAAA-MM-DD` pasada, y el Github un nombre de usuario válido
+/// (sin la URL del perfil).
+fn validate_profile(
+    name: &str,
+    birthday: Option<&str>,
+    email: Option<&str>,
+    github: Option<&str>,
+) -> Result<(), ApiError> {
+    Validator::new()
+        .required("name", Some(name))
+        .max_length("name", Some(name), MAX_NAME_LENGTH)
+        .past_date("birthday", birthday)
+        .email("email", email)
+        .github("github", github)
+        .finish()
+}
+
+/// Datos personales de un integrante, sobre los que aplica sus `PATCH` a `/v1/me`.
+///
+/// No incluye el estado ni el rol, así que un parche que intente cambiarlos se rechaza.
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct ProfileDocument {
+    /// Nombre del integrante.
+    pub name: String,
+    /// Cumpleaños del integrante.
+    pub birthday: Option<String>,
+    /// Email del integrante.
+    pub email: Option<String>,
+    /// Github del integrante.
+    pub github: Option<String>,
+}
+
+impl From<&ClubMemberModel> for ProfileDocument {
+    /// Obtiene el perfil actual de un integrante.
+    fn from(member: &ClubMemberModel) -> ProfileDocument {
+        ProfileDocument {
+            name: member.name.to_owned(),
+            birthday: member.birthday.to_owned(),
+            email: member.email.to_owned(),
+            github: member.github.to_owned(),
+        }
+    }
+}
+
+/// Columnas por las que se puede ordenar el listado de integrantes.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum MemberSort {
+    /// Por nombre.
+    #[default]
+    Name,
+    /// Por fecha de creación.
+    CreatedAt,
+    /// Por fecha de la última modificación.
+    UpdatedAt,
+}
+
+impl MemberSort {
+    /// Columna de la tabla `club_members` por la que se ordena.
+    pub fn column(&self) -> &'static str {
+        match self {
+            MemberSort::Name => "name",
+            MemberSort::CreatedAt => "created_at",
+            MemberSort::UpdatedAt => "updated_at",
+        }
+    }
+
+    /// Valor de la columna de orden de un integrante, tal como se guarda en la bdd.
+    pub fn value_of(&self, member: &ClubMemberModel) -> String {
+        match self {
+            MemberSort::Name => member.name.to_owned(),
+            MemberSort::CreatedAt => member.created_at.format("%Y-%m-%d %H:%M:%S%.f").to_string(),
+            MemberSort::UpdatedAt => member.updated_at.format("%Y-%m-%d %H:%M:%S%.f").to_string(),
+        }
+    }
+}
+
+/// Parámetros del listado de integrantes.
+///
+/// Todos son opcionales. Sin parámetros, se entregan los primeros 50 integrantes por nombre.
+#[derive(Serialize, Deserialize, Debug, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct MemberListQuery {
+    /// Solo integrantes en este estado.
+    pub state: Option<MemberState>,
+
+    /// Texto a buscar en el nombre, email o Github del integrante.
+    pub search: Option<String>,
+
+    /// Columna por la que se ordena.
+    #[serde(default)]
+    pub sort: MemberSort,
+
+    /// Dirección del orden.
+    #[serde(default)]
+    pub order: SortOrder,
+
+    /// Cantidad de integrantes por página.
+    pub limit: Option<i64>,
+
+    /// Integrantes que se saltan.
+    pub offset: Option<i64>,
+
+    /// Cursor entregado por la API en una página anterior.
+    pub cursor: Option<String>,
+}
+
+impl Validate for MemberListQuery {
+    /// Revisa el texto a buscar; la paginación se ajusta sola a sus límites.
+    fn validate(&self) -> Result<(), ApiError> {
+        Validator::new()
+            .max_length("search", self.search.as_deref(), MAX_SEARCH_LENGTH)
+            .finish()
+    }
+}
+
+impl MemberListQuery {
+    /// Página pedida por el cliente.
+    pub fn page(&self) -> Result<PageRequest, ApiError> {
+        PageRequest::new(self.limit, self.offset, self.cursor.as_deref(), self.order)
+    }
 }
 
 /// Estructura para enviar a un integrante como respuesta.
 ///
 /// La mayor diferencia con los esquemas anteriores es la presencia de la UUID y el estado del
 /// integrante como valores requeridos para instanciarlos.
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
 pub struct ClubMemberResponse {
     /// ID único del integrante.
     uuid: String,
@@ -76,9 +326,17 @@ pub struct ClubMemberResponse {
     /// Github del integrante.
     #[serde(skip_serializing_if = "Option::is_none")]
     github: Option<String>,
+
+    /// Rol del integrante dentro del club.
+    role: String,
 }
 
 impl ClubMemberResponse {
+    /// UUID del integrante.
+    pub fn uuid(&self) -> &str {
+        &self.uuid
+    }
+
     /// Crea una nueva estructura de respuesta a partir de un modelo de la bdd.
     pub fn new(cmm: &ClubMemberModel) -> ClubMemberResponse {
         ClubMemberResponse {
@@ -88,13 +346,14 @@ impl ClubMemberResponse {
             email: cmm.email.to_owned(),
             github: cmm.github.to_owned(),
             state: cmm.state.to_owned(),
+            role: cmm.role.to_owned(),
         }
     }
 
     /// Convierte un slice (o vector) de modelos en un vector de respuestas
     ///
     /// # Ejemplo:
-    /// ```
+    /// ```ignore
     /// // Vector de Modelos
     /// let modelos = ClubMemberModel::get_all(&data.pool);
     ///
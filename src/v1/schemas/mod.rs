@@ -5,4 +5,8 @@
 
 pub mod auth;
 pub mod club_member;
+pub mod pagination;
+pub mod patch;
 pub mod project;
+pub mod session;
+pub mod validation;
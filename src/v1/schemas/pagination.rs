@@ -0,0 +1,494 @@
+//! Esquemas de paginación de los listados.
+//!
+//! Los listados aceptan dos formas de paginar:
+//! - Con `limit` y `offset`, como en cualquier consulta SQL.
+//! - Con `limit` y `cursor`, donde el cursor es un valor opaco que entrega la misma API. Los
+//!   cursores no se desordenan si se agregan o eliminan filas mientras se recorre el listado.
+//!
+//! En ambos casos la respuesta incluye el total de resultados, los cursores de la página
+//! siguiente y anterior, y los enlaces para pedirlas.
+
+use actix_web::HttpRequest;
+use serde::{Deserialize, Serialize};
+use sqlx::{QueryBuilder, Sqlite};
+use utoipa::ToSchema;
+
+use crate::v1::responders::errors::ApiError;
+
+/// Cantidad de resultados por página si la petición no indica otra.
+pub const DEFAULT_LIMIT: i64 = 50;
+
+/// Cantidad máxima de resultados por página.
+pub const MAX_LIMIT: i64 = 200;
+
+/// Separador entre los valores de un cursor.
+const CURSOR_SEPARATOR: char = '\u{1f}';
+
+/// Dirección del orden de un listado.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    /// De menor a mayor.
+    #[default]
+    Asc,
+    /// De mayor a menor.
+    Desc,
+}
+
+impl SortOrder {
+    /// Palabra clave SQL de la dirección.
+    fn as_sql(&self) -> &'static str {
+        match self {
+            SortOrder::Asc => "ASC",
+            SortOrder::Desc => "DESC",
+        }
+    }
+
+    /// La dirección contraria.
+    fn reverse(&self) -> SortOrder {
+        match self {
+            SortOrder::Asc => SortOrder::Desc,
+            SortOrder::Desc => SortOrder::Asc,
+        }
+    }
+}
+
+/// Posición dentro de un listado.
+///
+/// Guarda el valor de la columna de orden y la UUID de una fila, y si se quieren las filas que
+/// vienen después o antes de ella.
+pub struct Cursor {
+    /// `true` si se quieren las filas que vienen después.
+    forward: bool,
+    /// Valor de la columna de orden de la fila.
+    value: String,
+    /// UUID de la fila, para desempatar filas con el mismo valor.
+    uuid: String,
+}
+
+/// Error de los cursores que no fueron entregados por la API o fueron alterados.
+fn invalid_cursor() -> ApiError {
+    ApiError::BadRequest(String::from("El cursor no es válido"))
+}
+
+impl Cursor {
+    /// Codifica el cursor como un valor opaco.
+    fn encode(&self) -> String {
+        let direction = if self.forward { "n" } else { "p" };
+
+        hex::encode(format!(
+            "{direction}{CURSOR_SEPARATOR}{}{CURSOR_SEPARATOR}{}",
+            self.value, self.uuid
+        ))
+    }
+
+    /// Decodifica un cursor entregado por la API.
+    fn decode(cursor: &str) -> Result<Cursor, ApiError> {
+        let cursor = hex::decode(cursor)
+            .ok()
+            .and_then(|bytes| String::from_utf8(bytes).ok())
+            .ok_or_else(invalid_cursor)?;
+
+        let mut parts = cursor.splitn(3, CURSOR_SEPARATOR);
+        let (Some(direction), Some(value), Some(uuid)) = (parts.next(), parts.next(), parts.next())
+        else {
+            return Err(invalid_cursor());
+        };
+
+        let forward = match direction {
+            "n" => true,
+            "p" => false,
+            _ => return Err(invalid_cursor()),
+        };
+
+        Ok(Cursor {
+            forward,
+            value: value.to_owned(),
+            uuid: uuid.to_owned(),
+        })
+    }
+}
+
+/// Página pedida por el cliente.
+pub struct PageRequest {
+    /// Cantidad de resultados de la página.
+    limit: i64,
+    /// Filas que se saltan, si se pagina con `offset`.
+    offset: i64,
+    /// Posición desde la que se lista, si se pagina con `cursor`.
+    cursor: Option<Cursor>,
+    /// Dirección del orden pedida por el cliente.
+    order: SortOrder,
+}
+
+/// Resultado de una consulta paginada.
+pub struct PageResult<T> {
+    /// Las filas de la página, ya en el orden pedido.
+    pub items: Vec<T>,
+    /// Total de filas que cumplen los filtros, sin paginar.
+    pub total: i64,
+    /// Si quedaban más filas en la dirección en que se listó.
+    has_more: bool,
+}
+
+/// Datos de paginación que acompañan a un listado en la respuesta.
+#[derive(Serialize, Debug, ToSchema)]
+pub struct Pagination {
+    /// Total de resultados que cumplen los filtros.
+    pub total: i64,
+
+    /// Cantidad máxima de resultados de la página.
+    pub limit: i64,
+
+    /// Filas saltadas, si se paginó con `offset`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub offset: Option<i64>,
+
+    /// Cursor de la página siguiente, si existe.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<String>,
+
+    /// Cursor de la página anterior, si existe.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub prev_cursor: Option<String>,
+
+    /// Enlace a la página siguiente, si existe.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next: Option<String>,
+
+    /// Enlace a la página anterior, si existe.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub prev: Option<String>,
+}
+
+impl PageRequest {
+    /// Crea la página a partir de los parámetros de la petición.
+    ///
+    /// El límite se ajusta entre 1 y `MAX_LIMIT`. Si viene un cursor, se ignora el `offset`.
+    pub fn new(
+        limit: Option<i64>,
+        offset: Option<i64>,
+        cursor: Option<&str>,
+        order: SortOrder,
+    ) -> Result<PageRequest, ApiError> {
+        Ok(PageRequest {
+            limit: limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT),
+            offset: offset.unwrap_or(0).max(0),
+            cursor: cursor.map(Cursor::decode).transpose()?,
+            order,
+        })
+    }
+
+    /// Agrega a la consulta la condición del cursor, el orden y el límite.
+    ///
+    /// `column` es la columna de orden y debe venir de la API, nunca del cliente. La consulta ya
+    /// debe tener una cláusula `WHERE`, pues la condición del cursor se agrega con `AND`. Se pide
+    /// una fila de más para saber si quedan más resultados.
+    pub fn push_sql(&self, query: &mut QueryBuilder<Sqlite>, table: &str, column: &str) {
+        let order = match &self.cursor {
+            Some(cursor) if !cursor.forward => self.order.reverse(),
+            _ => self.order,
+        };
+
+        if let Some(cursor) = &self.cursor {
+            let operator = match order {
+                SortOrder::Asc => ">",
+                SortOrder::Desc => "<",
+            };
+
+            query
+                .push(format!(
+                    " AND ({table}.{column}, {table}.uuid) {operator} ("
+                ))
+                .push_bind(cursor.value.clone())
+                .push(", ")
+                .push_bind(cursor.uuid.clone())
+                .push(")");
+        }
+
+        query.push(format!(
+            " ORDER BY {table}.{column} {0}, {table}.uuid {0}",
+            order.as_sql()
+        ));
+        query.push(" LIMIT ").push_bind(self.limit + 1);
+
+        if self.cursor.is_none() {
+            query.push(" OFFSET ").push_bind(self.offset);
+        }
+    }
+
+    /// Ordena el resultado de la consulta hecha con `push_sql`.
+    pub fn finish<T>(&self, mut rows: Vec<T>, total: i64) -> PageResult<T> {
+        let has_more = rows.len() as i64 > self.limit;
+        rows.truncate(self.limit as usize);
+
+        // Al listar hacia atrás, la consulta entrega las filas en el orden contrario.
+        if matches!(&self.cursor, Some(cursor) if !cursor.forward) {
+            rows.reverse();
+        }
+
+        PageResult {
+            items: rows,
+            total,
+            has_more,
+        }
+    }
+
+    /// Calcula los datos de paginación de un resultado.
+    ///
+    /// `position` entrega el valor de la columna de orden y la UUID de una fila.
+    pub fn pagination<T>(
+        &self,
+        result: &PageResult<T>,
+        position: impl Fn(&T) -> (String, String),
+        req: &HttpRequest,
+    ) -> Pagination {
+        let (has_next, has_prev) = match &self.cursor {
+            None => (
+                self.offset + (result.items.len() as i64) < result.total,
+                self.offset > 0,
+            ),
+            Some(cursor) if cursor.forward => (result.has_more, true),
+            Some(_) => (true, result.has_more),
+        };
+
+        let cursor_at = |item: Option<&T>, forward: bool| {
+            item.map(|item| {
+                let (value, uuid) = position(item);
+                Cursor {
+                    forward,
+                    value,
+                    uuid,
+                }
+                .encode()
+            })
+        };
+
+        let next_cursor = cursor_at(result.items.last(), true).filter(|_| has_next);
+        let prev_cursor = cursor_at(result.items.first(), false).filter(|_| has_prev);
+
+        let (next, prev) = match &self.cursor {
+            None => (
+                has_next.then(|| link(req, "offset", &(self.offset + self.limit).to_string())),
+                has_prev.then(|| {
+                    link(req, "offset", &(self.offset - self.limit).max(0).to_string())
+                }),
+            ),
+            Some(_) => (
+                next_cursor.as_ref().map(|cursor| link(req, "cursor", cursor)),
+                prev_cursor.as_ref().map(|cursor| link(req, "cursor", cursor)),
+            ),
+        };
+
+        Pagination {
+            total: result.total,
+            limit: self.limit,
+            offset: self.cursor.is_none().then_some(self.offset),
+            next_cursor,
+            prev_cursor,
+            next,
+            prev,
+        }
+    }
+}
+
+/// Enlace a la misma ruta de la petición, cambiando un parámetro de paginación.
+///
+/// Se mantienen los demás parámetros (filtros, orden y límite), y se quitan `offset` y `cursor`
+/// antes de agregar el nuevo.
+fn link(req: &HttpRequest, key: &str, value: &str) -> String {
+    let mut url = reqwest::Url::parse("http://localhost").expect("La URL base es válida");
+    url.set_path(req.path());
+    url.set_query(Some(req.query_string()));
+
+    let pairs: Vec<(String, String)> = url
+        .query_pairs()
+        .filter(|(name, _)| name != "offset" && name != "cursor")
+        .map(|(name, value)| (name.into_owned(), value.into_owned()))
+        .collect();
+
+    url.query_pairs_mut()
+        .clear()
+        .extend_pairs(pairs)
+        .append_pair(key, value);
+
+    format!("{}?{}", url.path(), url.query().unwrap_or_default())
+}
+
+/// Escapa los comodines de `LIKE` en un texto de búsqueda.
+///
+/// Se usa junto con `ESCAPE '\'` en la consulta, para que `%` y `_` se busquen literalmente.
+pub fn like_pattern(search: &str) -> String {
+    let escaped = search
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_");
+
+    format!("%{escaped}%")
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::test::TestRequest;
+
+    use super::{Cursor, PageRequest, SortOrder};
+
+    /// Filas de prueba, como `(valor de la columna de orden, UUID)`.
+    fn rows(count: usize) -> Vec<(String, String)> {
+        (0..count)
+            .map(|index| (format!("Integrante {index}"), format!("uuid-{index}")))
+            .collect()
+    }
+
+    /// Posición de una fila de prueba.
+    fn position(row: &(String, String)) -> (String, String) {
+        row.clone()
+    }
+
+    /// Un cursor se decodifica con los mismos valores con los que se codificó, aunque el valor
+    /// tenga caracteres que no son ASCII.
+    #[test]
+    fn cursor_round_trip() {
+        for forward in [true, false] {
+            let cursor = Cursor {
+                forward,
+                value: String::from("Ñandú, 2024-01-01 12:00:00"),
+                uuid: String::from("6f1c2a4e-8a43-4a43-9d0e-3f2b1a0c9d8e"),
+            };
+
+            let decoded = Cursor::decode(&cursor.encode()).expect("El cursor debería ser válido");
+
+            assert_eq!(decoded.forward, forward);
+            assert_eq!(decoded.value, cursor.value);
+            assert_eq!(decoded.uuid, cursor.uuid);
+        }
+    }
+
+    /// Los cursores que no entregó la API se rechazan.
+    #[test]
+    fn invalid_cursors_are_rejected() {
+        let cursors = [
+            String::from("no es hex"),
+            hex::encode("n\u{1f}solo-valor"),
+            hex::encode("x\u{1f}valor\u{1f}uuid"),
+            hex::encode([0xff, 0xfe]),
+        ];
+
+        for cursor in cursors {
+            assert!(
+                Cursor::decode(&cursor).is_err(),
+                "{cursor} debería ser inválido"
+            );
+        }
+    }
+
+    /// Con `offset`, los enlaces cambian el `offset` y mantienen los demás parámetros.
+    #[test]
+    fn offset_links_keep_the_other_parameters() {
+        let req =
+            TestRequest::with_uri("/v1/members?state=Active&offset=2&limit=2").to_http_request();
+        let page = PageRequest::new(Some(2), Some(2), None, SortOrder::Asc).unwrap();
+        let result = page.finish(rows(3), 5);
+
+        let pagination = page.pagination(&result, position, &req);
+
+        assert_eq!(pagination.total, 5);
+        assert_eq!(pagination.offset, Some(2));
+        assert_eq!(
+            pagination.next.as_deref(),
+            Some("/v1/members?state=Active&limit=2&offset=4")
+        );
+        assert_eq!(
+            pagination.prev.as_deref(),
+            Some("/v1/members?state=Active&limit=2&offset=0")
+        );
+    }
+
+    /// La primera página no tiene enlace a la anterior, y la última no tiene a la siguiente.
+    #[test]
+    fn offset_links_stop_at_the_ends() {
+        let req = TestRequest::with_uri("/v1/projects?limit=2").to_http_request();
+
+        let first = PageRequest::new(Some(2), None, None, SortOrder::Asc).unwrap();
+        let pagination = first.pagination(&first.finish(rows(3), 3), position, &req);
+        assert!(pagination.prev.is_none());
+        assert_eq!(
+            pagination.next.as_deref(),
+            Some("/v1/projects?limit=2&offset=2")
+        );
+
+        let last = PageRequest::new(Some(2), Some(2), None, SortOrder::Asc).unwrap();
+        let pagination = last.pagination(&last.finish(rows(1), 3), position, &req);
+        assert!(pagination.next.is_none());
+        assert!(pagination.next_cursor.is_none());
+        assert_eq!(
+            pagination.prev.as_deref(),
+            Some("/v1/projects?limit=2&offset=0")
+        );
+    }
+
+    /// Con `cursor`, los enlaces llevan el cursor de la primera y la última fila de la página, y
+    /// quitan el `offset`.
+    #[test]
+    fn cursor_links_point_to_the_page_edges() {
+        let start = Cursor {
+            forward: true,
+            value: String::from("Integrante 0"),
+            uuid: String::from("uuid-0"),
+        }
+        .encode();
+        let req = TestRequest::with_uri(&format!("/v1/members?sort=name&offset=10&cursor={start}"))
+            .to_http_request();
+        let page = PageRequest::new(Some(2), Some(10), Some(&start), SortOrder::Asc).unwrap();
+        let result = page.finish(rows(3), 10);
+
+        let pagination = page.pagination(&result, position, &req);
+
+        assert_eq!(pagination.offset, None);
+
+        let next = Cursor::decode(pagination.next_cursor.as_deref().unwrap()).unwrap();
+        assert!(next.forward);
+        assert_eq!(next.uuid, "uuid-1");
+
+        let prev = Cursor::decode(pagination.prev_cursor.as_deref().unwrap()).unwrap();
+        assert!(!prev.forward);
+        assert_eq!(prev.uuid, "uuid-0");
+
+        assert_eq!(
+            pagination.next,
+            Some(format!(
+                "/v1/members?sort=name&cursor={}",
+                pagination.next_cursor.unwrap()
+            ))
+        );
+        assert_eq!(
+            pagination.prev,
+            Some(format!(
+                "/v1/members?sort=name&cursor={}",
+                pagination.prev_cursor.unwrap()
+            ))
+        );
+    }
+
+    /// Al listar hacia atrás, las filas quedan en el orden pedido y, si no quedan más, no hay
+    /// página anterior.
+    #[test]
+    fn backward_cursor_reverses_rows() {
+        let start = Cursor {
+            forward: false,
+            value: String::from("Integrante 9"),
+            uuid: String::from("uuid-9"),
+        }
+        .encode();
+        let req = TestRequest::with_uri("/v1/members").to_http_request();
+        let page = PageRequest::new(Some(2), None, Some(&start), SortOrder::Asc).unwrap();
+
+        // La consulta entrega las filas al revés, sin una fila de más.
+        let result = page.finish(vec![rows(2)[1].clone(), rows(2)[0].clone()], 10);
+        let pagination = page.pagination(&result, position, &req);
+
+        assert_eq!(result.items, rows(2));
+        assert!(pagination.prev.is_none());
+        assert!(pagination.next.is_some());
+    }
+}
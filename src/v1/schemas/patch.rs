@@ -0,0 +1,255 @@
+//! Actualizaciones parciales de los recursos (`PATCH`).
+//!
+//! Los `PATCH` aceptan dos formatos, según el tipo de contenido de la petición:
+//!
+//! - JSON Merge Patch (RFC 7396), con `application/merge-patch+json`: el cuerpo es un objeto con
+//!   los campos que se quieren cambiar. Un campo ausente mantiene su valor, un campo en `null` se
+//!   borra, y cualquier otro valor reemplaza al anterior. Así, por ejemplo, un integrante puede
+//!   quitar su cumpleaños con `{"birthday": null}`.
+//! - JSON Patch (RFC 6902), con `application/json-patch+json`: el cuerpo es una lista de
+//!   operaciones (`add`, `remove`, `replace`, `move`, `copy` y `test`), por ejemplo
+//!   `[{"op": "add", "path": "/involved/-", "value": "<uuid>"}]`. Las operaciones se aplican
+//!   todas o ninguna.
+//!
+//! Cada recurso define un "documento" con sus campos editables. El parche se aplica sobre el
+//! documento actual del recurso y el resultado se vuelve a leer como documento, de forma que un
+//! parche que deja un campo obligatorio en `null`, cambia el tipo de un campo o agrega campos
+//! desconocidos se rechaza completo.
+
+use actix_web::{http::header::CONTENT_TYPE, HttpRequest};
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+
+use crate::v1::responders::errors::{ApiError, FieldError};
+
+/// Tipo de contenido de un JSON Merge Patch.
+pub const MERGE_PATCH: &str = "application/merge-patch+json";
+
+/// Tipo de contenido de un JSON Patch.
+pub const JSON_PATCH: &str = "application/json-patch+json";
+
+/// Aplica un JSON Merge Patch sobre el documento de un recurso.
+///
+/// Se acepta el cuerpo con tipo `application/merge-patch+json` o `application/json`.
+pub fn merge_patch<T: Serialize + DeserializeOwned>(
+    document: &T,
+    req: &HttpRequest,
+    patch: &Value,
+) -> Result<T, ApiError> {
+    match content_type(req).as_str() {
+        MERGE_PATCH | "application/json" => {}
+        _ => return Err(unsupported_media_type(MERGE_PATCH)),
+    }
+
+    let mut document = to_value(document)?;
+    json_patch::merge(&mut document, patch);
+
+    from_value(document)
+}
+
+/// Aplica un JSON Merge Patch o un JSON Patch sobre el documento de un recurso, según el tipo de
+/// contenido de la petición.
+pub fn apply_patch<T: Serialize + DeserializeOwned>(
+    document: &T,
+    req: &HttpRequest,
+    patch: &Value,
+) -> Result<T, ApiError> {
+    if content_type(req) != JSON_PATCH {
+        return merge_patch(document, req, patch).map_err(|err| match err {
+            ApiError::UnsupportedMediaType(_) => {
+                unsupported_media_type("application/merge-patch+json o application/json-patch+json")
+            }
+            err => err,
+        });
+    }
+
+    let operations: json_patch::Patch = serde_json::from_value(patch.clone())
+        .map_err(|err| ApiError::BadRequest(format!("El parche no es válido: {err}")))?;
+
+    let mut document = to_value(document)?;
+    json_patch::patch(&mut document, &operations).map_err(|err| match err.kind {
+        json_patch::PatchErrorKind::TestFailed => ApiError::Conflict(format!(
+            "No se cumplió la operación test número {} del parche",
+            err.operation
+        )),
+        _ => invalid_result(err),
+    })?;
+
+    from_value(document)
+}
+
+/// Tipo de contenido de la petición, sin parámetros y en minúsculas.
+fn content_type(req: &HttpRequest) -> String {
+    req.headers()
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.split(';').next())
+        .map(|value| value.trim().to_ascii_lowercase())
+        .unwrap_or_default()
+}
+
+/// Convierte un documento en JSON.
+fn to_value<T: Serialize>(document: &T) -> Result<Value, ApiError> {
+    serde_json::to_value(document).map_err(|err| ApiError::Internal(err.to_string()))
+}
+
+/// Lee el documento resultante de aplicar un parche.
+fn from_value<T: DeserializeOwned>(document: Value) -> Result<T, ApiError> {
+    serde_json::from_value(document).map_err(invalid_result)
+}
+
+/// Error de los parches enviados con un tipo de contenido que no se acepta.
+fn unsupported_media_type(accepted: &str) -> ApiError {
+    ApiError::UnsupportedMediaType(format!("El parche debe enviarse como {accepted}"))
+}
+
+/// Error de validación de un parche que no se puede aplicar o que genera un recurso inválido,
+/// por ejemplo porque borra un campo obligatorio.
+fn invalid_result(err: impl std::fmt::Display) -> ApiError {
+    ApiError::Validation(vec![FieldError {
+        field: String::from("body"),
+        code: "invalid_patch",
+        message: format!("El parche genera un recurso inválido: {err}"),
+    }])
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{http::header::CONTENT_TYPE, test::TestRequest, HttpRequest};
+    use serde::{Deserialize, Serialize};
+    use serde_json::json;
+
+    use super::{apply_patch, JSON_PATCH, MERGE_PATCH};
+    use crate::v1::responders::errors::ApiError;
+
+    /// Documento de prueba, con un campo obligatorio, uno opcional y una lista.
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    #[serde(deny_unknown_fields)]
+    struct Document {
+        name: String,
+        birthday: Option<String>,
+        involved: Vec<String>,
+    }
+
+    /// Documento actual sobre el que se aplican los parches.
+    fn document() -> Document {
+        Document {
+            name: String::from("Integrante"),
+            birthday: Some(String::from("2000-01-01")),
+            involved: vec![String::from("uuid-1")],
+        }
+    }
+
+    /// Petición con el tipo de contenido indicado.
+    fn request(content_type: &str) -> HttpRequest {
+        TestRequest::default()
+            .insert_header((CONTENT_TYPE, content_type))
+            .to_http_request()
+    }
+
+    /// Un JSON Patch válido aplica todas sus operaciones, en orden.
+    #[test]
+    fn json_patch_applies_every_operation() {
+        let original = document();
+        let patch = json!([
+            {"op": "test", "path": "/name", "value": "Integrante"},
+            {"op": "replace", "path": "/name", "value": "Otro nombre"},
+            {"op": "add", "path": "/involved/-", "value": "uuid-2"},
+            {"op": "remove", "path": "/birthday"},
+            {"op": "add", "path": "/birthday", "value": null},
+        ]);
+
+        let patched = apply_patch(&original, &request(JSON_PATCH), &patch).unwrap();
+
+        assert_eq!(
+            patched,
+            Document {
+                name: String::from("Otro nombre"),
+                birthday: None,
+                involved: vec![String::from("uuid-1"), String::from("uuid-2")],
+            }
+        );
+        assert_eq!(original, document());
+    }
+
+    /// Si falla una operación `test` a mitad del parche, no se aplica ninguna operación y se
+    /// responde con un conflicto que indica la operación.
+    #[test]
+    fn failed_test_operation_discards_the_whole_patch() {
+        let original = document();
+        let patch = json!([
+            {"op": "replace", "path": "/name", "value": "Otro nombre"},
+            {"op": "test", "path": "/birthday", "value": "1999-12-31"},
+            {"op": "add", "path": "/involved/-", "value": "uuid-2"},
+        ]);
+
+        match apply_patch(&original, &request(JSON_PATCH), &patch) {
+            Err(ApiError::Conflict(message)) => assert!(message.contains("número 1"), "{message}"),
+            other => panic!("Se esperaba un conflicto, pero se obtuvo {other:?}"),
+        }
+        assert_eq!(original, document());
+    }
+
+    /// Si una operación no se puede aplicar, o el resultado no es un documento válido, el parche
+    /// se rechaza completo como un error de validación.
+    #[test]
+    fn invalid_json_patches_are_rejected() {
+        let original = document();
+
+        for patch in [
+            json!([
+                {"op": "replace", "path": "/name", "value": "Otro nombre"},
+                {"op": "remove", "path": "/involved/5"},
+            ]),
+            json!([
+                {"op": "add", "path": "/involved/-", "value": "uuid-2"},
+                {"op": "remove", "path": "/name"},
+            ]),
+            json!([{"op": "add", "path": "/nickname", "value": "Apodo"}]),
+        ] {
+            match apply_patch(&original, &request(JSON_PATCH), &patch) {
+                Err(ApiError::Validation(errors)) => assert_eq!(errors[0].code, "invalid_patch"),
+                other => panic!("Se esperaba un error de validación, pero se obtuvo {other:?}"),
+            }
+        }
+        assert_eq!(original, document());
+    }
+
+    /// Un JSON Merge Patch que borra un campo obligatorio o agrega campos desconocidos se rechaza
+    /// completo, aunque traiga otros cambios válidos.
+    #[test]
+    fn invalid_merge_patches_are_rejected() {
+        let original = document();
+
+        for patch in [
+            json!({"name": null, "birthday": "2001-02-03"}),
+            json!({"birthday": null, "nickname": "Apodo"}),
+            json!({"involved": "uuid-2"}),
+        ] {
+            match apply_patch(&original, &request(MERGE_PATCH), &patch) {
+                Err(ApiError::Validation(errors)) => assert_eq!(errors[0].code, "invalid_patch"),
+                other => panic!("Se esperaba un error de validación, pero se obtuvo {other:?}"),
+            }
+        }
+        assert_eq!(original, document());
+    }
+
+    /// Los parches mal formados o con un tipo de contenido desconocido se rechazan antes de
+    /// aplicarse.
+    #[test]
+    fn malformed_patches_are_rejected() {
+        let original = document();
+
+        let patch = json!([{"op": "rename", "path": "/name"}]);
+        assert!(matches!(
+            apply_patch(&original, &request(JSON_PATCH), &patch),
+            Err(ApiError::BadRequest(_))
+        ));
+
+        let patch = json!({"name": "Otro nombre"});
+        assert!(matches!(
+            apply_patch(&original, &request("text/plain"), &patch),
+            Err(ApiError::UnsupportedMediaType(_))
+        ));
+    }
+}
@@ -1,12 +1,24 @@
+use chrono::NaiveDate;
 use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
 
-use crate::v1::models::project::ProjectState;
+use crate::v1::{
+    models::project::{ProjectModel, ProjectState},
+    responders::errors::ApiError,
+    schemas::{
+        pagination::{PageRequest, SortOrder},
+        validation::{
+            Validate, Validator, MAX_DESCRIPTION_LENGTH, MAX_NAME_LENGTH, MAX_SEARCH_LENGTH,
+            MAX_TEXT_LENGTH,
+        },
+    },
+};
 
 /// Esquema de creación de proyectos
 ///
 /// Solo el nombre del proyecto es necesario. Los involucrados pueden ser entregados via un arreglo
 /// de UUID. Si no se puede parsear la UUID, la API arrojará un error (no panic).
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, ToSchema)]
 pub struct CreateProjectSchema {
     /// Nombre del proyecto.
     pub name: String,
@@ -19,11 +31,18 @@ pub struct CreateProjectSchema {
     pub involved: Option<Vec<uuid::Uuid>>,
 }
 
+impl Validate for CreateProjectSchema {
+    /// Revisa el nombre y la descripción del nuevo proyecto.
+    fn validate(&self) -> Result<(), ApiError> {
+        validate_project(&self.name, self.description.as_deref())
+    }
+}
+
 /// Esquema de actualización del proyecto
 ///
 /// Todos los valores son opcionales. Esta es la forma de actualizar el estado de un proyecto; esto
 /// se hace via una String que sea textual un valor del Enum ProjectState.
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, ToSchema)]
 pub struct UpdateProjectSchema {
     /// Nuevo nombre del proyecto.
     pub name: Option<String>,
@@ -34,15 +53,86 @@ pub struct UpdateProjectSchema {
     /// Cómo alterar la lista de involucrados en el proyecto.
     pub involved: Option<UpdateInvolvedSchema>,
 
-    /// Nuevo estado del proyecto.
+    /// Nuevo estado del proyecto. Solo se permiten los cambios del `ProjectWorkflow` de la API.
     pub state: Option<ProjectState>,
+
+    /// Motivo del cambio de estado, que queda en el historial del proyecto.
+    pub reason: Option<String>,
+
+    /// Archiva (`true`) o desarchiva (`false`) el proyecto.
+    pub archived: Option<bool>,
+}
+
+impl Validate for UpdateProjectSchema {
+    /// Revisa el motivo del cambio de estado. Los demás campos se revisan en el `ProjectDocument`
+    /// que resulta de aplicar los cambios.
+    fn validate(&self) -> Result<(), ApiError> {
+        Validator::new()
+            .max_length("reason", self.reason.as_deref(), MAX_TEXT_LENGTH)
+            .finish()
+    }
+}
+
+/// Campos editables de un proyecto, sobre los que se aplican los `PATCH`.
+///
+/// A diferencia de `UpdateProjectSchema`, representa todos los datos del proyecto: una
+/// descripción en `None` significa que el proyecto no tiene descripción.
+#[derive(Serialize, Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct ProjectDocument {
+    /// Nombre del proyecto.
+    pub name: String,
+
+    /// Descripción del proyecto.
+    pub description: Option<String>,
+
+    /// Estado del proyecto.
+    pub state: ProjectState,
+
+    /// Si el proyecto está archivado.
+    pub archived: bool,
+
+    /// UUID de los involucrados en el proyecto.
+    pub involved: Vec<uuid::Uuid>,
+}
+
+impl ProjectDocument {
+    /// Aplica los cambios de un `PUT`, manteniendo los valores que no vienen en él.
+    pub fn apply(self, changes: &UpdateProjectSchema) -> ProjectDocument {
+        ProjectDocument {
+            name: changes.name.to_owned().unwrap_or(self.name),
+            description: changes.description.to_owned().or(self.description),
+            state: changes.state.unwrap_or(self.state),
+            archived: changes.archived.unwrap_or(self.archived),
+            involved: match &changes.involved {
+                Some(involved) => involved.apply(self.involved),
+                None => self.involved,
+            },
+        }
+    }
+}
+
+impl Validate for ProjectDocument {
+    /// Revisa el nombre y la descripción del proyecto.
+    fn validate(&self) -> Result<(), ApiError> {
+        validate_project(&self.name, self.description.as_deref())
+    }
+}
+
+/// Revisa el nombre y la descripción de un proyecto.
+fn validate_project(name: &str, description: Option<&str>) -> Result<(), ApiError> {
+    Validator::new()
+        .required("name", Some(name))
+        .max_length("name", Some(name), MAX_NAME_LENGTH)
+        .max_length("description", description, MAX_DESCRIPTION_LENGTH)
+        .finish()
 }
 
 /// Esquema de actualización de involucrados.
 ///
 /// Permite agregar y eliminar involucrados vía un objeto durante la llamada de actualización del
 /// proyecto.
-#[derive(Serialize, Deserialize, Default)]
+#[derive(Serialize, Deserialize, Default, ToSchema)]
 pub struct UpdateInvolvedSchema {
     /// Lista de UUID a agregar.
     pub add: Option<Vec<uuid::Uuid>>,
@@ -50,3 +140,154 @@ pub struct UpdateInvolvedSchema {
     /// Lista de UUID a eliminar.
     pub remove: Option<Vec<uuid::Uuid>>,
 }
+
+impl UpdateInvolvedSchema {
+    /// Agrega y quita involucrados de una lista, sin repetir a ninguno.
+    pub fn apply(&self, mut involved: Vec<uuid::Uuid>) -> Vec<uuid::Uuid> {
+        for id in self.add.iter().flatten() {
+            if !involved.contains(id) {
+                involved.push(*id);
+            }
+        }
+
+        if let Some(remove) = &self.remove {
+            involved.retain(|id| !remove.contains(id));
+        }
+
+        involved
+    }
+}
+
+/// Columnas por las que se puede ordenar el listado de proyectos.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ProjectSort {
+    /// Por nombre.
+    #[default]
+    Name,
+    /// Por fecha de creación.
+    CreatedAt,
+    /// Por fecha de la última modificación.
+    UpdatedAt,
+}
+
+impl ProjectSort {
+    /// Columna de la tabla `projects` por la que se ordena.
+    pub fn column(&self) -> &'static str {
+        match self {
+            ProjectSort::Name => "name",
+            ProjectSort::CreatedAt => "created_at",
+            ProjectSort::UpdatedAt => "updated_at",
+        }
+    }
+
+    /// Valor de la columna de orden de un proyecto, tal como se guarda en la bdd.
+    pub fn value_of(&self, project: &ProjectModel) -> String {
+        match self {
+            ProjectSort::Name => project.name.to_owned(),
+            ProjectSort::CreatedAt => project
+                .created_at
+                .format("%Y-%m-%d %H:%M:%S%.f")
+                .to_string(),
+            ProjectSort::UpdatedAt => project
+                .updated_at
+                .format("%Y-%m-%d %H:%M:%S%.f")
+                .to_string(),
+        }
+    }
+}
+
+/// Parámetros del listado de proyectos.
+///
+/// Todos son opcionales. Sin parámetros, se entregan los primeros 50 proyectos no archivados por
+/// nombre y sin sus involucrados.
+#[derive(Serialize, Deserialize, Debug, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ProjectListQuery {
+    /// Solo proyectos en este estado.
+    pub state: Option<ProjectState>,
+
+    /// Solo proyectos en los que participa el integrante con esta UUID.
+    pub member: Option<uuid::Uuid>,
+
+    /// Solo proyectos creados desde esta fecha, inclusive (`AAAA-MM-DD`).
+    pub created_from: Option<NaiveDate>,
+
+    /// Solo proyectos creados hasta esta fecha, inclusive (`AAAA-MM-DD`).
+    pub created_to: Option<NaiveDate>,
+
+    /// Texto a buscar en el nombre o la descripción del proyecto.
+    pub search: Option<String>,
+
+    /// Si se incluyen los proyectos archivados.
+    #[serde(default)]
+    pub include_archived: bool,
+
+    /// Columna por la que se ordena.
+    #[serde(default)]
+    pub sort: ProjectSort,
+
+    /// Dirección del orden.
+    #[serde(default)]
+    pub order: SortOrder,
+
+    /// Cantidad de proyectos por página.
+    pub limit: Option<i64>,
+
+    /// Proyectos que se saltan.
+    pub offset: Option<i64>,
+
+    /// Cursor entregado por la API en una página anterior.
+    pub cursor: Option<String>,
+
+    /// Datos relacionados a incluir, separados por comas. Por ahora solo existe `involved`.
+    pub include: Option<String>,
+}
+
+impl Validate for ProjectListQuery {
+    /// Revisa el texto a buscar y que el rango de fechas no esté invertido.
+    fn validate(&self) -> Result<(), ApiError> {
+        let validator =
+            Validator::new().max_length("search", self.search.as_deref(), MAX_SEARCH_LENGTH);
+
+        match (self.created_from, self.created_to) {
+            (Some(from), Some(to)) if from > to => validator.error(
+                "created_to",
+                "invalid_range",
+                "No puede ser anterior a created_from",
+            ),
+            _ => validator,
+        }
+        .finish()
+    }
+}
+
+impl ProjectListQuery {
+    /// Página pedida por el cliente.
+    ///
+    /// Falla también si se pide incluir algo que no existe.
+    pub fn page(&self) -> Result<PageRequest, ApiError> {
+        if let Some(unknown) = self.includes().find(|include| *include != "involved") {
+            return Err(ApiError::BadRequest(format!(
+                "No se puede incluir {unknown} en este listado"
+            )));
+        }
+
+        PageRequest::new(self.limit, self.offset, self.cursor.as_deref(), self.order)
+    }
+
+    /// Si se pidió incluir a los involucrados de cada proyecto.
+    pub fn includes_involved(&self) -> bool {
+        self.includes().any(|include| include == "involved")
+    }
+
+    /// Datos relacionados que se pidieron incluir.
+    fn includes(&self) -> impl Iterator<Item = &str> {
+        self.include
+            .as_deref()
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|include| !include.is_empty())
+    }
+}
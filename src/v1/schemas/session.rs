@@ -0,0 +1,114 @@
+//! Esquemas del inicio de sesión de integrantes.
+//!
+//! Estos esquemas se refieren a los pasos para que un integrante del club inicie sesión, y al
+//! token de sesión que recibe al terminar.
+
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::v1::{
+    responders::errors::ApiError,
+    schemas::{
+        club_member::ClubMemberResponse,
+        validation::{Validate, Validator},
+    },
+};
+
+/// Parámetros para iniciar sesión con GitHub.
+#[derive(Serialize, Deserialize, Debug, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct GithubAuthorizeQuery {
+    /// URL de la App cliente a la que GitHub redirigirá al integrante.
+    pub redirect_uri: String,
+}
+
+/// Datos que la App cliente recibe de GitHub y envía a la API para terminar el inicio de sesión.
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct GithubCallbackSchema {
+    /// Código entregado por GitHub.
+    pub code: String,
+
+    /// El mismo `state` que entregó la API al iniciar el flujo.
+    pub state: String,
+
+    /// La misma URL de redirección usada al iniciar el flujo.
+    pub redirect_uri: String,
+}
+
+/// Datos para pedir un enlace de inicio de sesión por correo.
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct MagicLinkSchema {
+    /// Email del integrante.
+    pub email: String,
+
+    /// URL de la App cliente a la que llevará el enlace. La API le agrega el parámetro `token`.
+    pub redirect_uri: String,
+}
+
+impl Validate for MagicLinkSchema {
+    /// Revisa el email y que venga una URL de redirección.
+    fn validate(&self) -> Result<(), ApiError> {
+        Validator::new()
+            .email("email", Some(&self.email))
+            .required("redirect_uri", Some(&self.redirect_uri))
+            .finish()
+    }
+}
+
+/// Código de verificación en dos pasos, o uno de recuperación.
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct TotpCodeSchema {
+    /// Código de 6 dígitos de la aplicación de autenticación, o un código de recuperación.
+    pub code: String,
+}
+
+/// Datos para terminar un inicio de sesión que pidió la verificación en dos pasos.
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct TotpLoginSchema {
+    /// Desafío entregado por la API junto con el error `totp_required`.
+    pub challenge: String,
+
+    /// Código de 6 dígitos de la aplicación de autenticación, o un código de recuperación.
+    pub code: String,
+}
+
+/// Secreto para activar la verificación en dos pasos.
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct TotpEnrolmentResponse {
+    /// Secreto en base32, para ingresarlo a mano en la aplicación de autenticación.
+    pub secret: String,
+
+    /// URI `otpauth://` con el secreto, normalmente mostrada como código QR.
+    pub otpauth_uri: String,
+}
+
+/// Códigos de recuperación de la verificación en dos pasos.
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct RecoveryCodesResponse {
+    /// Códigos de un solo uso. Solo se muestran esta vez.
+    pub recovery_codes: Vec<String>,
+}
+
+/// Respuesta al iniciar el flujo de OAuth.
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct AuthorizeResponse {
+    /// URL a la que se debe enviar al integrante.
+    pub url: String,
+
+    /// Valor que se debe devolver a la API junto con el código.
+    pub state: String,
+}
+
+/// Sesión de un integrante.
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct SessionResponse {
+    /// Token de sesión, que se debe enviar en el header `X-Session-Token`.
+    pub token: String,
+
+    /// Fecha (UTC) en que expira la sesión.
+    pub expires_at: NaiveDateTime,
+
+    /// El integrante dueño de la sesión.
+    pub member: ClubMemberResponse,
+}
@@ -0,0 +1,536 @@
+//! Validación de los datos enviados por los clientes.
+//!
+//! Los esquemas que reciben datos implementan `Validate`, que revisa todos sus campos y junta los
+//! problemas encontrados en un `ApiError::Validation`. Así, el cliente recibe un error 422 con la
+//! lista completa de campos a corregir, en vez de un error de la bdd o de que se guarden datos
+//! inválidos:
+//!
+//! ```json
+//! {
+//!     "status": 422,
+//!     "message": "Los datos enviados no son válidos",
+//!     "errors": [
+//!         {"field": "email", "code": "invalid_email", "message": "No es un email válido"}
+//!     ]
+//! }
+//! ```
+//!
+//! En las actualizaciones (`PUT` y `PATCH`) se valida el documento que resulta de aplicar los
+//! cambios, de forma que ambos métodos siguen exactamente las mismas reglas.
+//!
+//! Los cuerpos y parámetros que ni siquiera se pueden leer (un JSON mal formado, un campo
+//! obligatorio que falta, un estado que no existe) generan el mismo tipo de error, gracias a
+//! `json_error_handler` y `query_error_handler`.
+
+use actix_web::{
+    error::{JsonPayloadError, QueryPayloadError},
+    HttpRequest,
+};
+use chrono::{NaiveDate, NaiveDateTime};
+
+use crate::v1::responders::errors::{ApiError, FieldError};
+
+/// Largo máximo de un nombre, ya sea de un integrante, un proyecto o una aplicación.
+pub const MAX_NAME_LENGTH: usize = 100;
+
+/// Largo máximo de la descripción de un proyecto.
+pub const MAX_DESCRIPTION_LENGTH: usize = 10_000;
+
+/// Largo máximo de los textos cortos, como la descripción de una aplicación o el motivo de un
+/// cambio de estado.
+pub const MAX_TEXT_LENGTH: usize = 1_000;
+
+/// Largo máximo del texto a buscar en un listado.
+pub const MAX_SEARCH_LENGTH: usize = 100;
+
+/// Datos que se pueden validar antes de usarlos.
+pub trait Validate {
+    /// Revisa los datos, y entrega todos los problemas encontrados.
+    fn validate(&self) -> Result<(), ApiError>;
+}
+
+/// Acumulador de los problemas encontrados al validar un esquema.
+///
+/// Cada regla recibe el nombre del campo y su valor; un valor en `None` significa que el campo no
+/// viene o no cambia, así que no se revisa.
+///
+/// # Ejemplo:
+/// ```ignore
+/// Validator::new()
+///     .required("name", Some(&self.name))
+///     .max_length("name", Some(&self.name), MAX_NAME_LENGTH)
+///     .email("email", self.email.as_deref())
+///     .finish()
+/// ```
+#[derive(Default)]
+pub struct Validator {
+    errors: Vec<FieldError>,
+}
+
+impl Validator {
+    /// Crea un validador sin problemas.
+    pub fn new() -> Validator {
+        Validator::default()
+    }
+
+    /// Agrega un problema en un campo.
+    pub fn error(mut self, field: &str, code: &'static str, message: impl Into<String>) -> Self {
+        self.errors.push(FieldError {
+            field: field.to_owned(),
+            code,
+            message: message.into(),
+        });
+        self
+    }
+
+    /// El texto no puede estar vacío ni tener solo espacios.
+    pub fn required(self, field: &str, value: Option<&str>) -> Self {
+        match value {
+            Some(value) if value.trim().is_empty() => {
+                self.error(field, "required", "No puede estar vacío")
+            }
+            _ => self,
+        }
+    }
+
+    /// El texto no puede tener más de `max` caracteres.
+    pub fn max_length(self, field: &str, value: Option<&str>, max: usize) -> Self {
+        match value {
+            Some(value) if value.chars().count() > max => self.error(
+                field,
+                "too_long",
+                format!("No puede tener más de {max} caracteres"),
+            ),
+            _ => self,
+        }
+    }
+
+    /// El texto debe ser un email, como `nombre@dominio.cl`.
+    pub fn email(self, field: &str, value: Option<&str>) -> Self {
+        match value {
+            Some(value) if !is_email(value) => {
+                self.error(field, "invalid_email", "No es un email válido")
+            }
+            _ => self,
+        }
+    }
+
+    /// El texto debe ser un nombre de usuario de GitHub: hasta 39 letras, números o guiones, sin
+    /// guiones al principio, al final ni seguidos.
+    pub fn github(self, field: &str, value: Option<&str>) -> Self {
+        match value {
+            Some(value) if !is_github_handle(value) => self.error(
+                field,
+                "invalid_github",
+                "No es un nombre de usuario de GitHub válido",
+            ),
+            _ => self,
+        }
+    }
+
+    /// El texto debe ser una fecha `AAAA-MM-DD` que ya pasó, como un cumpleaños.
+    pub fn past_date(self, field: &str, value: Option<&str>) -> Self {
+        let Some(value) = value else {
+            return self;
+        };
+
+        match NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+            Err(_) => self.error(
+                field,
+                "invalid_date",
+                "No es una fecha válida con el formato AAAA-MM-DD",
+            ),
+            Ok(date) if date > chrono::Utc::now().date_naive() => {
+                self.error(field, "future_date", "No puede ser una fecha futura")
+            }
+            Ok(_) => self,
+        }
+    }
+
+    /// La fecha (UTC) debe ser futura.
+    pub fn future(self, field: &str, value: Option<NaiveDateTime>) -> Self {
+        match value {
+            Some(value) if value <= chrono::Utc::now().naive_utc() => {
+                self.error(field, "past_date", "Debe ser una fecha futura")
+            }
+            _ => self,
+        }
+    }
+
+    /// El número debe estar entre `min` y `max`, inclusive.
+    pub fn range(self, field: &str, value: Option<i64>, min: i64, max: i64) -> Self {
+        match value {
+            Some(value) if !(min..=max).contains(&value) => self.error(
+                field,
+                "out_of_range",
+                format!("Debe estar entre {min} y {max}"),
+            ),
+            _ => self,
+        }
+    }
+
+    /// Termina la validación, fallando si se encontró algún problema.
+    pub fn finish(self) -> Result<(), ApiError> {
+        if self.errors.is_empty() {
+            Ok(())
+        } else {
+            Err(ApiError::Validation(self.errors))
+        }
+    }
+}
+
+/// Revisa que un texto tenga la forma de un email.
+///
+/// No es una validación completa del RFC 5322, pero descarta los errores típicos: sin `@`, sin
+/// dominio, con espacios, etc.
+fn is_email(value: &str) -> bool {
+    let Some((local, domain)) = value.split_once('@') else {
+        return false;
+    };
+
+    value.len() <= 254
+        && !local.is_empty()
+        && !value.chars().any(char::is_whitespace)
+        && !domain.contains('@')
+        && domain.contains('.')
+        && !domain.starts_with('.')
+        && !domain.ends_with('.')
+        && !domain.contains("..")
+}
+
+/// Revisa que un texto cumpla las reglas de GitHub para los nombres de usuario.
+fn is_github_handle(value: &str) -> bool {
+    (1..=39).contains(&value.len())
+        && value.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+        && !value.starts_with('-')
+        && !value.ends_with('-')
+        && !value.contains("--")
+}
+
+/// Convierte los errores al leer un cuerpo JSON en errores de validación.
+///
+/// Los demás errores (un cuerpo demasiado grande, un tipo de contenido incorrecto) se entregan tal
+/// cual los genera Actix.
+pub fn json_error_handler(err: JsonPayloadError, _req: &HttpRequest) -> actix_web::Error {
+    match err {
+        JsonPayloadError::Deserialize(err) => {
+            let message = err.to_string();
+            let (field, code) = field_of(&message).unwrap_or(("body", "invalid"));
+
+            ApiError::Validation(vec![FieldError {
+                field: field.to_owned(),
+                code,
+                message: format!("No se pudo leer el cuerpo de la petición: {message}"),
+            }])
+            .into()
+        }
+        err => err.into(),
+    }
+}
+
+/// Convierte los errores al leer los parámetros de la URL en errores de validación.
+pub fn query_error_handler(err: QueryPayloadError, _req: &HttpRequest) -> actix_web::Error {
+    match err {
+        QueryPayloadError::Deserialize(err) => {
+            let message = err.to_string();
+            let (field, code) = field_of(&message).unwrap_or(("query", "invalid"));
+
+            ApiError::Validation(vec![FieldError {
+                field: field.to_owned(),
+                code,
+                message: format!("No se pudieron leer los parámetros de la URL: {message}"),
+            }])
+            .into()
+        }
+        err => err.into(),
+    }
+}
+
+/// Campo y código de un error de serde, si el mensaje los indica.
+///
+/// Serde no entrega el campo de forma estructurada, pero lo incluye en los mensajes de los campos
+/// obligatorios y desconocidos, por ejemplo "missing field \`name\` at line 1 column 2".
+fn field_of(message: &str) -> Option<(&str, &'static str)> {
+    let (code, rest) = if let Some(rest) = message.strip_prefix("missing field `") {
+        ("required", rest)
+    } else if let Some(rest) = message.strip_prefix("unknown field `") {
+        ("unknown_field", rest)
+    } else {
+        return None;
+    };
+
+    rest.split_once('`').map(|(field, _)| (field, code))
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{
+        http::StatusCode,
+        test::{call_service, init_service, read_body_json, TestRequest},
+        web, App, HttpResponse,
+    };
+    use chrono::{Duration, Utc};
+    use serde::Deserialize;
+    use serde_json::Value;
+
+    use super::{
+        field_of, is_email, is_github_handle, json_error_handler, query_error_handler, Validator,
+    };
+    use crate::v1::responders::errors::ApiError;
+
+    /// Campos y códigos de los problemas encontrados por una validación.
+    fn problems(result: Result<(), ApiError>) -> Vec<(String, &'static str)> {
+        match result {
+            Ok(()) => Vec::new(),
+            Err(ApiError::Validation(errors)) => errors
+                .into_iter()
+                .map(|error| (error.field, error.code))
+                .collect(),
+            Err(err) => panic!("Se esperaba un error de validación, pero se obtuvo {err:?}"),
+        }
+    }
+
+    /// Cuerpo de prueba, que rechaza los campos desconocidos.
+    #[derive(Deserialize)]
+    #[serde(deny_unknown_fields)]
+    struct Body {
+        name: String,
+        age: u8,
+    }
+
+    /// Handler de prueba que solo lee el cuerpo.
+    async fn read_body(body: web::Json<Body>) -> HttpResponse {
+        HttpResponse::Ok().body(format!("{} {}", body.name, body.age))
+    }
+
+    /// Handler de prueba que solo lee los parámetros de la URL.
+    async fn read_query(query: web::Query<Body>) -> HttpResponse {
+        HttpResponse::Ok().body(format!("{} {}", query.name, query.age))
+    }
+
+    /// Los emails necesitan exactamente un `@`, algo antes de él y un dominio con punto.
+    #[test]
+    fn emails() {
+        for email in ["nombre@dominio.cl", "a.b+c@sub.dominio.cl", "a@b.c"] {
+            assert!(is_email(email), "{email}");
+        }
+
+        let long = format!("{}@dominio.cl", "a".repeat(250));
+        for email in [
+            "",
+            "nombre",
+            "nombre@",
+            "@dominio.cl",
+            "nombre@@dominio.cl",
+            "nombre@otro@dominio.cl",
+            "nombre@dominio",
+            "nombre@.dominio.cl",
+            "nombre@dominio.cl.",
+            "nombre@dominio..cl",
+            "nom bre@dominio.cl",
+            "nombre@dominio.cl ",
+            long.as_str(),
+        ] {
+            assert!(!is_email(email), "{email}");
+        }
+    }
+
+    /// Los nombres de GitHub tienen hasta 39 letras, números o guiones, sin guiones al principio,
+    /// al final ni seguidos.
+    #[test]
+    fn github_handles() {
+        let longest = "a".repeat(39);
+        for handle in [
+            "octocat",
+            "a",
+            "exdev-utem",
+            "a-b-c",
+            "User123",
+            longest.as_str(),
+        ] {
+            assert!(is_github_handle(handle), "{handle}");
+        }
+
+        let too_long = "a".repeat(40);
+        for handle in [
+            "",
+            "-octocat",
+            "octocat-",
+            "-",
+            "exdev--utem",
+            "exdev_utem",
+            "exdev.utem",
+            "ñandú",
+            "octo cat",
+            too_long.as_str(),
+        ] {
+            assert!(!is_github_handle(handle), "{handle}");
+        }
+    }
+
+    /// Una fecha pasada puede ser la de hoy, pero no la de mañana, y debe tener el formato
+    /// `AAAA-MM-DD`.
+    #[test]
+    fn past_dates() {
+        let today = Utc::now().date_naive();
+        let check =
+            |value: &str| problems(Validator::new().past_date("birthday", Some(value)).finish());
+
+        assert!(check(&today.to_string()).is_empty());
+        assert!(check(&(today - Duration::days(1)).to_string()).is_empty());
+        assert!(check("2000-02-29").is_empty());
+        assert_eq!(
+            check(&(today + Duration::days(1)).to_string()),
+            [(String::from("birthday"), "future_date")]
+        );
+
+        for value in ["2001-02-29", "01-01-2000", "2000/01/01", "ayer", ""] {
+            assert_eq!(
+                check(value),
+                [(String::from("birthday"), "invalid_date")],
+                "{value}"
+            );
+        }
+        assert!(problems(Validator::new().past_date("birthday", None).finish()).is_empty());
+    }
+
+    /// Una fecha futura debe ser posterior a este momento.
+    #[test]
+    fn future_dates() {
+        let now = Utc::now().naive_utc();
+        let check = |value| problems(Validator::new().future("expires_at", value).finish());
+
+        assert!(check(Some(now + Duration::hours(1))).is_empty());
+        assert!(check(None).is_empty());
+        assert_eq!(
+            check(Some(now - Duration::minutes(1))),
+            [(String::from("expires_at"), "past_date")]
+        );
+    }
+
+    /// Los límites de un rango están incluidos.
+    #[test]
+    fn ranges() {
+        let check = |value| problems(Validator::new().range("rate_limit", value, 1, 10).finish());
+
+        for value in [Some(1), Some(5), Some(10), None] {
+            assert!(check(value).is_empty(), "{value:?}");
+        }
+        for value in [0, 11, -1, i64::MAX] {
+            assert_eq!(
+                check(Some(value)),
+                [(String::from("rate_limit"), "out_of_range")],
+                "{value}"
+            );
+        }
+    }
+
+    /// El validador junta todos los problemas, en el orden de las reglas.
+    #[test]
+    fn validator_collects_every_problem() {
+        let result = Validator::new()
+            .required("name", Some("   "))
+            .max_length("name", Some("ñandú"), 4)
+            .max_length("description", Some("ñandú"), 5)
+            .email("email", Some("nombre@@dominio.cl"))
+            .github("github", Some("-octocat"))
+            .email("other", None)
+            .finish();
+
+        assert_eq!(
+            problems(result),
+            [
+                (String::from("name"), "required"),
+                (String::from("name"), "too_long"),
+                (String::from("email"), "invalid_email"),
+                (String::from("github"), "invalid_github"),
+            ]
+        );
+    }
+
+    /// El campo se saca de los mensajes de serde de los campos obligatorios y desconocidos.
+    #[test]
+    fn field_of_serde_messages() {
+        assert_eq!(
+            field_of("missing field `name` at line 1 column 2"),
+            Some(("name", "required"))
+        );
+        assert_eq!(
+            field_of("unknown field `nickname`, expected `name` or `age`"),
+            Some(("nickname", "unknown_field"))
+        );
+        assert_eq!(field_of("invalid type: string \"a\", expected u8"), None);
+        assert_eq!(field_of("missing field `name"), None);
+    }
+
+    /// Los cuerpos que no se pueden leer responden un error de validación con el campo, si serde
+    /// lo indica; los demás errores se responden tal cual.
+    #[actix_web::test]
+    async fn json_errors_are_validation_errors() {
+        let service = init_service(
+            App::new()
+                .app_data(web::JsonConfig::default().error_handler(json_error_handler))
+                .route("/", web::post().to(read_body)),
+        )
+        .await;
+
+        for (body, field, code) in [
+            (r#"{"age": 20}"#, "name", "required"),
+            (
+                r#"{"name": "a", "age": 20, "nick": "b"}"#,
+                "nick",
+                "unknown_field",
+            ),
+            (r#"{"name": "a", "age": 300}"#, "body", "invalid"),
+            (r#"{"name": "a", "#, "body", "invalid"),
+        ] {
+            let req = TestRequest::post()
+                .uri("/")
+                .insert_header(("Content-Type", "application/json"))
+                .set_payload(body)
+                .to_request();
+            let res = call_service(&service, req).await;
+            assert_eq!(res.status(), StatusCode::UNPROCESSABLE_ENTITY, "{body}");
+
+            let res: Value = read_body_json(res).await;
+            assert_eq!(res["errors"][0]["field"], field, "{body}");
+            assert_eq!(res["errors"][0]["code"], code, "{body}");
+        }
+
+        let req = TestRequest::post()
+            .uri("/")
+            .insert_header(("Content-Type", "text/plain"))
+            .set_payload(r#"{"name": "a", "age": 20}"#)
+            .to_request();
+        let res = call_service(&service, req).await;
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    }
+
+    /// Los parámetros de la URL que no se pueden leer responden un error de validación.
+    #[actix_web::test]
+    async fn query_errors_are_validation_errors() {
+        let service = init_service(
+            App::new()
+                .app_data(web::QueryConfig::default().error_handler(query_error_handler))
+                .route("/", web::get().to(read_query)),
+        )
+        .await;
+
+        for (query, field, code) in [
+            ("age=20", "name", "required"),
+            ("name=a&age=20&nick=b", "nick", "unknown_field"),
+            ("name=a&age=muchos", "query", "invalid"),
+        ] {
+            let req = TestRequest::get().uri(&format!("/?{query}")).to_request();
+            let res = call_service(&service, req).await;
+            assert_eq!(res.status(), StatusCode::UNPROCESSABLE_ENTITY, "{query}");
+
+            let res: Value = read_body_json(res).await;
+            assert_eq!(res["errors"][0]["field"], field, "{query}");
+            assert_eq!(res["errors"][0]["code"], code, "{query}");
+        }
+
+        let req = TestRequest::get().uri("/?name=a&age=20").to_request();
+        assert_eq!(call_service(&service, req).await.status(), StatusCode::OK);
+    }
+}